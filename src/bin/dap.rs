@@ -0,0 +1,26 @@
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+
+use rust_6502_emulator::dap::{read_message, DapServer};
+
+// Debug adapter protocol server: requests on stdin, responses and events on stdout
+fn main() {
+    let (sender, requests) = mpsc::channel();
+
+    // reading happens on its own thread so a pause can reach a running program
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        while let Ok(Some(message)) = read_message(&mut stdin) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut server = DapServer::new(io::stdout());
+    if let Err(e) = server.serve(&requests) {
+        eprintln!("debug adapter failed {}", e);
+        std::process::exit(1);
+    }
+}
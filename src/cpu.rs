@@ -1,7 +1,10 @@
 use crate::Memory;
 use crate::op_code::OpCodeHandler;
 use crate::op_code::OpcodeTable;
-use crate::debugger::Debugger;
+use crate::op_code::AddressingMode;
+use crate::op_code::{is_branch, CYCLES};
+use crate::debugger::{Debugger, HistoryEntry, MinCPU, StopReason};
+use crate::memory::{Access, AccessKind};
 
 
 pub struct CPU {
@@ -11,10 +14,23 @@ pub struct CPU {
     pub sp: u8,
     pub pc: u16,
     pub status: u8,
+    // cycles run since the CPU was created or reset
+    pub cycles: u64,
+    // IRQ is a level: it stays asserted until whatever raised it clears it
+    pub irq_pending: bool,
+    // NMI is an edge, latched until serviced
+    pub nmi_pending: bool,
+    // the devices' NMI line as last seen, to catch its edges
+    nmi_line: bool,
     pub debugger: Debugger,
     opcode_table: OpcodeTable,
 }
 
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+const INTERRUPT_CYCLES: u64 = 7;
+
 
 pub const NEGATIVE_FLAG: u8 = 0b1000_0000; // Bit 7
 pub const OVERFLOW_FLAG: u8 = 0b0100_0000; // Bit 6
@@ -87,12 +103,6 @@ impl CPU {
         self.set_flag (flag, false);
     }
 }
-/*
-impl CPU {
-    pub fn get_operand_addr (&mut self, )
-}
-
-*/
 
 // constructor for cpu
 impl CPU {
@@ -106,6 +116,10 @@ impl CPU {
             sp: 0xff,
             pc: 0x0000,
             status: 0,
+            cycles: 0,
+            irq_pending: false,
+            nmi_pending: false,
+            nmi_line: false,
             debugger,
             opcode_table,
         }
@@ -118,10 +132,94 @@ impl CPU {
         self.sp = 0xff;
         self.pc = 0x0000;
         self.status = 0;
+        self.cycles = 0;
+        self.irq_pending = false;
+        self.nmi_pending = false;
+        self.nmi_line = false;
+    }
+
+    // what the reset line does on a real 6502: interrupts off, three phantom pushes, then PC from $FFFC
+    pub fn reset_to_vector (&mut self, memory: &mut Memory) {
+        self.reset();
+        self.sp = 0xFD;
+        self.set_flag(INTERRUPT_FLAG, true);
+        let lo = memory.read(RESET_VECTOR) as u16;
+        let hi = memory.read(RESET_VECTOR.wrapping_add(1)) as u16;
+        self.pc = (hi << 8) | lo;
     }
 
+    pub fn nmi (&mut self) {
+        self.nmi_pending = true;
+    }
+
+    pub fn set_irq (&mut self, asserted: bool) {
+        self.irq_pending = asserted;
+    }
+
+    // pushes PC and status and jumps through vector, like the hardware does between instructions
+    fn interrupt (&mut self, memory: &mut Memory, vector: u16) {
+        self.push(memory, (self.pc >> 8) as u8);
+        self.push(memory, (self.pc & 0xFF) as u8);
+        self.push(memory, (self.status & !BREAK_FLAG) | 0b0010_0000);
+        self.set_flag(INTERRUPT_FLAG, true);
+
+        let lo = memory.read(vector) as u16;
+        let hi = memory.read(vector.wrapping_add(1)) as u16;
+        self.pc = (hi << 8) | lo;
+        self.cycles += INTERRUPT_CYCLES;
+    }
+
+    /*
+     *  Services a pending NMI, or an IRQ when interrupts are enabled; true if one was taken.
+     *  The IRQ line is held by set_irq or by any attached device. An NMI is latched by nmi, or
+     *  when a device's NMI line goes active.
+     */
+    fn service_interrupts (&mut self, memory: &mut Memory) -> bool {
+        let nmi_line = memory.nmi();
+        if nmi_line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = nmi_line;
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.interrupt(memory, NMI_VECTOR);
+            return true;
+        }
+        if (self.irq_pending || memory.irq()) && self.status & INTERRUPT_FLAG == 0 {
+            self.interrupt(memory, IRQ_VECTOR);
+            return true;
+        }
+        false
+    }
+
+    // whether execute can run opcode rather than panicking on it, which is every documented opcode
+    pub fn implements(&self, opcode: u8) -> bool {
+        self.opcode_table.table[opcode as usize].handler.is_some()
+    }
+
+    /*
+     *  Runs one instruction, or takes a pending interrupt instead, then clocks the attached
+     *  devices. Cycles come from the opcode table plus one for a taken branch and another when
+     *  it lands on a different page.
+     */
     pub fn execute(&mut self, memory: &mut Memory) {
+        self.execute_or_interrupt(memory);
+    }
+
+    // execute, saying whether an interrupt was taken in place of the instruction
+    fn execute_or_interrupt(&mut self, memory: &mut Memory) -> bool {
+        let start = self.cycles;
+        let interrupted = self.service_interrupts(memory);
+        if !interrupted {
+            self.execute_instruction(memory);
+        }
+        memory.tick(self.cycles - start);
+        interrupted
+    }
+
+    fn execute_instruction(&mut self, memory: &mut Memory) {
         // Fetch the opcode from memory
+        let pc = self.pc;
         let op_code = memory.read(self.pc);
 
         // Retrieve the OpcodeEntry from the table
@@ -136,176 +234,206 @@ impl CPU {
         } else {
             panic!("Invalid opcode: [{:#X}] at address: [{:#X}]", op_code, self.pc);
         }
-    }
-
-}
-
 
-
-/*
- * LOADING
- * <LDA, LDX, LDY>
- */
-
-impl CPU {
-
-    fn set_ld_flags (&mut self, reg: u8) {
-        self.set_flag(ZERO_FLAG, reg == 0);
-        self.set_flag(NEGATIVE_FLAG, (reg & 0b1000_0000) != 0);
+        self.cycles += CYCLES[op_code as usize] as u64;
+        let fall_through = pc.wrapping_add(2);
+        if is_branch(op_code) && self.pc != fall_through {
+            self.cycles += if self.pc & 0xFF00 != fall_through & 0xFF00 { 2 } else { 1 };
+        }
     }
 
-    // LDA
-    pub fn lda_immediate(&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1);
-        self.a = value;
-        self.set_ld_flags (self.a);
-        self.pc += 2;
-    }
+    /*
+     *  Executes one instruction, then asks the debugger whether to stop: a watchpoint hit by
+     *  the instruction that just ran, or a breakpoint on the one about to run.
+     */
+    pub fn step(&mut self, memory: &mut Memory) -> Option<StopReason> {
+        let tracing = self.debugger.wants_bus_trace();
+        let before = MinCPU::from_cpu(self);
+        let pc = self.pc;
+        let opcode = memory.peek(pc);
+        let start = self.cycles;
+
+        memory.set_tracing(tracing);
+        let interrupted = self.execute_or_interrupt(memory);
+        if self.debugger.profiler.is_some() {
+            let after = MinCPU::from_cpu(self);
+            let cycles = self.cycles - start;
+            if let Some(profiler) = &mut self.debugger.profiler {
+                profiler.record(pc, opcode, interrupted, cycles, &after);
+            }
+        }
 
+        if tracing {
+            let accesses = memory.take_accesses();
+            let hit = self.debugger.check_watchpoints(pc, opcode, &accesses);
 
-    pub fn lda_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
+            if self.debugger.history.is_recording() {
+                let writes: Vec<Access> = accesses.into_iter().filter(|access| access.kind == AccessKind::Write).collect();
+                if writes.iter().any(|write| memory.is_device(write.addr)) {
+                    self.debugger.history.clear();
+                } else {
+                    self.debugger.history.push(HistoryEntry { before, after: MinCPU::from_cpu(self), cycles: start, opcode, writes });
+                }
+            }
+            if let Some(hit) = hit {
+                return Some(StopReason::Watchpoint(hit));
+            }
+        }
 
-        self.a = value;
-        self.set_ld_flags (self.a);
-        self.pc += 2;
+        let state = MinCPU::from_cpu(self);
+        self.debugger.check_breakpoints(&state, memory).map(StopReason::Breakpoint)
     }
 
-    pub fn lda_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = memory.read(addr);
-
-        self.a = value;
-        self.set_ld_flags (self.a);
-        self.pc += 3;
-    }
+    // undoes the last recorded instruction, returning what it changed; history only holds RAM writes
+    pub fn step_back(&mut self, memory: &mut Memory) -> Option<HistoryEntry> {
+        let entry = self.debugger.history.pop()?;
 
-    // LDX
-    pub fn ldx_immediate(&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1);
+        for write in entry.writes.iter().rev() {
+            memory.poke(write.addr, write.old);
+        }
 
-        self.x = value;
-        self.set_ld_flags (self.x);
+        let state = entry.before;
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.sp = state.sp;
+        self.pc = state.pc;
+        self.status = state.status;
+        self.cycles = entry.cycles;
+        Some(entry)
+    }
+
+    /*
+     *  Steps back until a breakpoint matches the restored state, or an undone write hits a
+     *  write watchpoint. None means the history ran out first.
+     */
+    pub fn reverse_continue(&mut self, memory: &mut Memory) -> Option<StopReason> {
+        while let Some(entry) = self.step_back(memory) {
+            if let Some(hit) = self.debugger.check_watchpoints(entry.before.pc, entry.opcode, &entry.writes) {
+                return Some(StopReason::Watchpoint(hit));
+            }
 
-        self.pc += 2;
+            let state = MinCPU::from_cpu(self);
+            if let Some(id) = self.debugger.check_breakpoints(&state, memory) {
+                return Some(StopReason::Breakpoint(id));
+            }
+        }
+        None
     }
+}
 
-    pub fn ldx_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-
-        self.x = value;
-        self.set_ld_flags (self.x);
-
-        self.pc += 2;
-    }
 
-    pub fn ldx_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = memory.read(addr);
 
-        self.x = value;
-        self.set_ld_flags (self.x);
+/*
+ *  OPERANDS
+ *  Every instruction takes its operand through one of the addressing modes. Zero page indexing
+ *  and zero page pointers wrap within page zero, and reads that index across a page take a
+ *  cycle more.
+ */
 
-        self.pc += 3;
+impl CPU {
+    fn operand_word (&mut self, memory: &mut Memory) -> u16 {
+        let lo = memory.read(self.pc.wrapping_add(1)) as u16;
+        let hi = memory.read(self.pc.wrapping_add(2)) as u16;
+        (hi << 8) | lo
+    }
+
+    // a pointer in page zero, whose high byte wraps back to $00
+    fn zero_page_pointer (memory: &mut Memory, pointer: u8) -> u16 {
+        let lo = memory.read(pointer as u16) as u16;
+        let hi = memory.read(pointer.wrapping_add(1) as u16) as u16;
+        (hi << 8) | lo
+    }
+
+    // the effective address, and whether indexing crossed a page
+    fn operand_addr (&mut self, memory: &mut Memory, mode: AddressingMode) -> (u16, bool) {
+        let indexed = |base: u16, index: u8| {
+            let addr = base.wrapping_add(index as u16);
+            (addr, addr & 0xFF00 != base & 0xFF00)
+        };
+        match mode {
+            AddressingMode::Immediate => (self.pc.wrapping_add(1), false),
+            AddressingMode::ZeroPage => (memory.read(self.pc.wrapping_add(1)) as u16, false),
+            AddressingMode::ZeroPage_X => (memory.read(self.pc.wrapping_add(1)).wrapping_add(self.x) as u16, false),
+            AddressingMode::ZeroPage_Y => (memory.read(self.pc.wrapping_add(1)).wrapping_add(self.y) as u16, false),
+            AddressingMode::Absolute => (self.operand_word(memory), false),
+            AddressingMode::Absolute_X => indexed(self.operand_word(memory), self.x),
+            AddressingMode::Absolute_Y => indexed(self.operand_word(memory), self.y),
+            AddressingMode::Indirect_X => {
+                let pointer = memory.read(self.pc.wrapping_add(1)).wrapping_add(self.x);
+                (Self::zero_page_pointer(memory, pointer), false)
+            }
+            AddressingMode::Indirect_Y => {
+                let pointer = memory.read(self.pc.wrapping_add(1));
+                indexed(Self::zero_page_pointer(memory, pointer), self.y)
+            }
+            AddressingMode::Implied => panic!("Implied instructions have no operand"),
+        }
     }
 
-    // LDY
-    pub fn ldy_immediate(&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1);
-
-
-        self.y = value;
-        self.set_ld_flags (self.y);
-        self.pc += 2;
+    // the operand's value, for instructions that only read it
+    fn read_operand (&mut self, memory: &mut Memory, mode: AddressingMode) -> u8 {
+        let (addr, crossed) = self.operand_addr(memory, mode);
+        if crossed {
+            self.cycles += 1;
+        }
+        memory.read(addr)
     }
 
-    pub fn ldy_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-
-        self.y = value;
-        self.set_ld_flags (self.y);
-        self.pc += 2;
+    fn advance (&mut self, mode: AddressingMode) {
+        self.pc = self.pc.wrapping_add(1 + mode.operand_size());
     }
 
-    pub fn ldy_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = memory.read(addr);
+    fn set_zn (&mut self, value: u8) {
+        self.set_flag(ZERO_FLAG, value == 0);
+        self.set_flag(NEGATIVE_FLAG, value & 0b1000_0000 != 0);
+    }
 
-        self.y = value;
-        self.set_ld_flags (self.y);
-        self.pc += 3;
+    fn carry (&self) -> u8 {
+        self.status & CARRY_FLAG
     }
 }
 
-// * STORING:
-//  * <STA, STX, STY>
-
+/*
+ * LOADING AND STORING
+ * <LDA, LDX, LDY, STA, STX, STY>
+ */
 
 impl CPU {
-    fn st_(&mut self, addr: u16, memory: &mut Memory, value: u8) {
-        memory.write(addr, value);
+    pub fn lda (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.a = self.read_operand(memory, mode);
+        self.set_zn(self.a);
+        self.advance(mode);
     }
 
-    pub fn sta_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = self.a; // read the CPU register
-        self.st_(addr, memory, value);
-
-        self.pc += 2;
+    pub fn ldx (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.x = self.read_operand(memory, mode);
+        self.set_zn(self.x);
+        self.advance(mode);
     }
 
-    pub fn sta_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = self.a;
-        self.st_(addr, memory, value);
-
-        self.pc += 3;
+    pub fn ldy (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.y = self.read_operand(memory, mode);
+        self.set_zn(self.y);
+        self.advance(mode);
     }
 
-    pub fn stx_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = self.x;
-        self.st_(addr, memory, value);
-        self.pc += 2;
+    fn store (&mut self, memory: &mut Memory, mode: AddressingMode, value: u8) {
+        let (addr, _) = self.operand_addr(memory, mode);
+        memory.write(addr, value);
+        self.advance(mode);
     }
 
-    pub fn stx_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = self.x;
-        self.st_(addr, memory, value);
-
-        self.pc += 3;
+    pub fn sta (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.store(memory, mode, self.a);
     }
 
-    pub fn sty_zero_page(&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = self.y;
-        self.st_(addr, memory, value);
-
-        self.pc += 2;
+    pub fn stx (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.store(memory, mode, self.x);
     }
 
-    pub fn sty_absolute(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1) as u16;
-        let hi = memory.read(self.pc + 2) as u16;
-        let addr = (hi << 8) | lo;
-        let value = self.y;
-        self.st_(addr, memory, value);
-
-        self.pc += 3;
+    pub fn sty (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.store(memory, mode, self.y);
     }
 }
 
@@ -315,456 +443,322 @@ impl CPU {
 */
 
 impl CPU {
-    pub fn tax(&mut self) {
+    pub fn tax (&mut self) {
         self.x = self.a;
-        self.set_flag(ZERO_FLAG, self.x == 0);
-        self.set_flag(NEGATIVE_FLAG, self.x & 0b1000_0000 != 0);
-
-        self.pc += 1;
+        self.set_zn(self.x);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn tay(&mut self) {
+    pub fn tay (&mut self) {
         self.y = self.a;
-        self.set_flag(ZERO_FLAG, self.y == 0);
-        self.set_flag(NEGATIVE_FLAG, self.y & 0b1000_0000 != 0);
-
-        self.pc += 1;
+        self.set_zn(self.y);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn tsx(&mut self) {
+    pub fn tsx (&mut self) {
         self.x = self.sp;
-        self.set_flag(ZERO_FLAG, self.x == 0);
-        self.set_flag(NEGATIVE_FLAG, self.x & 0b1000_0000 != 0);
-
-        self.pc += 1;
+        self.set_zn(self.x);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn txa(&mut self) {
+    pub fn txa (&mut self) {
         self.a = self.x;
-        self.set_flag(ZERO_FLAG, self.a == 0);
-        self.set_flag(NEGATIVE_FLAG, self.a & 0b1000_0000 != 0);
-
-        self.pc += 1;
+        self.set_zn(self.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn txs(&mut self) {
+    // the only transfer that leaves the flags alone
+    pub fn txs (&mut self) {
         self.sp = self.x;
-
-        self.pc += 1;
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn tya(&mut self) {
+    pub fn tya (&mut self) {
         self.a = self.y;
-        self.set_flag(ZERO_FLAG, self.a == 0);
-        self.set_flag(NEGATIVE_FLAG, self.a & 0b1000_0000 != 0);
-
-        self.pc += 1;
+        self.set_zn(self.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 }
 
-
 /*
- * BRANCHING
- * <JMP, JNE, JSR, RTS, RTI, BCC, BCS, BEQ, BMI, BPL, BVC, BVS>
+ * JUMPS AND BRANCHES
+ * <JMP, JSR, BCC, BCS, BEQ, BMI, BNE, BPL, BVC, BVS>
 */
 
 impl CPU {
     pub fn jmp_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-
-        let addr = ((hi as u16) << 8) | lo as u16;
-        self.pc = addr;
+        self.pc = self.operand_word(memory);
     }
 
+    // the pointer's high byte comes from the start of its page when it sits at $xxFF, as on the NMOS part
     pub fn jmp_indirect (&mut self, memory: &mut Memory) {
-        let pointer_hi = memory.read (self.pc + 1);
-        let pointer_lo = memory.read (self.pc + 2);
-        let pointer = ((pointer_hi as u16) << 8) | pointer_lo as u16;
-
-        let target_hi = memory.read (pointer);
-        let target_lo = memory.read ((pointer & 0xFF00) | (pointer + 1 & 0x00FF));
-        let target = ((target_hi as u16) << 8) | target_lo as u16;
-
-        self.pc = target;
+        let pointer = self.operand_word(memory);
+        let lo = memory.read(pointer) as u16;
+        let hi = memory.read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF)) as u16;
+        self.pc = (hi << 8) | lo;
     }
 
-    pub fn jsr(&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let target = ((hi as u16) << 8) | lo as u16;
-
-        let return_addr = self.pc + 2;
-
-        memory.write(0x0100 + self.sp as u16, (return_addr >> 8) as u8);
-        self.sp = self.sp.wrapping_sub(1);
-
-        memory.write(0x0100 + self.sp as u16, (return_addr & 0xFF) as u8);
-        self.sp = self.sp.wrapping_sub(1);
-
+    // pushes the address of its own last byte, which RTS steps past
+    pub fn jsr (&mut self, memory: &mut Memory) {
+        let target = self.operand_word(memory);
+        let return_addr = self.pc.wrapping_add(2);
+        self.push(memory, (return_addr >> 8) as u8);
+        self.push(memory, (return_addr & 0xFF) as u8);
         self.pc = target;
     }
 
-    pub fn bcs (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b0000_0001 != 0 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
+    // offsets count from the instruction after the branch
+    fn branch (&mut self, memory: &mut Memory, taken: bool) {
+        let offset = memory.read(self.pc.wrapping_add(1)) as i8;
+        self.pc = self.pc.wrapping_add(2);
+        if taken {
+            self.pc = self.pc.wrapping_add(offset as u16);
         }
     }
 
     pub fn bcc (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b0000_0001 == 0 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+        self.branch(memory, self.status & CARRY_FLAG == 0);
     }
 
-    pub fn beq (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b0000_0010 == 1 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+    pub fn bcs (&mut self, memory: &mut Memory) {
+        self.branch(memory, self.status & CARRY_FLAG != 0);
     }
 
-    pub fn bmi (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b1000_0000 == 1 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+    pub fn beq (&mut self, memory: &mut Memory) {
+        self.branch(memory, self.status & ZERO_FLAG != 0);
     }
 
     pub fn bne (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b0000_0010 == 0 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+        self.branch(memory, self.status & ZERO_FLAG == 0);
     }
 
-    pub fn bpl (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b1000_0000 == 0 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+    pub fn bmi (&mut self, memory: &mut Memory) {
+        self.branch(memory, self.status & NEGATIVE_FLAG != 0);
     }
 
-    pub fn bvc (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
-
-        if self.status & 0b0100_0000 == 0 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+    pub fn bpl (&mut self, memory: &mut Memory) {
+        self.branch(memory, self.status & NEGATIVE_FLAG == 0);
     }
 
     pub fn bvs (&mut self, memory: &mut Memory) {
-        let value: i8 = memory.read(self.pc + 1) as i8;
+        self.branch(memory, self.status & OVERFLOW_FLAG != 0);
+    }
 
-        if self.status & 0b0100_0000 == 1 {
-            let new_pc = (self.pc as i16) + (value as i16);
-            self.pc = new_pc as u16;
-        } else {
-            self.pc += 2;
-        }
+    pub fn bvc (&mut self, memory: &mut Memory) {
+        self.branch(memory, self.status & OVERFLOW_FLAG == 0);
     }
 }
 
+/*
+ * COMPARING
+ * <CMP, CPX, CPY>
+*/
 
 impl CPU {
-    pub fn cmp_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr) as u16;
-        let result = self.a.wrapping_sub(value as u8);
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.a as u16) >= value);           // Carry flag if A >= value
-
-        self.pc += 3;
-   }
-
-    pub fn cmp_immediate (&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1) as u16;
-        let result = self.a.wrapping_sub(value as u8);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.a as u16) >= value);           // Carry flag if A >= value
-
-        self.pc += 3;
-   }
-
-
-    pub fn cmp_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
-
-        let result = self.a.wrapping_sub(value as u8);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.a as u16) >= value);           // Carry flag if A >= value
-
-        self.pc += 3;
-   }
-
-    pub fn cpy_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-        let result = self.y.wrapping_sub(value);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.y as u16) >= value as u16); // Set carry flag if Y >= value
-
-        self.pc += 3;
+    // carry means the register is at least the operand
+    fn compare (&mut self, memory: &mut Memory, mode: AddressingMode, register: u8) {
+        let value = self.read_operand(memory, mode);
+        self.set_flag(CARRY_FLAG, register >= value);
+        self.set_zn(register.wrapping_sub(value));
+        self.advance(mode);
     }
 
-    pub fn cpy_immediate (&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1);
-        let result = self.y.wrapping_sub(value);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.y as u16) >= value as u16); // Set carry flag if Y >= value
-
-        self.pc += 2;
+    pub fn cmp (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.compare(memory, mode, self.a);
     }
 
-
-    pub fn cpy_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
-
-        let result = self.y.wrapping_sub(value as u8);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.y as u16) >= value as u16); // Set carry flag if Y >= value
-
-        self.pc += 3;
+    pub fn cpx (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.compare(memory, mode, self.x);
     }
 
-
-    pub fn cpx_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-
-
-        let result = self.x.wrapping_sub(value as u8);
-
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.x as u16) >= value as u16); // Set carry flag if Y >= value
-
-        self.pc += 2;
+    pub fn cpy (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.compare(memory, mode, self.y);
     }
+}
 
-    pub fn cpx_immediate (&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc + 1);
-
-        let result = self.x.wrapping_sub(value as u8);
+// FLAGS:
+// <CLC, CLD, CLI, CLV, SEC, SED, SEI>
 
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.x as u16) >= value as u16); // Set carry flag if Y >= value
+impl CPU {
+    fn flag_instruction (&mut self, flag: u8, value: bool) {
+        self.set_flag(flag, value);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
+    pub fn clc (&mut self) {
+        self.flag_instruction(CARRY_FLAG, false);
+    }
 
-        self.pc += 2;
+    pub fn cld (&mut self) {
+        self.flag_instruction(DECIMAL_FLAG, false);
     }
 
+    pub fn cli (&mut self) {
+        self.flag_instruction(INTERRUPT_FLAG, false);
+    }
 
-    pub fn cpx_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
+    pub fn clv (&mut self) {
+        self.flag_instruction(OVERFLOW_FLAG, false);
+    }
 
-        let result = self.x.wrapping_sub(value as u8);
+    pub fn sec (&mut self) {
+        self.flag_instruction(CARRY_FLAG, true);
+    }
 
-        self.set_flag(ZERO_FLAG, result == 0);                // Zero flag if A == value
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);   // Negative flag if result is negative
-        self.set_flag(CARRY_FLAG, (self.x as u16) >= value as u16); // Set carry flag if Y >= value
+    pub fn sed (&mut self) {
+        self.flag_instruction(DECIMAL_FLAG, true);
+    }
 
-        self.pc += 3;
+    pub fn sei (&mut self) {
+        self.flag_instruction(INTERRUPT_FLAG, true);
     }
 }
 
-// CLEAR FLAGS:
-// <CLC, CLD, CLI, CLV>
+// BITWISE:
+// <AND, ORA, EOR, BIT>
 
 impl CPU {
-   pub fn clc (&mut self) {
-       self.clear_flag(CARRY_FLAG);
-   }
-    pub fn cld (&mut self) {
-       self.clear_flag(DECIMAL_FLAG);
-   }
-    pub fn cli (&mut self) {
-       self.clear_flag(INTERRUPT_FLAG);
-   }
-    pub fn cly (&mut self) {
-       self.clear_flag(OVERFLOW_FLAG);
-   }
-}
-
-//
-//
-impl CPU {
-
-    // and
-
-    pub fn and_immediate (&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc+1);
-        self.a = self.a & value;
-
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
+    pub fn and (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.a &= self.read_operand(memory, mode);
+        self.set_zn(self.a);
+        self.advance(mode);
     }
 
-    pub fn and_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-        self.a = self.a & value;
+    pub fn ora (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.a |= self.read_operand(memory, mode);
+        self.set_zn(self.a);
+        self.advance(mode);
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
+    pub fn eor (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.a ^= self.read_operand(memory, mode);
+        self.set_zn(self.a);
+        self.advance(mode);
     }
 
-    pub fn and_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
+    // N and V come straight from the operand's top bits
+    pub fn bit (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        let value = self.read_operand(memory, mode);
+        self.set_flag(ZERO_FLAG, value & self.a == 0);
+        self.set_flag(NEGATIVE_FLAG, value & 0b1000_0000 != 0);
+        self.set_flag(OVERFLOW_FLAG, value & 0b0100_0000 != 0);
+        self.advance(mode);
+    }
+}
 
-        self.a = self.a & (value as u8);
+// SHIFTS AND INCREMENTS:
+// <ASL, LSR, ROL, ROR, INC, DEC, INX, INY, DEX, DEY>
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 3;
+impl CPU {
+    fn asl_value (&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY_FLAG, value & 0x80 != 0);
+        value << 1
     }
 
-    // ora
+    fn lsr_value (&mut self, value: u8) -> u8 {
+        self.set_flag(CARRY_FLAG, value & 0x01 != 0);
+        value >> 1
+    }
 
-    pub fn ora_immediate (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-        self.a = self.a & value;
+    fn rol_value (&mut self, value: u8) -> u8 {
+        let carry = self.carry();
+        self.set_flag(CARRY_FLAG, value & 0x80 != 0);
+        (value << 1) | carry
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
+    fn ror_value (&mut self, value: u8) -> u8 {
+        let carry = self.carry();
+        self.set_flag(CARRY_FLAG, value & 0x01 != 0);
+        (value >> 1) | (carry << 7)
     }
 
-    pub fn ora_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
+    // reads the operand, writes back what operation makes of it and sets N and Z from that
+    fn modify (&mut self, memory: &mut Memory, mode: AddressingMode, operation: fn(&mut CPU, u8) -> u8) {
+        let (addr, _) = self.operand_addr(memory, mode);
         let value = memory.read(addr);
-        self.a = self.a & value;
-
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
-     }
-
-    pub fn ora_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
-
-        self.a = self.a | (value as u8);
+        let result = operation(self, value);
+        memory.write(addr, result);
+        self.set_zn(result);
+        self.advance(mode);
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 3;
+    fn modify_accumulator (&mut self, operation: fn(&mut CPU, u8) -> u8) {
+        self.a = operation(self, self.a);
+        self.set_zn(self.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    // eor
+    pub fn asl (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, CPU::asl_value);
+    }
 
-    pub fn eor_immediate (&mut self, memory: &mut Memory) {
-        let value = memory.read(self.pc+1);
-        self.a = self.a ^ value;
+    pub fn lsr (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, CPU::lsr_value);
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
+    pub fn rol (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, CPU::rol_value);
     }
 
-    pub fn eor_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
-        self.a = self.a ^ value;
+    pub fn ror (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, CPU::ror_value);
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 2;
+    pub fn asl_accumulator (&mut self) {
+        self.modify_accumulator(CPU::asl_value);
     }
 
-    pub fn eor_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
+    pub fn lsr_accumulator (&mut self) {
+        self.modify_accumulator(CPU::lsr_value);
+    }
 
-        self.a = self.a ^ (value as u8);
+    pub fn rol_accumulator (&mut self) {
+        self.modify_accumulator(CPU::rol_value);
+    }
 
-        self.set_flag (ZERO_FLAG, value == 0);
-        self.set_flag (NEGATIVE_FLAG, (self.a & 0b1000_000) != 0);
-        self.pc += 3;
-     }
+    pub fn ror_accumulator (&mut self) {
+        self.modify_accumulator(CPU::ror_value);
+    }
 
-    // bit
+    pub fn inc (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, |_, value| value.wrapping_add(1));
+    }
 
-    pub fn bit_zero_page (&mut self, memory: &mut Memory) {
-        let addr = memory.read(self.pc + 1) as u16;
-        let value = memory.read(addr);
+    pub fn dec (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        self.modify(memory, mode, |_, value| value.wrapping_sub(1));
+    }
 
-        self.set_flag (ZERO_FLAG, value as u8 & self.a == 0);
-        self.set_flag (NEGATIVE_FLAG, value & 0b1000_000 != 0);
-        self.set_flag(OVERFLOW_FLAG, value & 0b0100_0000 != 0);
+    pub fn inx (&mut self) {
+        self.x = self.x.wrapping_add(1);
+        self.set_zn(self.x);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        self.pc += 2;
+    pub fn iny (&mut self) {
+        self.y = self.y.wrapping_add(1);
+        self.set_zn(self.y);
+        self.pc = self.pc.wrapping_add(1);
     }
 
-    pub fn bit_absolute (&mut self, memory: &mut Memory) {
-        let lo = memory.read(self.pc + 1);
-        let hi = memory.read(self.pc + 2);
-        let value = ((hi as u16) << 8) | lo as u16;
+    pub fn dex (&mut self) {
+        self.x = self.x.wrapping_sub(1);
+        self.set_zn(self.x);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        self.set_flag (ZERO_FLAG, (value as u8 & self.a) == 0);
-        self.set_flag (NEGATIVE_FLAG, (value as u8 & 0b1000_000) != 0);
-        self.set_flag(OVERFLOW_FLAG, (value as u8) & 0b0100_0000 != 0);
+    pub fn dey (&mut self) {
+        self.y = self.y.wrapping_sub(1);
+        self.set_zn(self.y);
+        self.pc = self.pc.wrapping_add(1);
+    }
 
-        self.pc += 3;
+    pub fn nop (&mut self) {
+        self.pc = self.pc.wrapping_add(1);
     }
 }
 
 // STACK INSTRUCTIONS
-// < PHP, PLP, >
+// < PHA, PLA, PHP, PLP, RTS, BRK, RTI >
 
 impl CPU {
 
@@ -773,67 +767,139 @@ impl CPU {
 
     fn push (&mut self, memory: &mut Memory, value: u8) {
         memory.write(Self::START_STACK + self.sp as u16, value);
-        self.sp -= 1;
+        self.sp = self.sp.wrapping_sub(1);
     }
 
     fn pop (&mut self, memory: &mut Memory) -> u8 {
-        self.sp += 1;
-        memory.read(Self::START_STACK + self.sp as u16 + 1)
+        self.sp = self.sp.wrapping_add(1);
+        memory.read(Self::START_STACK + self.sp as u16)
+    }
+
+    // B and bit 5 only exist on the stack, never in the status register
+    fn pop_status (&mut self, memory: &mut Memory) {
+        self.status = self.pop(memory) & !(BREAK_FLAG | 0b0010_0000);
     }
 
     pub fn php (&mut self, memory: &mut Memory) {
-        self.push (memory, self.status);
+        self.push(memory, self.status | BREAK_FLAG | 0b0010_0000);
+        self.pc = self.pc.wrapping_add(1);
     }
 
     pub fn plp (&mut self, memory: &mut Memory) {
-        self.status = self.pop(memory);
+        self.pop_status(memory);
+        self.pc = self.pc.wrapping_add(1);
     }
 
     pub fn pha (&mut self, memory: &mut Memory) {
-        self.push (memory, self.a);
+        self.push(memory, self.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 
     pub fn pla (&mut self, memory: &mut Memory) {
         self.a = self.pop(memory);
+        self.set_zn(self.a);
+        self.pc = self.pc.wrapping_add(1);
     }
 
     pub fn rts(&mut self, memory: &mut Memory) {
         let lo = self.pop(memory) as u16;
         let hi = self.pop(memory) as u16;
-        self.pc = ((hi << 8) | lo) + 1;
+        self.pc = ((hi << 8) | lo).wrapping_add(1);
+    }
+
+    // like an IRQ with the B flag set; the byte after BRK is skipped
+    pub fn brk(&mut self, memory: &mut Memory) {
+        let ret = self.pc.wrapping_add(2);
+        self.push(memory, (ret >> 8) as u8);
+        self.push(memory, (ret & 0xFF) as u8);
+        self.push(memory, self.status | BREAK_FLAG | 0b0010_0000);
+        self.set_flag(INTERRUPT_FLAG, true);
+
+        let lo = memory.read(IRQ_VECTOR) as u16;
+        let hi = memory.read(IRQ_VECTOR.wrapping_add(1)) as u16;
+        self.pc = (hi << 8) | lo;
     }
 
     pub fn rti(&mut self, memory: &mut Memory) {
-        self.status = self.pop(memory);
+        self.pop_status(memory);
         let lo = self.pop(memory) as u16;
         let hi = self.pop(memory) as u16;
         self.pc = (hi << 8) | lo;
     }
 }
 
-// ARTHIMATIC
-// < ADC, SBC,
+/*
+ *  ARITHMETIC
+ *  <ADC, SBC>
+ *  With the decimal flag set both work on packed BCD the way the NMOS part does: the result
+ *  and carry are decimal, while N, V and Z come out of the binary sum along the way.
+ */
 
 impl CPU {
-    pub fn adc (&mut self, memory: &mut Memory)  {
-        let value = memory.read (self.pc) as u16;
-        let carry: u16 = if (self.status & CARRY_FLAG) != 0 {1} else {0} ;
-        let result = (value + carry) as u8;
-        self.a = result;
-        self.set_flag(ZERO_FLAG, result == 0);
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);
-        let overflow = (!((self.a as u16) ^ (value as u16)) & ((self.a as u16) ^ result as u16) & 0x80) != 0;
-        self.set_flag(OVERFLOW_FLAG, overflow);
-    }
-
-    pub fn sbc (&mut self, memory: &mut Memory)  {
-        let value = memory.read (self.pc) as u16;
-        let carry: u16 = if (self.status & CARRY_FLAG) != 0 {1} else {0} ;
-        let result = (value - (1 - carry)) as u8;
-        self.a = result;
-        self.set_flag(ZERO_FLAG, result == 0);
-        self.set_flag(NEGATIVE_FLAG, (result & 0x80) != 0);
-        let overflow = (!((self.a as u16) ^ (value as u16)) & ((self.a as u16) ^ result as u16) & 0x80) != 0;
-        self.set_flag(OVERFLOW_FLAG, overflow);
+    fn add (&mut self, value: u8) {
+        let carry = self.carry() as u16;
+        let binary = self.a as u16 + value as u16 + carry;
+        self.set_flag(OVERFLOW_FLAG, (!(self.a ^ value) & (self.a ^ binary as u8) & 0x80) != 0);
+        self.set_flag(ZERO_FLAG, binary as u8 == 0);
+
+        if self.status & DECIMAL_FLAG == 0 {
+            self.set_flag(CARRY_FLAG, binary > 0xFF);
+            self.set_flag(NEGATIVE_FLAG, binary & 0x80 != 0);
+            self.a = binary as u8;
+            return;
+        }
+
+        let mut lo = (self.a & 0x0F) as u16 + (value & 0x0F) as u16 + carry;
+        let mut hi = (self.a >> 4) as u16 + (value >> 4) as u16;
+        if lo > 9 {
+            lo += 6;
+        }
+        if lo > 0x0F {
+            hi += 1;
+        }
+        self.set_flag(NEGATIVE_FLAG, hi & 0x08 != 0);
+        self.set_flag(OVERFLOW_FLAG, (!(self.a ^ value) & (self.a ^ (hi << 4) as u8) & 0x80) != 0);
+        if hi > 9 {
+            hi += 6;
+        }
+        self.set_flag(CARRY_FLAG, hi > 0x0F);
+        self.a = ((hi << 4) | (lo & 0x0F)) as u8;
+    }
+
+    fn subtract (&mut self, value: u8) {
+        let borrow = 1 - self.carry() as i16;
+        let binary = self.a as i16 - value as i16 - borrow;
+        let result = binary as u8;
+        self.set_flag(CARRY_FLAG, binary >= 0);
+        self.set_flag(OVERFLOW_FLAG, ((self.a ^ value) & (self.a ^ result) & 0x80) != 0);
+        self.set_zn(result);
+
+        if self.status & DECIMAL_FLAG == 0 {
+            self.a = result;
+            return;
+        }
+
+        let mut lo = (self.a & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+        let mut hi = (self.a >> 4) as i16 - (value >> 4) as i16;
+        if lo < 0 {
+            lo -= 6;
+            hi -= 1;
+        }
+        if hi < 0 {
+            hi -= 6;
+        }
+        self.a = ((hi << 4) | (lo & 0x0F)) as u8;
+    }
+
+    pub fn adc (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        let value = self.read_operand(memory, mode);
+        self.add(value);
+        self.advance(mode);
+    }
+
+    pub fn sbc (&mut self, memory: &mut Memory, mode: AddressingMode) {
+        let value = self.read_operand(memory, mode);
+        self.subtract(value);
+        self.advance(mode);
     }
 }
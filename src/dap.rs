@@ -0,0 +1,528 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::sync::mpsc::Receiver;
+
+use crate::cpu::CPU;
+use crate::debugger::{flags, BreakOn, MinCPU, StopReason};
+use crate::expression::Expr;
+use crate::json::Json;
+use crate::loader::{assemble_file, Assembly};
+use crate::Memory;
+
+/*
+ *  Debug adapter protocol server. Requests come in over a channel so that a pause can reach
+ *  a running program; responses and events are written to out with Content-Length framing.
+ *
+ *  launch assembles the program and maps breakpoints by source line through its source map.
+ *  There is one thread and one stack frame, plus a Registers scope and readMemory /
+ *  writeMemory for memory views.
+ */
+
+pub const THREAD_ID: i64 = 1;
+const REGISTERS_REFERENCE: i64 = 1;
+
+// instructions run between checks for a pause request
+const POLL_INTERVAL: usize = 10_000;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+
+// reads one Content-Length framed message, None at the end of input
+pub fn read_message<R: BufRead> (reader: &mut R) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    let text = String::from_utf8(body).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    Json::parse(&text).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<W: Write> (out: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    out.flush()
+}
+
+// what to do once a request has been answered
+enum FollowUp {
+    Initialized,
+    Stopped(&'static str),
+    Run(RunMode),
+    Terminated,
+}
+
+#[derive(Clone, Copy)]
+enum RunMode {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+    StepBack,
+    ReverseContinue,
+}
+
+struct Program {
+    path: String,
+    assembly: Assembly,
+}
+
+type RequestResult = Result<(Json, Option<FollowUp>), String>;
+
+pub struct DapServer<W: Write> {
+    out: W,
+    seq: i64,
+    pub cpu: CPU,
+    pub memory: Memory,
+    program: Option<Program>,
+    // debugger ids of the current source breakpoints
+    breakpoints: Vec<usize>,
+    stop_on_entry: bool,
+    // requests that arrived while the program was running
+    deferred: VecDeque<Json>,
+    done: bool,
+}
+
+impl<W: Write> DapServer<W> {
+    pub fn new (out: W) -> Self {
+        DapServer {
+            out,
+            seq: 1,
+            cpu: CPU::new(),
+            memory: Memory::new(),
+            program: None,
+            breakpoints: Vec::new(),
+            stop_on_entry: false,
+            deferred: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    // handles requests until disconnect or until the channel closes
+    pub fn serve (&mut self, requests: &Receiver<Json>) -> io::Result<()> {
+        while !self.done {
+            let request = match self.deferred.pop_front() {
+                Some(request) => request,
+                None => match requests.recv() {
+                    Ok(request) => request,
+                    Err(_) => break,
+                },
+            };
+            self.handle(&request, requests)?;
+        }
+        Ok(())
+    }
+
+    fn send (&mut self, mut message: Vec<(String, Json)>) -> io::Result<()> {
+        message.insert(0, (String::from("seq"), Json::from(self.seq)));
+        self.seq += 1;
+        write_message(&mut self.out, &Json::Object(message))
+    }
+
+    fn event (&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(vec![
+            (String::from("type"), Json::from("event")),
+            (String::from("event"), Json::from(event)),
+            (String::from("body"), body),
+        ])
+    }
+
+    fn respond (&mut self, request: &Json, result: Result<Json, String>) -> io::Result<()> {
+        let mut message = vec![
+            (String::from("type"), Json::from("response")),
+            (String::from("request_seq"), request.get("seq").cloned().unwrap_or(Json::Null)),
+            (String::from("success"), Json::from(result.is_ok())),
+            (String::from("command"), request.get("command").cloned().unwrap_or(Json::Null)),
+        ];
+        match result {
+            Ok(Json::Null) => {}
+            Ok(body) => message.push((String::from("body"), body)),
+            Err(error) => message.push((String::from("message"), Json::from(error))),
+        }
+        self.send(message)
+    }
+
+    fn handle (&mut self, request: &Json, requests: &Receiver<Json>) -> io::Result<()> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or("");
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Null);
+
+        match self.dispatch(command, &arguments) {
+            Ok((body, follow_up)) => {
+                self.respond(request, Ok(body))?;
+                match follow_up {
+                    Some(FollowUp::Initialized) => self.event("initialized", Json::Null),
+                    Some(FollowUp::Stopped(reason)) => self.stopped(reason),
+                    Some(FollowUp::Run(mode)) => {
+                        let reason = self.resume(mode, requests)?;
+                        self.stopped(reason)
+                    }
+                    Some(FollowUp::Terminated) => self.event("terminated", Json::Object(Vec::new())),
+                    None => Ok(()),
+                }
+            }
+            Err(error) => self.respond(request, Err(error)),
+        }
+    }
+
+    fn stopped (&mut self, reason: &str) -> io::Result<()> {
+        let body = Json::object([
+            ("reason", Json::from(reason)),
+            ("threadId", Json::from(THREAD_ID)),
+            ("allThreadsStopped", Json::from(true)),
+        ]);
+        self.event("stopped", body)
+    }
+
+    fn dispatch (&mut self, command: &str, arguments: &Json) -> RequestResult {
+        let body = match command {
+            "initialize" => Json::object([
+                ("supportsConfigurationDoneRequest", Json::from(true)),
+                ("supportsStepBack", Json::from(true)),
+                ("supportsReadMemoryRequest", Json::from(true)),
+                ("supportsWriteMemoryRequest", Json::from(true)),
+                ("supportsEvaluateForHovers", Json::from(true)),
+            ]),
+            "launch" => {
+                self.launch(arguments)?;
+                return Ok((Json::Null, Some(FollowUp::Initialized)));
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments)?,
+            "configurationDone" => {
+                let follow_up = if self.stop_on_entry {
+                    FollowUp::Stopped("entry")
+                } else {
+                    FollowUp::Run(RunMode::Continue)
+                };
+                return Ok((Json::Null, Some(follow_up)));
+            }
+            "threads" => Json::object([(
+                "threads",
+                Json::from(vec![Json::object([("id", Json::from(THREAD_ID)), ("name", Json::from("6502"))])]),
+            )]),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Json::object([(
+                "scopes",
+                Json::from(vec![Json::object([
+                    ("name", Json::from("Registers")),
+                    ("variablesReference", Json::from(REGISTERS_REFERENCE)),
+                    ("expensive", Json::from(false)),
+                ])]),
+            )]),
+            "variables" => self.variables(arguments),
+            "readMemory" => self.read_memory(arguments)?,
+            "writeMemory" => self.write_memory(arguments)?,
+            "evaluate" => {
+                let text = arguments.get("expression").and_then(Json::as_str).ok_or("Missing expression")?;
+                let expr = Expr::parse(text).map_err(|e| e.to_string())?;
+                let value = expr.eval(&MinCPU::from_cpu(&self.cpu), &self.memory);
+                Json::object([
+                    ("result", Json::from(format!("${:X} ({})", value, value))),
+                    ("variablesReference", Json::from(0i64)),
+                ])
+            }
+            "continue" => {
+                let body = Json::object([("allThreadsContinued", Json::from(true))]);
+                return Ok((body, Some(FollowUp::Run(RunMode::Continue))));
+            }
+            "next" => return Ok((Json::Null, Some(FollowUp::Run(RunMode::Next)))),
+            "stepIn" => return Ok((Json::Null, Some(FollowUp::Run(RunMode::StepIn)))),
+            "stepOut" => return Ok((Json::Null, Some(FollowUp::Run(RunMode::StepOut)))),
+            "stepBack" => return Ok((Json::Null, Some(FollowUp::Run(RunMode::StepBack)))),
+            "reverseContinue" => return Ok((Json::Null, Some(FollowUp::Run(RunMode::ReverseContinue)))),
+            // only reaches here while stopped; a pause during a run is handled in run_until
+            "pause" => return Ok((Json::Null, Some(FollowUp::Stopped("pause")))),
+            "disconnect" | "terminate" => {
+                self.done = true;
+                return Ok((Json::Null, Some(FollowUp::Terminated)));
+            }
+            _ => return Err(format!("Unsupported command: {}", command)),
+        };
+        Ok((body, None))
+    }
+
+    fn launch (&mut self, arguments: &Json) -> Result<(), String> {
+        let path = arguments.get("program").and_then(Json::as_str).ok_or("launch needs a program")?;
+        let assembly = assemble_file(path.to_string()).map_err(|e| format!("failed to assemble {}: {}", path, e))?;
+
+        self.cpu = CPU::new();
+        self.cpu.reset();
+        self.memory = Memory::new();
+        for block in assembly.blocks() {
+            for (i, &byte) in block.data.iter().enumerate() {
+                self.memory.write(block.addr.wrapping_add(i as u16), byte);
+            }
+        }
+        self.cpu.pc = assembly.origin;
+        self.cpu.debugger.source_map = assembly.source_map.clone();
+
+        self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+        self.breakpoints.clear();
+        self.program = Some(Program { path: path.to_string(), assembly });
+        Ok(())
+    }
+
+    fn set_breakpoints (&mut self, arguments: &Json) -> Result<Json, String> {
+        let program = self.program.as_ref().ok_or("No program has been launched")?;
+        let lines: Vec<usize> = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|breakpoint| breakpoint.get("line").and_then(Json::as_i64))
+            .map(|line| line as usize)
+            .collect();
+
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"))
+            .and_then(Json::as_str)
+            .unwrap_or(&program.path);
+        let resolved: Vec<(usize, Option<(usize, u16)>)> =
+            lines.iter().map(|&line| (line, program.assembly.source_map.resolve(path, line))).collect();
+
+        for id in self.breakpoints.drain(..) {
+            self.cpu.debugger.remove_breakpoint(id);
+        }
+
+        let mut results = Vec::new();
+        for (line, target) in resolved {
+            results.push(match target {
+                Some((line, addr)) => {
+                    let id = self.cpu.debugger.add_breakpoint(BreakOn::Address(addr), None);
+                    self.breakpoints.push(id);
+                    Json::object([
+                        ("id", Json::from(id)),
+                        ("verified", Json::from(true)),
+                        ("line", Json::from(line)),
+                    ])
+                }
+                None => Json::object([
+                    ("verified", Json::from(false)),
+                    ("line", Json::from(line)),
+                    ("message", Json::from("No code at or after this line")),
+                ]),
+            });
+        }
+        Ok(Json::object([("breakpoints", Json::from(results))]))
+    }
+
+    fn stack_trace (&self) -> Json {
+        let pc = self.cpu.pc;
+        let mut frame = vec![
+            (String::from("id"), Json::from(0i64)),
+            (String::from("column"), Json::from(1i64)),
+            (String::from("instructionPointerReference"), Json::from(format!("0x{:04X}", pc))),
+        ];
+
+        let mut name = format!("${:04X}", pc);
+        let mut line = 0;
+        if let Some(program) = &self.program {
+            if let Some(label) = program.assembly.symbols.name_at(pc) {
+                name = label.to_string();
+            }
+            if let Some(location) = program.assembly.source_map.location(pc) {
+                line = location.line;
+                let file_name = std::path::Path::new(&location.file)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                frame.push((
+                    String::from("source"),
+                    Json::object([("name", Json::from(file_name)), ("path", Json::from(location.file.as_str()))]),
+                ));
+            }
+        }
+        frame.push((String::from("name"), Json::from(name)));
+        frame.push((String::from("line"), Json::from(line)));
+
+        Json::object([
+            ("stackFrames", Json::from(vec![Json::Object(frame)])),
+            ("totalFrames", Json::from(1i64)),
+        ])
+    }
+
+    fn variables (&self, arguments: &Json) -> Json {
+        let mut variables = Vec::new();
+        if arguments.get("variablesReference").and_then(Json::as_i64) == Some(REGISTERS_REFERENCE) {
+            let cpu = &self.cpu;
+            let registers = [
+                ("A", format!("${:02X}", cpu.a)),
+                ("X", format!("${:02X}", cpu.x)),
+                ("Y", format!("${:02X}", cpu.y)),
+                ("SP", format!("${:02X}", cpu.sp)),
+                ("P", format!("${:02X} {}", cpu.status, flags(cpu.status))),
+                ("PC", format!("${:04X}", cpu.pc)),
+            ];
+            for (name, value) in registers {
+                let mut variable = vec![
+                    (String::from("name"), Json::from(name)),
+                    (String::from("value"), Json::from(value)),
+                    (String::from("variablesReference"), Json::from(0i64)),
+                ];
+                if name == "PC" {
+                    variable.push((String::from("memoryReference"), Json::from(format!("0x{:04X}", cpu.pc))));
+                }
+                variables.push(Json::Object(variable));
+            }
+        }
+        Json::object([("variables", Json::from(variables))])
+    }
+
+    fn read_memory (&self, arguments: &Json) -> Result<Json, String> {
+        let start = memory_address(arguments)?;
+        let count = arguments.get("count").and_then(Json::as_i64).unwrap_or(0).max(0) as usize;
+        let count = count.min(0x10000 - start as usize);
+
+        let bytes: Vec<u8> = (0..count).map(|i| self.memory.peek(start + i as u16)).collect();
+        Ok(Json::object([
+            ("address", Json::from(format!("0x{:04X}", start))),
+            ("data", Json::from(encode_base64(&bytes))),
+        ]))
+    }
+
+    fn write_memory (&mut self, arguments: &Json) -> Result<Json, String> {
+        let start = memory_address(arguments)?;
+        let data = arguments.get("data").and_then(Json::as_str).ok_or("Missing data")?;
+        let bytes = decode_base64(data)?;
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.memory.write(start.wrapping_add(i as u16), byte);
+        }
+        Ok(Json::object([("bytesWritten", Json::from(bytes.len()))]))
+    }
+
+    // runs and returns the reason to report in the stopped event
+    fn resume (&mut self, mode: RunMode, requests: &Receiver<Json>) -> io::Result<&'static str> {
+        let single_step = |server: &mut Self| {
+            if !server.cpu.implements(server.memory.peek(server.cpu.pc)) {
+                return ILLEGAL_OPCODE;
+            }
+            server.cpu.step(&mut server.memory).map_or("step", stop_reason)
+        };
+
+        Ok(match mode {
+            RunMode::StepIn => single_step(self),
+            RunMode::Next if self.memory.peek(self.cpu.pc) == JSR => {
+                let (ret, sp) = (self.cpu.pc.wrapping_add(3), self.cpu.sp);
+                self.run_until(requests, |cpu, _| cpu.pc == ret && cpu.sp == sp)?
+            }
+            RunMode::Next => single_step(self),
+            RunMode::StepOut => {
+                let sp = self.cpu.sp;
+                self.run_until(requests, |cpu, opcode| opcode == RTS && cpu.sp > sp)?
+            }
+            RunMode::Continue => self.run_until(requests, |_, _| false)?,
+            RunMode::StepBack => {
+                self.cpu.step_back(&mut self.memory);
+                "step"
+            }
+            RunMode::ReverseContinue => self.cpu.reverse_continue(&mut self.memory).map_or("step", stop_reason),
+        })
+    }
+
+    /*
+     *  Steps until done(cpu, opcode just executed) holds or a breakpoint, watchpoint, pause or
+     *  illegal opcode stops execution. Other requests that arrive meanwhile are answered once stopped, except
+     *  disconnect which also stops the run.
+     */
+    fn run_until (&mut self, requests: &Receiver<Json>, mut done: impl FnMut(&CPU, u8) -> bool) -> io::Result<&'static str> {
+        loop {
+            for _ in 0..POLL_INTERVAL {
+                let opcode = self.memory.peek(self.cpu.pc);
+                if !self.cpu.implements(opcode) {
+                    return Ok(ILLEGAL_OPCODE);
+                }
+                if let Some(reason) = self.cpu.step(&mut self.memory) {
+                    return Ok(stop_reason(reason));
+                }
+                if done(&self.cpu, opcode) {
+                    return Ok("step");
+                }
+            }
+
+            while let Ok(request) = requests.try_recv() {
+                match request.get("command").and_then(Json::as_str) {
+                    Some("pause") => {
+                        self.respond(&request, Ok(Json::Null))?;
+                        return Ok("pause");
+                    }
+                    Some("disconnect" | "terminate") => {
+                        self.deferred.push_front(request);
+                        return Ok("pause");
+                    }
+                    _ => self.deferred.push_back(request),
+                }
+            }
+        }
+    }
+}
+
+// the stop reason for an opcode the CPU can't run, which is left for the user to look at
+const ILLEGAL_OPCODE: &str = "exception";
+
+fn stop_reason (reason: StopReason) -> &'static str {
+    match reason {
+        StopReason::Breakpoint(_) => "breakpoint",
+        StopReason::Watchpoint(_) => "data breakpoint",
+    }
+}
+
+
+// memoryReference plus the optional offset
+fn memory_address (arguments: &Json) -> Result<u16, String> {
+    let reference = arguments.get("memoryReference").and_then(Json::as_str).ok_or("Missing memoryReference")?;
+    let digits = reference.trim_start_matches("0x").trim_start_matches('$');
+    let base = i64::from_str_radix(digits, 16).map_err(|_| format!("Invalid memoryReference: {}", reference))?;
+    let offset = arguments.get("offset").and_then(Json::as_i64).unwrap_or(0);
+
+    let addr = base.saturating_add(offset);
+    u16::try_from(addr).map_err(|_| format!("Address out of range: {}", addr))
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode_base64 (bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, &byte)| value | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(value >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+pub fn decode_base64 (text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let (mut value, mut bits) = (0u32, 0);
+
+    for c in text.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace()) {
+        let digit = BASE64.iter().position(|&d| d == c).ok_or_else(|| format!("Invalid base64: {}", text))?;
+        value = value << 6 | digit as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((value >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
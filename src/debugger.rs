@@ -1,11 +1,19 @@
 #![allow(dead_code)]
 
+use std::collections::VecDeque;
+use std::fmt;
+
 use crate::Memory;
-use crate::op_code::OpCodeHandler;
-use crate::op_code::OpcodeTable;
 use crate::cpu::CPU;
+use crate::expression::Expr;
+use crate::loader::{disassemble, instruction_length, is_mnemonic, mnemonic};
+use crate::memory::{Access, AccessKind};
+use crate::profiler::Profiler;
+use crate::source_map::{parse_location, SourceMap};
+use crate::symbols::SymbolTable;
 
-// same as the CPU struct, just without opcodes. Used to save space on the stack. Min means minimal
+// same as the CPU struct, just without opcodes. Used to save space in the history. Min means minimal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MinCPU {
     pub a: u8,
     pub x: u8,
@@ -27,53 +35,382 @@ impl MinCPU {
         }
     }
 
+    pub fn from_cpu (cpu: &CPU) -> Self {
+        let mut temp = MinCPU::new();
+        temp.copy_cpu(cpu);
+        temp
+    }
+
     pub fn copy_cpu (&mut self, cpu: &CPU) {
         self.a = cpu.a;
         self.x = cpu.x;
         self.y = cpu.y;
         self.sp = cpu.sp;
         self.pc = cpu.pc;
+        self.status = cpu.status;
     }
 }
 
-pub struct CPUStack {
-    elements: Vec<MinCPU>,
+// status register as NV-BDIZC, with '.' for clear flags
+pub fn flags (status: u8) -> String {
+    "NV-BDIZC"
+        .chars()
+        .enumerate()
+        .map(|(i, name)| if status & (0x80 >> i) != 0 { name } else { '.' })
+        .collect()
 }
 
-impl CPUStack {
-    pub fn new () -> Self {
-        CPUStack {
-            elements: Vec::new(),
+// how many instructions the history keeps by default
+pub const HISTORY_LIMIT: usize = 10_000;
+
+// one executed instruction: registers either side of it, the cycle count before it and the bytes it wrote, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub before: MinCPU,
+    pub after: MinCPU,
+    pub cycles: u64,
+    pub opcode: u8,
+    pub writes: Vec<Access>,
+}
+
+impl fmt::Display for HistoryEntry {
+    // $0602  STA  PC: $0602 -> $0604  $0000: $00 -> $10
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = mnemonic(self.opcode).unwrap_or("???");
+        write!(f, "${:04X}  {}", self.before.pc, name)?;
+
+        let registers = [
+            ("A", self.before.a, self.after.a),
+            ("X", self.before.x, self.after.x),
+            ("Y", self.before.y, self.after.y),
+            ("SP", self.before.sp, self.after.sp),
+            ("P", self.before.status, self.after.status),
+        ];
+        write!(f, "  PC: ${:04X} -> ${:04X}", self.before.pc, self.after.pc)?;
+        for (register, before, after) in registers {
+            if before != after {
+                write!(f, "  {}: ${:02X} -> ${:02X}", register, before, after)?;
+            }
+        }
+        for write in &self.writes {
+            write!(f, "  ${:04X}: ${:02X} -> ${:02X}", write.addr, write.old, write.value)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ *  Bounded execution history, oldest entries are dropped first. A limit of 0 turns recording
+ *  off. Writes are undone by putting the old byte back in RAM. Writing a device register
+ *  can't be taken back that way, so the CPU clears the history at an instruction that does,
+ *  and stepping back stops there.
+ */
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new (limit: usize) -> Self {
+        History {
+            entries: VecDeque::new(),
+            limit,
         }
     }
 
-    pub fn pop (&mut self) -> Option<MinCPU> {
-        self.elements.pop()
+    pub fn push (&mut self, entry: HistoryEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.entries.len() >= self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop (&mut self) -> Option<HistoryEntry> {
+        self.entries.pop_back()
+    }
+
+    pub fn last (&self) -> Option<&HistoryEntry> {
+        self.entries.back()
+    }
+
+    // most recent first
+    pub fn iter (&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter().rev()
     }
 
-    pub fn push (&mut self, x: MinCPU) {
-       self.elements.push(x);
+    pub fn len (&self) -> usize {
+        self.entries.len()
     }
 
-    pub fn peek (&self) -> Option<&MinCPU> {
-        self.elements.last()
+    pub fn is_empty (&self) -> bool {
+        self.entries.is_empty()
     }
 
-    pub fn size (self) -> usize {
-        self.elements.len()
+    pub fn is_recording (&self) -> bool {
+        self.limit > 0
+    }
+
+    pub fn limit (&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit (&mut self, limit: usize) {
+        self.limit = limit;
+        while self.entries.len() > limit {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn clear (&mut self) {
+        self.entries.clear();
+    }
+}
+
+
+// what a breakpoint triggers on, checked before the instruction at PC executes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakOn {
+    Address(u16),
+    Opcode(u8),
+    Mnemonic(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub text: String,
+    pub expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub on: BreakOn,
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+    // times the location matched and the condition held, including ignored hits
+    pub hit_count: u32,
+    // number of upcoming hits to skip before stopping
+    pub ignore_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub id: usize,
+    pub kind: WatchKind,
+    // inclusive range
+    pub start: u16,
+    pub end: u16,
+    // only trigger when this value is read or written
+    pub value: Option<u8>,
+    pub enabled: bool,
+    pub hit_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub id: usize,
+    // instruction that made the access
+    pub pc: u16,
+    pub kind: AccessKind,
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+// why the debugger wants execution to stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+}
+
+impl Condition {
+    pub fn parse (text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Condition {
+            text: text.trim().to_string(),
+            expr: Expr::parse(text)?,
+        })
+    }
+}
+
+impl BreakOn {
+    /*
+     *  Breakpoint spec as typed in the debugger:
+     *      $0600            address
+     *      LDA              any instruction with that mnemonic
+     *      opcode $A9       one opcode
+     *  optionally followed by "if <condition>".
+     */
+    pub fn parse (spec: &str) -> Result<(BreakOn, Option<Condition>), Box<dyn std::error::Error>> {
+        let (location, condition) = match spec.split_once(" if ") {
+            Some((location, condition)) => (location.trim(), Some(Condition::parse(condition)?)),
+            None => (spec.trim(), None),
+        };
+
+        let on = if let Some(opcode) = location.strip_prefix("opcode") {
+            let opcode = opcode.trim().trim_start_matches('$');
+            BreakOn::Opcode(u8::from_str_radix(opcode, 16).map_err(|_| format!("Invalid opcode: {}", opcode))?)
+        } else if !location.starts_with('$') && is_mnemonic(location) {
+            BreakOn::Mnemonic(location.to_ascii_uppercase())
+        } else {
+            let addr = location.trim_start_matches('$');
+            BreakOn::Address(u16::from_str_radix(addr, 16).map_err(|_| format!("Invalid breakpoint location: {}", location))?)
+        };
+
+        Ok((on, condition))
+    }
+
+    pub fn matches (&self, pc: u16, opcode: u8) -> bool {
+        match self {
+            BreakOn::Address(addr) => *addr == pc,
+            BreakOn::Opcode(code) => *code == opcode,
+            BreakOn::Mnemonic(name) => mnemonic(opcode) == Some(name.as_str()),
+        }
+    }
+}
+
+impl Watchpoint {
+    /*
+     *  Watchpoint spec: an address or range, optionally filtered by value
+     *      $0200    $0200-$05FF    $00FF == $01
+     */
+    pub fn parse_spec (spec: &str) -> Result<(u16, u16, Option<u8>), Box<dyn std::error::Error>> {
+        let (range, value) = match spec.split_once("==") {
+            Some((range, value)) => {
+                let value = value.trim().trim_start_matches('$');
+                let value = u8::from_str_radix(value, 16).map_err(|_| format!("Invalid watch value: {}", value))?;
+                (range.trim(), Some(value))
+            }
+            None => (spec.trim(), None),
+        };
+
+        let parse_addr = |text: &str| {
+            let text = text.trim().trim_start_matches('$');
+            u16::from_str_radix(text, 16).map_err(|_| format!("Invalid watch address: {}", text))
+        };
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+            None => (parse_addr(range)?, parse_addr(range)?),
+        };
+        if end < start {
+            return Err(format!("Watch range ends before it starts: {}", range).into());
+        }
+
+        Ok((start, end, value))
+    }
+
+    pub fn matches (&self, access: &Access) -> bool {
+        let kind_matches = match self.kind {
+            WatchKind::Read => access.kind == AccessKind::Read,
+            WatchKind::Write => access.kind == AccessKind::Write,
+            WatchKind::Access => true,
+        };
+
+        kind_matches
+            && (self.start..=self.end).contains(&access.addr)
+            && self.value.is_none_or(|value| value == access.value)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        write!(f, "#{} {} ${:04X}", self.id, kind, self.start)?;
+        if self.end != self.start {
+            write!(f, "-${:04X}", self.end)?;
+        }
+        if let Some(value) = self.value {
+            write!(f, " == ${:02X}", value)?;
+        }
+        write!(f, " (hits: {})", self.hit_count)?;
+        if !self.enabled {
+            write!(f, " [disabled]")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            AccessKind::Read => write!(
+                f,
+                "watchpoint #{}: read ${:04X} = ${:02X} by instruction at ${:04X}",
+                self.id, self.addr, self.new, self.pc
+            ),
+            AccessKind::Write => write!(
+                f,
+                "watchpoint #{}: write ${:04X}: ${:02X} -> ${:02X} by instruction at ${:04X}",
+                self.id, self.addr, self.old, self.new, self.pc
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} ", self.id)?;
+        match &self.on {
+            BreakOn::Address(addr) => write!(f, "${:04X}", addr)?,
+            BreakOn::Opcode(code) => write!(f, "opcode ${:02X}", code)?,
+            BreakOn::Mnemonic(name) => write!(f, "{}", name)?,
+        }
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition.text)?;
+        }
+        write!(f, " (hits: {}", self.hit_count)?;
+        if self.ignore_count > 0 {
+            write!(f, ", ignore next {}", self.ignore_count)?;
+        }
+        write!(f, ")")?;
+        if !self.enabled {
+            write!(f, " [disabled]")?;
+        }
+        Ok(())
     }
 }
 
 
 pub struct Debugger {
-    pub cpu_stack: CPUStack,
+    pub history: History,
+    // empty unless the program was assembled from source
+    pub source_map: SourceMap,
+    // labels for reports, from the assembler or a label file
+    pub symbols: SymbolTable,
+    // counts every step while set
+    pub profiler: Option<Profiler>,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    // breakpoints and watchpoints share one numbering
+    next_id: usize,
 }
 
 
 impl Debugger {
     pub fn new () -> Self {
         Debugger {
-            cpu_stack: CPUStack::new(),
+            history: History::new(HISTORY_LIMIT),
+            source_map: SourceMap::new(),
+            symbols: SymbolTable::new(),
+            profiler: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_id: 1,
         }
     }
 
@@ -84,9 +421,165 @@ impl Debugger {
         );
     }
 
-    pub fn push (&mut self, cpu: &mut CPU) {
-        let mut temp = MinCPU::new();
-        temp.copy_cpu(&cpu);
-        self.cpu_stack.push(temp);
+    /*
+     *  One line per instruction for trace logs, before it executes:
+     *      $0602  85 10     STA $10       A=01 X=00 Y=00 SP=FF P=........  ; prog.asm:5: STA $10
+     */
+    pub fn trace_line (&self, state: &MinCPU, memory: &Memory) -> String {
+        let (text, length) = disassemble(memory, state.pc);
+        let bytes: Vec<String> = (0..length).map(|i| format!("{:02X}", memory.peek(state.pc.wrapping_add(i)))).collect();
+        let mut line = format!(
+            "${:04X}  {:<8}  {:<12}  A={:02X} X={:02X} Y={:02X} SP={:02X} P={}",
+            state.pc, bytes.join(" "), text, state.a, state.x, state.y, state.sp, flags(state.status)
+        );
+        if let Some(source) = self.source_map.annotate(state.pc) {
+            line.push_str("  ; ");
+            line.push_str(&source);
+        }
+        line
+    }
+}
+
+// breakpoints
+impl Debugger {
+    // BreakOn::parse plus file:line, which needs the source map to find an address
+    pub fn parse_breakpoint (&self, spec: &str) -> Result<(BreakOn, Option<Condition>), Box<dyn std::error::Error>> {
+        let location = spec.split_once(" if ").map_or(spec, |(location, _)| location);
+        let Some((file, line)) = parse_location(location) else {
+            return BreakOn::parse(spec);
+        };
+
+        let (_, addr) = self
+            .source_map
+            .resolve(file, line)
+            .ok_or_else(|| format!("No code at or after {}:{}", file, line))?;
+        let condition = match spec.split_once(" if ") {
+            Some((_, condition)) => Some(Condition::parse(condition)?),
+            None => None,
+        };
+        Ok((BreakOn::Address(addr), condition))
+    }
+
+    pub fn add_breakpoint (&mut self, on: BreakOn, condition: Option<Condition>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            on,
+            condition,
+            enabled: true,
+            hit_count: 0,
+            ignore_count: 0,
+        });
+        id
+    }
+
+    pub fn remove_breakpoint (&mut self, id: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.breakpoints.len() != len
+    }
+
+    pub fn breakpoint_mut (&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.iter_mut().find(|breakpoint| breakpoint.id == id)
+    }
+
+    pub fn breakpoints (&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // checks the instruction about to execute; returns the id of the breakpoint to stop at
+    pub fn check_breakpoints (&mut self, state: &MinCPU, memory: &Memory) -> Option<usize> {
+        let opcode = memory.peek(state.pc);
+        let mut stop = None;
+
+        for breakpoint in self.breakpoints.iter_mut().filter(|breakpoint| breakpoint.enabled) {
+            if !breakpoint.on.matches(state.pc, opcode) {
+                continue;
+            }
+            if let Some(condition) = &breakpoint.condition {
+                if !condition.expr.is_true(state, memory) {
+                    continue;
+                }
+            }
+
+            breakpoint.hit_count += 1;
+            if breakpoint.ignore_count > 0 {
+                breakpoint.ignore_count -= 1;
+                continue;
+            }
+            stop.get_or_insert(breakpoint.id);
+        }
+
+        stop
+    }
+}
+
+// watchpoints
+impl Debugger {
+    pub fn add_watchpoint (&mut self, kind: WatchKind, start: u16, end: u16, value: Option<u8>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id,
+            kind,
+            start,
+            end,
+            value,
+            enabled: true,
+            hit_count: 0,
+        });
+        id
+    }
+
+    pub fn remove_watchpoint (&mut self, id: usize) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoint_mut (&mut self, id: usize) -> Option<&mut Watchpoint> {
+        self.watchpoints.iter_mut().find(|watchpoint| watchpoint.id == id)
+    }
+
+    pub fn watchpoints (&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // the bus only records accesses while this is true
+    pub fn wants_bus_trace (&self) -> bool {
+        self.history.is_recording() || self.watchpoints.iter().any(|watchpoint| watchpoint.enabled)
+    }
+
+    /*
+     *  Checks the accesses made by the instruction at pc. Reads of the instruction's own bytes
+     *  are fetches, not data reads, and are skipped.
+     */
+    pub fn check_watchpoints (&mut self, pc: u16, opcode: u8, accesses: &[Access]) -> Option<WatchHit> {
+        let length = instruction_length(opcode).unwrap_or(1);
+        let mut hit = None;
+
+        for access in accesses {
+            let is_fetch = access.kind == AccessKind::Read && access.addr.wrapping_sub(pc) < length;
+            if is_fetch {
+                continue;
+            }
+
+            for watchpoint in self.watchpoints.iter_mut().filter(|watchpoint| watchpoint.enabled) {
+                if watchpoint.matches(access) {
+                    watchpoint.hit_count += 1;
+                    hit.get_or_insert(WatchHit {
+                        id: watchpoint.id,
+                        pc,
+                        kind: access.kind,
+                        addr: access.addr,
+                        old: access.old,
+                        new: access.value,
+                    });
+                }
+            }
+        }
+
+        hit
     }
 }
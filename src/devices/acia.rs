@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use super::Device;
+
+/*
+ *  MOS 6551 ACIA, four registers:
+ *      0  write: transmit a byte; read: the byte received
+ *      1  read: status; write: programmed reset
+ *      2  command
+ *      3  control
+ *
+ *  Bytes go out as soon as they're written, so the transmitter is always empty, and a byte
+ *  is only taken from input once the program has read the last one: input waits its turn
+ *  rather than overrunning. The baud rate and word format in control are kept but ignored.
+ */
+
+pub const DATA: u16 = 0;
+pub const STATUS: u16 = 1;
+pub const COMMAND: u16 = 2;
+pub const CONTROL: u16 = 3;
+
+// status bits; DCD and DSR (bits 5 and 6) are active low and always asserted
+pub const STATUS_OVERRUN: u8 = 0x04;
+pub const STATUS_RDRF: u8 = 0x08;
+pub const STATUS_TDRE: u8 = 0x10;
+pub const STATUS_IRQ: u8 = 0x80;
+
+// command bits
+const COMMAND_DTR: u8 = 0x01;
+const COMMAND_RX_IRQ_DISABLED: u8 = 0x02;
+const COMMAND_TX_CONTROL: u8 = 0x0C;
+const COMMAND_TX_IRQ: u8 = 0x04;
+const COMMAND_ECHO: u8 = 0x10;
+// the parity bits, which a programmed reset leaves alone
+const COMMAND_PARITY: u8 = 0xE0;
+
+pub struct Acia {
+    output: Box<dyn Write>,
+    input: Receiver<u8>,
+    received: u8,
+    status: u8,
+    command: u8,
+    control: u8,
+}
+
+impl Acia {
+    pub fn new (output: impl Write + 'static, input: Receiver<u8>) -> Self {
+        Acia { output: Box::new(output), input, received: 0, status: STATUS_TDRE, command: 0, control: 0 }
+    }
+
+    fn transmit (&mut self, value: u8) {
+        let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+    }
+
+    fn rx_irq_enabled (&self) -> bool {
+        self.command & (COMMAND_DTR | COMMAND_RX_IRQ_DISABLED) == COMMAND_DTR
+    }
+
+    fn tx_irq_enabled (&self) -> bool {
+        self.command & COMMAND_TX_CONTROL == COMMAND_TX_IRQ
+    }
+}
+
+impl Device for Acia {
+    fn name (&self) -> &'static str {
+        "acia6551"
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            DATA => {
+                self.status &= !(STATUS_RDRF | STATUS_OVERRUN);
+                self.received
+            }
+            // reading status acknowledges the interrupt
+            STATUS => {
+                let status = self.status;
+                self.status &= !STATUS_IRQ;
+                status
+            }
+            offset => self.peek(offset),
+        }
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            DATA => self.received,
+            STATUS => self.status,
+            COMMAND => self.command,
+            _ => self.control,
+        }
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        match offset & 0x03 {
+            DATA => {
+                self.transmit(value);
+                if self.tx_irq_enabled() {
+                    self.status |= STATUS_IRQ;
+                }
+            }
+            STATUS => {
+                self.command &= COMMAND_PARITY;
+                self.status &= !STATUS_OVERRUN;
+            }
+            COMMAND => {
+                self.command = value;
+                if self.tx_irq_enabled() {
+                    self.status |= STATUS_IRQ;
+                }
+            }
+            _ => self.control = value,
+        }
+    }
+
+    fn tick (&mut self, _cycles: u64) {
+        if self.status & STATUS_RDRF != 0 {
+            return;
+        }
+        let Ok(value) = self.input.try_recv() else { return };
+
+        self.received = value;
+        self.status |= STATUS_RDRF;
+        if self.rx_irq_enabled() {
+            self.status |= STATUS_IRQ;
+        }
+        if self.command & (COMMAND_ECHO | COMMAND_TX_CONTROL) == COMMAND_ECHO {
+            self.transmit(value);
+        }
+    }
+
+    fn irq (&self) -> bool {
+        self.status & STATUS_IRQ != 0
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        vec![self.received, self.status, self.command, self.control]
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let [received, status, command, control] = data.try_into().map_err(|_| "Invalid ACIA state")?;
+        (self.received, self.status, self.command, self.control) = (received, status, command, control);
+        Ok(())
+    }
+}
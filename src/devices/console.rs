@@ -0,0 +1,64 @@
+use std::io::{self, Write};
+use std::sync::mpsc::Receiver;
+
+use super::Device;
+use crate::terminal::spawn_key_reader;
+
+/*
+ *  Character I/O for teaching machines:
+ *      offset 0  write: print the byte
+ *      offset 1  read: the next input byte, or 0 when there isn't one yet
+ */
+
+pub const OUTPUT: u16 = 0;
+pub const INPUT: u16 = 1;
+
+pub struct Console {
+    output: Box<dyn Write>,
+    input: Receiver<u8>,
+    // a byte already taken from input, so peek can show it without losing it
+    buffered: Option<u8>,
+}
+
+impl Console {
+    pub fn new (output: impl Write + 'static, input: Receiver<u8>) -> Self {
+        Console { output: Box::new(output), input, buffered: None }
+    }
+
+    pub fn stdio () -> Self {
+        Console::new(io::stdout(), spawn_key_reader())
+    }
+}
+
+impl Device for Console {
+    fn name (&self) -> &'static str {
+        "console"
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        match offset {
+            INPUT => self.buffered.take().or_else(|| self.input.try_recv().ok()).unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        match offset {
+            INPUT => self.buffered.unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        if offset == OUTPUT {
+            // flushed every byte so prompts show up before the program waits for input
+            let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+        }
+    }
+
+    fn tick (&mut self, _cycles: u64) {
+        if self.buffered.is_none() {
+            self.buffered = self.input.try_recv().ok();
+        }
+    }
+}
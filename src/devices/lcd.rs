@@ -0,0 +1,477 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+use super::via::Pins;
+use super::Device;
+
+/*
+ *  Hitachi HD44780 character LCD controller, as found on 16x2 and 20x4 modules. RS picks the
+ *  instruction register (0) or data register (1). Instructions, by their highest set bit:
+ *      0x01  clear display           0x10  cursor or display shift (S/C, R/L)
+ *      0x02  return home             0x20  function set (DL, N, F)
+ *      0x04  entry mode (I/D, S)     0x40  set CGRAM address
+ *      0x08  display on/off control  0x80  set DDRAM address
+ *
+ *  After each transfer the busy flag stays set for as long as the real controller takes,
+ *  counted in CPU cycles at the clock set with set_clock_hz. Transfers made while it is set
+ *  still go through. With DL clear the interface is 4-bit: only D7-D4 are used, high nibble
+ *  first.
+ *
+ *  Attached to the memory map, offset 0 is the instruction register (the busy flag and
+ *  address counter on reads) and offset 1 the data register. LcdPins wires it to a VIA.
+ */
+
+pub const DEFAULT_COLUMNS: usize = 16;
+pub const DEFAULT_ROWS: usize = 2;
+pub const DEFAULT_CLOCK_HZ: u64 = 1_000_000;
+
+pub const INSTRUCTION: u16 = 0;
+pub const DATA: u16 = 1;
+pub const BUSY_FLAG: u8 = 0x80;
+// how lines shows a custom (CGRAM) character
+pub const CUSTOM_GLYPH: char = '▒';
+
+// where each row starts in DDRAM; rows 3 and 4 of a 20x4 continue rows 1 and 2
+const ROW_STARTS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+// characters per line in two-line mode
+const LINE_LEN: u8 = 0x28;
+// execution times in microseconds, at the datasheet's 270 kHz oscillator
+const CLEAR_US: u64 = 1520;
+const INSTRUCTION_US: u64 = 37;
+const DATA_US: u64 = 41;
+
+const STATE_LEN: usize = 0x80 + 0x40 + 13;
+
+pub struct Lcd {
+    columns: usize,
+    rows: usize,
+    clock_hz: u64,
+    ddram: [u8; 0x80],
+    cgram: [u8; 0x40],
+    // the address counter, into CGRAM while cgram_selected
+    address: u8,
+    cgram_selected: bool,
+    // how far the display has shifted left, 0 to 39
+    shift: u8,
+    increment: bool,
+    shift_on_write: bool,
+    display_on: bool,
+    cursor_on: bool,
+    blink_on: bool,
+    two_lines: bool,
+    eight_bit: bool,
+    // 4-bit transfers: the high nibble of a write in progress, or the low nibble of a read
+    pending_write: Option<u8>,
+    pending_read: Option<u8>,
+    busy_cycles: u64,
+}
+
+impl Default for Lcd {
+    fn default () -> Self {
+        Lcd::new(DEFAULT_COLUMNS, DEFAULT_ROWS)
+    }
+}
+
+impl Lcd {
+    // a module with this many characters showing; the controller itself is the same
+    pub fn new (columns: usize, rows: usize) -> Self {
+        Lcd {
+            columns: columns.min(LINE_LEN as usize),
+            rows: rows.clamp(1, ROW_STARTS.len()),
+            clock_hz: DEFAULT_CLOCK_HZ,
+            ddram: [b' '; 0x80],
+            cgram: [0; 0x40],
+            address: 0,
+            cgram_selected: false,
+            shift: 0,
+            increment: true,
+            shift_on_write: false,
+            display_on: false,
+            cursor_on: false,
+            blink_on: false,
+            two_lines: false,
+            eight_bit: true,
+            pending_write: None,
+            pending_read: None,
+            busy_cycles: 0,
+        }
+    }
+
+    // the CPU clock, which sets how many cycles the busy flag lasts
+    pub fn set_clock_hz (&mut self, hz: u64) {
+        self.clock_hz = hz;
+    }
+
+    // a transfer on the data lines, D7-D0; in 4-bit mode each byte takes two, on D7-D4
+    pub fn bus_write (&mut self, rs: bool, value: u8) {
+        if self.eight_bit {
+            return self.write(rs, value);
+        }
+        match self.pending_write.take() {
+            Some(high) => self.write(rs, high | value >> 4),
+            None => self.pending_write = Some(value & 0xF0),
+        }
+    }
+
+    pub fn bus_read (&mut self, rs: bool) -> u8 {
+        if self.eight_bit {
+            return self.read(rs);
+        }
+        match self.pending_read.take() {
+            Some(low) => low,
+            None => {
+                let value = self.read(rs);
+                self.pending_read = Some(value << 4);
+                value & 0xF0
+            }
+        }
+    }
+
+    // a whole byte to a register, whichever interface is set up
+    pub fn write (&mut self, rs: bool, value: u8) {
+        if rs {
+            self.write_data(value)
+        } else {
+            self.write_instruction(value)
+        }
+    }
+
+    pub fn read (&mut self, rs: bool) -> u8 {
+        if rs {
+            self.read_data()
+        } else {
+            self.read_status()
+        }
+    }
+
+    pub fn write_instruction (&mut self, value: u8) {
+        self.pending_read = None;
+        self.busy(INSTRUCTION_US);
+        if value & 0x80 != 0 {
+            self.address = value & 0x7F;
+            self.cgram_selected = false;
+        } else if value & 0x40 != 0 {
+            self.address = value & 0x3F;
+            self.cgram_selected = true;
+        } else if value & 0x20 != 0 {
+            self.eight_bit = value & 0x10 != 0;
+            self.two_lines = value & 0x08 != 0;
+            self.pending_write = None;
+        } else if value & 0x10 != 0 {
+            // S/C picks the display over the cursor, R/L the direction
+            if value & 0x08 != 0 {
+                self.shift_display(value & 0x04 != 0);
+            } else if self.cgram_selected {
+                self.step_cgram(value & 0x04 != 0);
+            } else {
+                self.step(value & 0x04 != 0);
+            }
+        } else if value & 0x08 != 0 {
+            self.display_on = value & 0x04 != 0;
+            self.cursor_on = value & 0x02 != 0;
+            self.blink_on = value & 0x01 != 0;
+        } else if value & 0x04 != 0 {
+            self.increment = value & 0x02 != 0;
+            self.shift_on_write = value & 0x01 != 0;
+        } else if value & 0x02 != 0 {
+            self.home();
+        } else if value & 0x01 != 0 {
+            self.ddram = [b' '; 0x80];
+            self.home();
+            self.increment = true;
+        }
+    }
+
+    pub fn write_data (&mut self, value: u8) {
+        self.pending_read = None;
+        self.busy(DATA_US);
+        if self.cgram_selected {
+            self.cgram[self.address as usize] = value & 0x1F;
+            self.step_cgram(self.increment);
+            return;
+        }
+        self.ddram[self.address as usize] = value;
+        self.step(self.increment);
+        if self.shift_on_write {
+            // the display follows the cursor, so the cursor seems to stand still
+            self.shift_display(!self.increment);
+        }
+    }
+
+    // busy flag in bit 7 over the address counter
+    pub fn read_status (&self) -> u8 {
+        (if self.is_busy() { BUSY_FLAG } else { 0 }) | self.address
+    }
+
+    pub fn read_data (&mut self) -> u8 {
+        self.busy(DATA_US);
+        if self.cgram_selected {
+            let value = self.cgram[self.address as usize];
+            self.step_cgram(self.increment);
+            return value;
+        }
+        let value = self.ddram[self.address as usize];
+        self.step(self.increment);
+        value
+    }
+
+    pub fn is_busy (&self) -> bool {
+        self.busy_cycles > 0
+    }
+
+    // the address counter, which is also where the cursor is
+    pub fn address (&self) -> u8 {
+        self.address
+    }
+
+    // where the underline or blinking cursor is, when either is on
+    pub fn cursor (&self) -> Option<u8> {
+        (self.display_on && !self.cgram_selected && (self.cursor_on || self.blink_on)).then_some(self.address)
+    }
+
+    // the cursor's row and column on the glass, when it is on and in view
+    pub fn cursor_position (&self) -> Option<(usize, usize)> {
+        let address = self.cursor()?;
+        (0..self.rows).find_map(|row| (0..self.columns).find(|&column| self.visible_address(row, column) == address).map(|column| (row, column)))
+    }
+
+    pub fn is_display_on (&self) -> bool {
+        self.display_on
+    }
+
+    // the pixel rows of a custom character (0-7, repeated at 8-15), five pixels wide
+    pub fn custom_char (&self, code: u8) -> [u8; 8] {
+        let start = (code & 0x07) as usize * 8;
+        self.cgram[start..start + 8].try_into().unwrap()
+    }
+
+    fn busy (&mut self, microseconds: u64) {
+        self.busy_cycles = microseconds * self.clock_hz / 1_000_000;
+    }
+
+    fn home (&mut self) {
+        self.busy(CLEAR_US);
+        self.address = 0;
+        self.cgram_selected = false;
+        self.shift = 0;
+    }
+
+    // right moves the text right, which shows what is to the left of it
+    fn shift_display (&mut self, right: bool) {
+        self.shift = match right {
+            true => (self.shift + LINE_LEN - 1) % LINE_LEN,
+            false => (self.shift + 1) % LINE_LEN,
+        };
+    }
+
+    // CGRAM wraps within its 64 bytes
+    fn step_cgram (&mut self, forward: bool) {
+        self.address = (self.address + if forward { 1 } else { 0x3F }) & 0x3F;
+    }
+
+    // DDRAM wraps from the end of one line to the start of the next
+    fn step (&mut self, forward: bool) {
+        let last = LINE_LEN - 1;
+        self.address = match (self.two_lines, forward, self.address) {
+            (true, true, address) if address == last => 0x40,
+            (true, true, address) if address == 0x40 + last => 0x00,
+            (true, false, 0x00) => 0x40 + last,
+            (true, false, 0x40) => last,
+            (false, true, address) if address >= 2 * LINE_LEN - 1 => 0x00,
+            (false, false, 0x00) => 2 * LINE_LEN - 1,
+            (_, true, address) => (address + 1) & 0x7F,
+            (_, false, address) => address - 1,
+        };
+    }
+
+    // each line scrolls around its own 40 characters as the display shifts
+    fn visible_address (&self, row: usize, column: usize) -> u8 {
+        let start = ROW_STARTS[row];
+        let offset = ((start & 0x3F) + column as u8 + self.shift) % LINE_LEN;
+        (start & 0x40) | offset
+    }
+
+    // the visible characters, a string per row; blank while the display is off
+    pub fn lines (&self) -> Vec<String> {
+        (0..self.rows)
+            .map(|row| {
+                (0..self.columns)
+                    .map(|column| match self.display_on {
+                        true => glyph(self.ddram[self.visible_address(row, column) as usize]),
+                        false => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // the visible characters, a line per row
+    pub fn text (&self) -> String {
+        self.lines().join("\n")
+    }
+}
+
+// the A00 character ROM: custom characters, ASCII with a yen sign and arrows, then half-width katakana
+fn glyph (code: u8) -> char {
+    match code {
+        0x00..=0x0F => CUSTOM_GLYPH,
+        0x5C => '¥',
+        0x7E => '→',
+        0x7F => '←',
+        0x20..=0x7D => code as char,
+        0xA1..=0xDF => char::from_u32(0xFF61 + (code - 0xA1) as u32).unwrap_or('?'),
+        0xA0 => ' ',
+        _ => '?',
+    }
+}
+
+impl Device for Lcd {
+    fn name (&self) -> &'static str {
+        "hd44780"
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        self.bus_read(offset & 1 == DATA)
+    }
+
+    // the status, or the byte at the address counter without moving it
+    fn peek (&self, offset: u16) -> u8 {
+        match (offset & 1 == DATA, self.cgram_selected) {
+            (false, _) => self.read_status(),
+            (true, true) => self.cgram[self.address as usize],
+            (true, false) => self.ddram[self.address as usize],
+        }
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        self.bus_write(offset & 1 == DATA, value);
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.busy_cycles = self.busy_cycles.saturating_sub(cycles);
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        let flags = [self.cgram_selected, self.increment, self.shift_on_write, self.display_on, self.cursor_on, self.blink_on, self.two_lines, self.eight_bit];
+        let busy = self.busy_cycles.min(0xFFFF) as u16;
+        let mut state = self.ddram.to_vec();
+        state.extend_from_slice(&self.cgram);
+        state.extend_from_slice(&[self.address, self.shift, self.pending_write.unwrap_or(0x0F)]);
+        state.extend_from_slice(&flags.map(|flag| flag as u8));
+        state.extend_from_slice(&busy.to_le_bytes());
+        state
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        if data.len() != STATE_LEN {
+            return Err("Invalid LCD state".into());
+        }
+        self.ddram.copy_from_slice(&data[..0x80]);
+        self.cgram.copy_from_slice(&data[0x80..0xC0]);
+        let rest = &data[0xC0..];
+        self.shift = rest[1] % LINE_LEN;
+        // a pending high nibble never has low bits set
+        self.pending_write = (rest[2] & 0x0F == 0).then_some(rest[2]);
+        let flag = |i: usize| rest[3 + i] != 0;
+        (self.cgram_selected, self.increment, self.shift_on_write, self.display_on) = (flag(0), flag(1), flag(2), flag(3));
+        (self.cursor_on, self.blink_on, self.two_lines, self.eight_bit) = (flag(4), flag(5), flag(6), flag(7));
+        self.address = rest[0] & if self.cgram_selected { 0x3F } else { 0x7F };
+        self.busy_cycles = u16::from_le_bytes([rest[11], rest[12]]) as u64;
+        self.pending_read = None;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+}
+
+/*
+ *  Which VIA pins the LCD hangs off. E, RW and RS are masks on the control port. The data
+ *  lines take the whole data port, or with nibble_at, D4-D7 go on four pins starting at
+ *  that bit for a 4-bit interface. The LCD takes a write on E's falling edge, and drives the
+ *  data lines with a read while E is high.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViaWiring {
+    pub control: Port,
+    pub e: u8,
+    pub rw: u8,
+    pub rs: u8,
+    pub data: Port,
+    pub nibble_at: Option<u8>,
+}
+
+// the breadboard computer's wiring: data on port B, E on PA7, RW on PA6, RS on PA5
+pub const BREADBOARD_WIRING: ViaWiring = ViaWiring { control: Port::A, e: 0x80, rw: 0x40, rs: 0x20, data: Port::B, nibble_at: None };
+// its 4-bit variant, all on port B: D4-D7 on PB0-PB3, RS on PB4, RW on PB5, E on PB6
+pub const BREADBOARD_4BIT_WIRING: ViaWiring = ViaWiring { control: Port::B, e: 0x40, rw: 0x20, rs: 0x10, data: Port::B, nibble_at: Some(0) };
+
+pub struct LcdPins {
+    lcd: Rc<RefCell<Lcd>>,
+    wiring: ViaWiring,
+    control: u8,
+    // what the LCD is putting on the data lines, while it is
+    output: Option<u8>,
+}
+
+impl LcdPins {
+    pub fn new (lcd: Rc<RefCell<Lcd>>, wiring: ViaWiring) -> Self {
+        LcdPins { lcd, wiring, control: 0, output: None }
+    }
+
+    // the data lines from the port, as D7-D0
+    fn bus (&self, port: u8) -> u8 {
+        match self.wiring.nibble_at {
+            Some(bit) => (port >> bit & 0x0F) << 4,
+            None => port,
+        }
+    }
+}
+
+fn select (port: Port, port_a: u8, port_b: u8) -> u8 {
+    match port {
+        Port::A => port_a,
+        Port::B => port_b,
+    }
+}
+
+impl Pins for LcdPins {
+    fn drive (&mut self, port_a: u8, port_b: u8) {
+        let ViaWiring { e, rw, rs, .. } = self.wiring;
+        let control = select(self.wiring.control, port_a, port_b);
+        let data = self.bus(select(self.wiring.data, port_a, port_b));
+        let rising = control & e != 0 && self.control & e == 0;
+        let falling = control & e == 0 && self.control & e != 0;
+
+        if falling && self.control & rw == 0 {
+            self.lcd.borrow_mut().bus_write(self.control & rs != 0, data);
+        }
+        if rising && control & rw != 0 {
+            self.output = Some(self.lcd.borrow_mut().bus_read(control & rs != 0));
+        } else if control & e == 0 || control & rw == 0 {
+            self.output = None;
+        }
+        self.control = control;
+    }
+
+    // lines the LCD isn't driving float high
+    fn sense (&mut self) -> (u8, u8) {
+        let lines = match (self.output, self.wiring.nibble_at) {
+            (None, _) => 0xFF,
+            (Some(value), None) => value,
+            (Some(value), Some(bit)) => !(0x0F << bit) | (value >> 4) << bit,
+        };
+        match self.wiring.data {
+            Port::A => (lines, 0xFF),
+            Port::B => (0xFF, lines),
+        }
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.lcd.borrow_mut().tick(cycles);
+    }
+}
@@ -0,0 +1,88 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+pub mod acia;
+pub mod console;
+pub mod lcd;
+pub mod pia;
+pub mod rom;
+pub mod random;
+pub mod via;
+
+/*
+ *  A memory-mapped peripheral, attached to an address range with Memory::attach. Offsets are
+ *  relative to the start of that range. After every instruction the CPU clocks each device
+ *  with the cycles it took, and a device can hold the IRQ line until it has been serviced.
+ *  A device wired to NMI instead interrupts once each time its nmi line goes active.
+ *
+ *  A front end that needs to reach a device after attaching it (to feed it keys, or read
+ *  what it printed) keeps an Rc<RefCell<_>> of it and attaches a clone.
+ */
+pub trait Device {
+    // what kind of device this is; save states match devices up by kind and order
+    fn name (&self) -> &'static str;
+
+    fn read (&mut self, offset: u16) -> u8;
+
+    // a read without side effects, for debuggers and renderers
+    fn peek (&self, offset: u16) -> u8;
+
+    fn write (&mut self, offset: u16, value: u8);
+
+    fn tick (&mut self, _cycles: u64) {}
+
+    fn irq (&self) -> bool {
+        false
+    }
+
+    fn nmi (&self) -> bool {
+        false
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_state (&mut self, _data: &[u8]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl<D: Device> Device for Rc<RefCell<D>> {
+    fn name (&self) -> &'static str {
+        self.borrow().name()
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        self.borrow_mut().read(offset)
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        self.borrow().peek(offset)
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        self.borrow_mut().write(offset, value)
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.borrow_mut().tick(cycles)
+    }
+
+    fn irq (&self) -> bool {
+        self.borrow().irq()
+    }
+
+    fn nmi (&self) -> bool {
+        self.borrow().nmi()
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        self.borrow().save_state()
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.borrow_mut().load_state(data)
+    }
+}
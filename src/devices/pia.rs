@@ -0,0 +1,239 @@
+use std::error::Error;
+
+use super::via::Pins;
+use super::Device;
+
+/*
+ *  Motorola 6821 PIA (the 6820 on the Apple I), four registers:
+ *      0  ORA, or DDRA while CRA bit 2 is clear
+ *      1  CRA
+ *      2  ORB, or DDRB while CRB bit 2 is clear
+ *      3  CRB
+ *
+ *  Control register bits: 0 enables the C1 interrupt, 1 picks its edge (rising when set),
+ *  2 selects the output register over the DDR, 3-5 set up C2, and 6 and 7 are the C2 and
+ *  C1 flags, cleared by reading the data register. Ports are wired through the same Pins as
+ *  the VIA, with CB2 as an output; CA2 as an output goes nowhere.
+ */
+
+pub const PORT_A: u16 = 0;
+pub const CRA: u16 = 1;
+pub const PORT_B: u16 = 2;
+pub const CRB: u16 = 3;
+
+const CR_C1_IRQ: u8 = 0x01;
+const CR_C1_RISING: u8 = 0x02;
+const CR_OUTPUT_REGISTER: u8 = 0x04;
+const CR_C2_IRQ: u8 = 0x08;
+const CR_C2_RISING: u8 = 0x10;
+const CR_C2_OUTPUT: u8 = 0x20;
+const CR_C2_MODE: u8 = 0x38;
+const CR_C2_FLAG: u8 = 0x40;
+const CR_C1_FLAG: u8 = 0x80;
+// C2 output modes
+const C2_HANDSHAKE: u8 = 0x20;
+const C2_PULSE: u8 = 0x28;
+const C2_MANUAL: u8 = 0x30;
+
+const STATE_LEN: usize = 10;
+
+struct Unconnected;
+
+impl Pins for Unconnected {
+    fn drive (&mut self, _port_a: u8, _port_b: u8) {}
+}
+
+pub struct Pia {
+    pins: Box<dyn Pins>,
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    cra: u8,
+    crb: u8,
+    ca1: bool,
+    ca2: bool,
+    cb1: bool,
+    cb2: bool,
+}
+
+impl Default for Pia {
+    fn default () -> Self {
+        Pia::new()
+    }
+}
+
+impl Pia {
+    pub fn new () -> Self {
+        Pia {
+            pins: Box::new(Unconnected),
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            cra: 0,
+            crb: 0,
+            ca1: true,
+            ca2: true,
+            cb1: true,
+            cb2: true,
+        }
+    }
+
+    pub fn connect (&mut self, pins: impl Pins + 'static) {
+        self.pins = Box::new(pins);
+        self.drive();
+    }
+
+    // the levels the PIA drives, with input pins high
+    pub fn port_a (&self) -> u8 {
+        self.ora | !self.ddra
+    }
+
+    pub fn port_b (&self) -> u8 {
+        self.orb | !self.ddrb
+    }
+
+    pub fn set_ca1 (&mut self, level: bool) {
+        if edge(self.cra & CR_C1_RISING != 0, self.ca1, level) {
+            self.cra |= CR_C1_FLAG;
+        }
+        self.ca1 = level;
+    }
+
+    // also ends a CB2 write handshake
+    pub fn set_cb1 (&mut self, level: bool) {
+        if edge(self.crb & CR_C1_RISING != 0, self.cb1, level) {
+            self.crb |= CR_C1_FLAG;
+            if self.crb & CR_C2_MODE == C2_HANDSHAKE {
+                self.set_cb2(true);
+            }
+        }
+        self.cb1 = level;
+    }
+
+    // CA2 as an input; bit 4 of CRA picks the edge
+    pub fn set_ca2 (&mut self, level: bool) {
+        if self.cra & CR_C2_OUTPUT == 0 && edge(self.cra & CR_C2_RISING != 0, self.ca2, level) {
+            self.cra |= CR_C2_FLAG;
+        }
+        self.ca2 = level;
+    }
+
+    fn set_cb2 (&mut self, level: bool) {
+        self.cb2 = level;
+        self.pins.cb2_out(level);
+    }
+
+    fn drive (&mut self) {
+        let (port_a, port_b) = (self.port_a(), self.port_b());
+        self.pins.drive(port_a, port_b);
+    }
+
+    // control registers as a write leaves them: the flags are read-only
+    fn control (old: u8, value: u8) -> u8 {
+        (old & (CR_C1_FLAG | CR_C2_FLAG)) | (value & !(CR_C1_FLAG | CR_C2_FLAG))
+    }
+}
+
+// whether old to level is the active edge
+fn edge (rising: bool, old: bool, level: bool) -> bool {
+    old != level && level == rising
+}
+
+// a flag raised with its interrupt enabled; C2 only interrupts as an input
+fn irq (control: u8) -> bool {
+    let c1 = control & (CR_C1_FLAG | CR_C1_IRQ) == CR_C1_FLAG | CR_C1_IRQ;
+    let c2 = control & CR_C2_OUTPUT == 0 && control & (CR_C2_FLAG | CR_C2_IRQ) == CR_C2_FLAG | CR_C2_IRQ;
+    c1 || c2
+}
+
+impl Device for Pia {
+    fn name (&self) -> &'static str {
+        "pia6821"
+    }
+
+    // port A reads its pins; port B reads back its outputs and the pins of its inputs
+    fn read (&mut self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            PORT_A if self.cra & CR_OUTPUT_REGISTER != 0 => {
+                self.cra &= !(CR_C1_FLAG | CR_C2_FLAG);
+                let (sensed, _) = self.pins.sense();
+                sensed & self.port_a()
+            }
+            PORT_B if self.crb & CR_OUTPUT_REGISTER != 0 => {
+                self.crb &= !(CR_C1_FLAG | CR_C2_FLAG);
+                let (_, sensed) = self.pins.sense();
+                (self.orb & self.ddrb) | (sensed & !self.ddrb)
+            }
+            offset => self.peek(offset),
+        }
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        match offset & 0x03 {
+            PORT_A if self.cra & CR_OUTPUT_REGISTER != 0 => self.port_a(),
+            PORT_A => self.ddra,
+            CRA => self.cra,
+            PORT_B if self.crb & CR_OUTPUT_REGISTER != 0 => self.port_b(),
+            PORT_B => self.ddrb,
+            _ => self.crb,
+        }
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        match offset & 0x03 {
+            PORT_A if self.cra & CR_OUTPUT_REGISTER != 0 => self.ora = value,
+            PORT_A => self.ddra = value,
+            CRA => self.cra = Pia::control(self.cra, value),
+            // a write to ORB starts a CB2 handshake or pulse
+            PORT_B if self.crb & CR_OUTPUT_REGISTER != 0 => {
+                self.orb = value;
+                self.drive();
+                match self.crb & CR_C2_MODE {
+                    C2_HANDSHAKE => self.set_cb2(false),
+                    C2_PULSE => {
+                        self.set_cb2(false);
+                        self.set_cb2(true);
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            PORT_B => self.ddrb = value,
+            _ => {
+                self.crb = Pia::control(self.crb, value);
+                if self.crb & (CR_C2_OUTPUT | CR_C2_RISING) == C2_MANUAL {
+                    // in manual mode bit 3 is the level
+                    self.set_cb2(self.crb & CR_C2_IRQ != 0);
+                }
+            }
+        }
+        self.drive();
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.pins.tick(cycles);
+    }
+
+    fn irq (&self) -> bool {
+        irq(self.cra) || irq(self.crb)
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        let lines = [self.ca1, self.ca2, self.cb1, self.cb2].map(|level| level as u8);
+        let mut state = vec![self.ora, self.orb, self.ddra, self.ddrb, self.cra, self.crb];
+        state.extend_from_slice(&lines);
+        state
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        if data.len() != STATE_LEN {
+            return Err("Invalid PIA state".into());
+        }
+        (self.ora, self.orb, self.ddra, self.ddrb, self.cra, self.crb) = (data[0], data[1], data[2], data[3], data[4], data[5]);
+        (self.ca1, self.ca2, self.cb1, self.cb2) = (data[6] != 0, data[7] != 0, data[8] != 0, data[9] != 0);
+        self.drive();
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::error::Error;
+
+use super::Device;
+
+// every read returns a fresh pseudo-random byte (xorshift32); writes are ignored
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    // the same seed gives the same sequence, which keeps tests repeatable
+    pub fn new (seed: u32) -> Self {
+        Random { state: seed.max(1) }
+    }
+
+    pub fn from_time () -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.subsec_nanos());
+        Random::new(nanos)
+    }
+
+    fn next (&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+}
+
+impl Device for Random {
+    fn name (&self) -> &'static str {
+        "random"
+    }
+
+    fn read (&mut self, _offset: u16) -> u8 {
+        self.next() as u8
+    }
+
+    // the byte the next read would return, without advancing
+    fn peek (&self, _offset: u16) -> u8 {
+        Random { state: self.state }.next() as u8
+    }
+
+    fn write (&mut self, _offset: u16, _value: u8) {}
+
+    fn save_state (&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let state: [u8; 4] = data.try_into().map_err(|_| "Invalid random device state")?;
+        self.state = u32::from_le_bytes(state).max(1);
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use super::Device;
+
+// read-only memory: writes are ignored, and reads past the end of the image float high
+pub struct Rom {
+    data: Vec<u8>,
+}
+
+impl Rom {
+    pub fn new (data: Vec<u8>) -> Self {
+        Rom { data }
+    }
+
+    pub fn len (&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl Device for Rom {
+    fn name (&self) -> &'static str {
+        "rom"
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        self.peek(offset)
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        self.data.get(offset as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write (&mut self, _offset: u16, _value: u8) {}
+}
@@ -0,0 +1,483 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+use super::Device;
+
+/*
+ *  MOS 6522 Versatile Interface Adapter, sixteen registers repeated across its range:
+ *      0  ORB/IRB    4  T1C-L   8  T2C-L   12  PCR
+ *      1  ORA/IRA    5  T1C-H   9  T2C-H   13  IFR
+ *      2  DDRB       6  T1L-L  10  SR      14  IER
+ *      3  DDRA       7  T1L-H  11  ACR     15  ORA/IRA, no handshake
+ *
+ *  Timers count down once per CPU cycle. T1 is one-shot or free-running, optionally toggling
+ *  PB7; T2 is one-shot only, and holds in pulse-counting mode since nothing drives PB6. The
+ *  shift register works under T2 and under the system clock; its CB1-clocked modes never
+ *  shift. CA1 and CB1 edges set their flags through set_ca1 and set_cb1. The CA2/CB2
+ *  handshakes and input latching are not emulated.
+ */
+
+pub const ORB: u16 = 0;
+pub const ORA: u16 = 1;
+pub const DDRB: u16 = 2;
+pub const DDRA: u16 = 3;
+pub const T1C_L: u16 = 4;
+pub const T1C_H: u16 = 5;
+pub const T1L_L: u16 = 6;
+pub const T1L_H: u16 = 7;
+pub const T2C_L: u16 = 8;
+pub const T2C_H: u16 = 9;
+pub const SR: u16 = 10;
+pub const ACR: u16 = 11;
+pub const PCR: u16 = 12;
+pub const IFR: u16 = 13;
+pub const IER: u16 = 14;
+pub const ORA_NO_HANDSHAKE: u16 = 15;
+
+// interrupt flag and enable bits
+pub const IRQ_CA2: u8 = 0x01;
+pub const IRQ_CA1: u8 = 0x02;
+pub const IRQ_SR: u8 = 0x04;
+pub const IRQ_CB2: u8 = 0x08;
+pub const IRQ_CB1: u8 = 0x10;
+pub const IRQ_T2: u8 = 0x20;
+pub const IRQ_T1: u8 = 0x40;
+// IFR: set while any enabled flag is; IER: set or clear the other bits written
+const IRQ_ANY: u8 = 0x80;
+
+const ACR_T1_PB7: u8 = 0x80;
+const ACR_T1_FREE_RUN: u8 = 0x40;
+const ACR_T2_PULSE_COUNT: u8 = 0x20;
+const PB7: u8 = 0x80;
+
+const STATE_LEN: usize = 27;
+
+/*
+ *  Whatever is wired to the ports. drive is called whenever the levels the VIA puts out
+ *  change, with pins it doesn't drive pulled high; sense gives the levels on the pins it
+ *  reads. CB2 carries the shift register's data. tick passes on the VIA's clock, for
+ *  peripherals that keep time.
+ */
+pub trait Pins {
+    fn drive (&mut self, port_a: u8, port_b: u8);
+
+    fn sense (&mut self) -> (u8, u8) {
+        (0xFF, 0xFF)
+    }
+
+    fn cb2_out (&mut self, _level: bool) {}
+
+    fn cb2_in (&mut self) -> bool {
+        true
+    }
+
+    fn tick (&mut self, _cycles: u64) {}
+}
+
+impl<P: Pins> Pins for Rc<RefCell<P>> {
+    fn drive (&mut self, port_a: u8, port_b: u8) {
+        self.borrow_mut().drive(port_a, port_b)
+    }
+
+    fn sense (&mut self) -> (u8, u8) {
+        self.borrow_mut().sense()
+    }
+
+    fn cb2_out (&mut self, level: bool) {
+        self.borrow_mut().cb2_out(level)
+    }
+
+    fn cb2_in (&mut self) -> bool {
+        self.borrow_mut().cb2_in()
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.borrow_mut().tick(cycles)
+    }
+}
+
+// nothing connected: outputs go nowhere and inputs float high
+struct Unconnected;
+
+impl Pins for Unconnected {
+    fn drive (&mut self, _port_a: u8, _port_b: u8) {}
+}
+
+pub struct Via {
+    pins: Box<dyn Pins>,
+    ora: u8,
+    orb: u8,
+    ddra: u8,
+    ddrb: u8,
+    t1_counter: u16,
+    t1_latch: u16,
+    // whether T1 interrupts at its next timeout; free-running mode always does
+    t1_armed: bool,
+    // free-running T1 sits at $FFFF for a cycle after timing out, then reloads
+    t1_reload: bool,
+    t1_pb7: bool,
+    t2_counter: u16,
+    t2_latch_low: u8,
+    t2_armed: bool,
+    sr: u8,
+    // bits left to shift, and cycles toward the next one
+    sr_bits: u8,
+    sr_clock: u32,
+    acr: u8,
+    pcr: u8,
+    ifr: u8,
+    ier: u8,
+    ca1: bool,
+    cb1: bool,
+}
+
+impl Default for Via {
+    fn default () -> Self {
+        Via::new()
+    }
+}
+
+impl Via {
+    pub fn new () -> Self {
+        Via {
+            pins: Box::new(Unconnected),
+            ora: 0,
+            orb: 0,
+            ddra: 0,
+            ddrb: 0,
+            t1_counter: 0xFFFF,
+            t1_latch: 0xFFFF,
+            t1_armed: false,
+            t1_reload: false,
+            t1_pb7: true,
+            t2_counter: 0xFFFF,
+            t2_latch_low: 0xFF,
+            t2_armed: false,
+            sr: 0,
+            sr_bits: 0,
+            sr_clock: 0,
+            acr: 0,
+            pcr: 0,
+            ifr: 0,
+            ier: 0,
+            ca1: true,
+            cb1: true,
+        }
+    }
+
+    // wires something to the ports, replacing whatever was there
+    pub fn connect (&mut self, pins: impl Pins + 'static) {
+        self.pins = Box::new(pins);
+        self.drive();
+    }
+
+    // the levels the VIA drives, with input pins high
+    pub fn port_a (&self) -> u8 {
+        self.ora | !self.ddra
+    }
+
+    pub fn port_b (&self) -> u8 {
+        let levels = self.orb | !self.ddrb;
+        if self.acr & ACR_T1_PB7 != 0 {
+            (levels & !PB7) | if self.t1_pb7 { PB7 } else { 0 }
+        } else {
+            levels
+        }
+    }
+
+    // CA1 and CB1 flag the edge PCR selects: falling by default, rising with bit 0 or 4 set
+    pub fn set_ca1 (&mut self, level: bool) {
+        if self.ca1 != level && level == (self.pcr & 0x01 != 0) {
+            self.ifr |= IRQ_CA1;
+        }
+        self.ca1 = level;
+    }
+
+    pub fn set_cb1 (&mut self, level: bool) {
+        if self.cb1 != level && level == (self.pcr & 0x10 != 0) {
+            self.ifr |= IRQ_CB1;
+        }
+        self.cb1 = level;
+    }
+
+    fn drive (&mut self) {
+        let (port_a, port_b) = (self.port_a(), self.port_b());
+        self.pins.drive(port_a, port_b);
+    }
+
+    // outputs read back what they drive, inputs what is on the pin
+    fn input_a (&mut self) -> u8 {
+        let (sensed, _) = self.pins.sense();
+        (self.ora & self.ddra) | (sensed & !self.ddra)
+    }
+
+    fn input_b (&mut self) -> u8 {
+        let (_, sensed) = self.pins.sense();
+        (self.port_b() & self.ddrb) | (sensed & !self.ddrb)
+    }
+
+    fn shift_mode (&self) -> u8 {
+        (self.acr >> 2) & 0x07
+    }
+
+    // reading or writing SR restarts the shift
+    fn start_shift (&mut self) {
+        self.ifr &= !IRQ_SR;
+        if self.shift_mode() != 0 {
+            self.sr_bits = 8;
+            self.sr_clock = 0;
+        }
+    }
+
+    fn register (&self, offset: u16) -> u8 {
+        match offset {
+            T1C_L => self.t1_counter as u8,
+            T1C_H => (self.t1_counter >> 8) as u8,
+            T1L_L => self.t1_latch as u8,
+            T1L_H => (self.t1_latch >> 8) as u8,
+            T2C_L => self.t2_counter as u8,
+            T2C_H => (self.t2_counter >> 8) as u8,
+            SR => self.sr,
+            ACR => self.acr,
+            PCR => self.pcr,
+            IFR => self.ifr | if self.irq() { IRQ_ANY } else { 0 },
+            IER => self.ier | IRQ_ANY,
+            DDRB => self.ddrb,
+            DDRA => self.ddra,
+            _ => 0,
+        }
+    }
+
+    /*
+     *  T1 reaches its timeout a cycle after counting through zero, showing $FFFF. Free-running
+     *  it reloads from the latch on the next cycle, so it times out every latch + 2 cycles.
+     */
+    fn tick_t1 (&mut self, mut cycles: u64) {
+        if self.t1_reload && cycles > 0 {
+            self.t1_counter = self.t1_latch;
+            self.t1_reload = false;
+            cycles -= 1;
+        }
+        if cycles <= self.t1_counter as u64 {
+            self.t1_counter -= cycles as u16;
+            return;
+        }
+        let mut left = cycles - self.t1_counter as u64 - 1;
+        let mut timeouts = 1;
+
+        if self.acr & ACR_T1_FREE_RUN != 0 {
+            let period = self.t1_latch as u64 + 2;
+            timeouts += left / period;
+            left %= period;
+            self.t1_counter = if left == 0 { 0xFFFF } else { self.t1_latch - (left - 1) as u16 };
+            self.t1_reload = left == 0;
+            self.ifr |= IRQ_T1;
+            if timeouts % 2 == 1 {
+                self.t1_pb7 = !self.t1_pb7;
+            }
+        } else {
+            self.t1_counter = 0xFFFF_u16.wrapping_sub(left as u16);
+            if self.t1_armed {
+                self.ifr |= IRQ_T1;
+                self.t1_pb7 = true;
+            }
+            self.t1_armed = false;
+        }
+        if self.acr & ACR_T1_PB7 != 0 {
+            self.drive();
+        }
+    }
+
+    fn tick_t2 (&mut self, cycles: u64) {
+        if self.acr & ACR_T2_PULSE_COUNT != 0 {
+            return;
+        }
+        if cycles > self.t2_counter as u64 && self.t2_armed {
+            self.ifr |= IRQ_T2;
+            self.t2_armed = false;
+        }
+        self.t2_counter = self.t2_counter.wrapping_sub(cycles as u16);
+    }
+
+    /*
+     *  One bit every two cycles under the system clock, or every 2 * (T2 latch low + 2) under
+     *  T2. Shifting out recirculates, so free-running mode (4) repeats the byte forever and
+     *  never interrupts.
+     */
+    fn tick_shift (&mut self, cycles: u64) {
+        let mode = self.shift_mode();
+        let period = match mode {
+            1 | 4 | 5 => 2 * (self.t2_latch_low as u32 + 2),
+            2 | 6 => 2,
+            _ => return,
+        };
+        if self.sr_bits == 0 && mode != 4 {
+            return;
+        }
+
+        self.sr_clock = self.sr_clock.saturating_add(cycles.min(u32::MAX as u64) as u32);
+        while self.sr_clock >= period && (self.sr_bits > 0 || mode == 4) {
+            self.sr_clock -= period;
+            if mode >= 4 {
+                let bit = self.sr & 0x80 != 0;
+                self.sr = (self.sr << 1) | bit as u8;
+                self.pins.cb2_out(bit);
+            } else {
+                self.sr = (self.sr << 1) | self.pins.cb2_in() as u8;
+            }
+
+            if mode != 4 {
+                self.sr_bits -= 1;
+                if self.sr_bits == 0 {
+                    self.ifr |= IRQ_SR;
+                    self.sr_clock = 0;
+                }
+            }
+        }
+    }
+}
+
+impl Device for Via {
+    fn name (&self) -> &'static str {
+        "via6522"
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        match offset & 0x0F {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                self.input_b()
+            }
+            ORA => {
+                self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                self.input_a()
+            }
+            ORA_NO_HANDSHAKE => self.input_a(),
+            T1C_L => {
+                self.ifr &= !IRQ_T1;
+                self.t1_counter as u8
+            }
+            T2C_L => {
+                self.ifr &= !IRQ_T2;
+                self.t2_counter as u8
+            }
+            SR => {
+                self.start_shift();
+                self.sr
+            }
+            offset => self.register(offset),
+        }
+    }
+
+    // ports show the levels last driven, without asking the pins
+    fn peek (&self, offset: u16) -> u8 {
+        match offset & 0x0F {
+            ORB => self.port_b(),
+            ORA | ORA_NO_HANDSHAKE => self.port_a(),
+            offset => self.register(offset),
+        }
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        match offset & 0x0F {
+            ORB => {
+                self.ifr &= !(IRQ_CB1 | IRQ_CB2);
+                self.orb = value;
+                self.drive();
+            }
+            ORA | ORA_NO_HANDSHAKE => {
+                if offset & 0x0F == ORA {
+                    self.ifr &= !(IRQ_CA1 | IRQ_CA2);
+                }
+                self.ora = value;
+                self.drive();
+            }
+            DDRB => {
+                self.ddrb = value;
+                self.drive();
+            }
+            DDRA => {
+                self.ddra = value;
+                self.drive();
+            }
+            T1C_L | T1L_L => self.t1_latch = (self.t1_latch & 0xFF00) | value as u16,
+            // loads and starts the counter; PB7 goes low for a one-shot
+            T1C_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (value as u16) << 8;
+                self.t1_counter = self.t1_latch;
+                self.t1_armed = true;
+                self.t1_reload = false;
+                self.ifr &= !IRQ_T1;
+                self.t1_pb7 = false;
+                if self.acr & ACR_T1_PB7 != 0 {
+                    self.drive();
+                }
+            }
+            T1L_H => {
+                self.t1_latch = (self.t1_latch & 0x00FF) | (value as u16) << 8;
+                self.ifr &= !IRQ_T1;
+            }
+            T2C_L => self.t2_latch_low = value,
+            T2C_H => {
+                self.t2_counter = (value as u16) << 8 | self.t2_latch_low as u16;
+                self.t2_armed = true;
+                self.ifr &= !IRQ_T2;
+            }
+            SR => {
+                self.sr = value;
+                self.start_shift();
+            }
+            ACR => {
+                self.acr = value;
+                self.drive();
+            }
+            PCR => self.pcr = value,
+            IFR => self.ifr &= !(value & !IRQ_ANY),
+            IER if value & IRQ_ANY != 0 => self.ier |= value & !IRQ_ANY,
+            IER => self.ier &= !value,
+            _ => {}
+        }
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.tick_t1(cycles);
+        self.tick_t2(cycles);
+        self.tick_shift(cycles);
+        self.pins.tick(cycles);
+    }
+
+    fn irq (&self) -> bool {
+        self.ifr & self.ier & !IRQ_ANY != 0
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        let mut state = vec![self.ora, self.orb, self.ddra, self.ddrb];
+        state.extend_from_slice(&self.t1_counter.to_le_bytes());
+        state.extend_from_slice(&self.t1_latch.to_le_bytes());
+        state.extend_from_slice(&[self.t1_armed as u8, self.t1_reload as u8, self.t1_pb7 as u8]);
+        state.extend_from_slice(&self.t2_counter.to_le_bytes());
+        state.extend_from_slice(&[self.t2_latch_low, self.t2_armed as u8, self.sr, self.sr_bits]);
+        state.extend_from_slice(&self.sr_clock.to_le_bytes());
+        state.extend_from_slice(&[self.acr, self.pcr, self.ifr, self.ier, self.ca1 as u8, self.cb1 as u8]);
+        state
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        if data.len() != STATE_LEN {
+            return Err("Invalid VIA state".into());
+        }
+        let word = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+
+        (self.ora, self.orb, self.ddra, self.ddrb) = (data[0], data[1], data[2], data[3]);
+        (self.t1_counter, self.t1_latch) = (word(4), word(6));
+        (self.t1_armed, self.t1_reload, self.t1_pb7) = (data[8] != 0, data[9] != 0, data[10] != 0);
+        self.t2_counter = word(11);
+        (self.t2_latch_low, self.t2_armed, self.sr, self.sr_bits) = (data[13], data[14] != 0, data[15], data[16].min(8));
+        self.sr_clock = u32::from_le_bytes([data[17], data[18], data[19], data[20]]);
+        (self.acr, self.pcr, self.ifr, self.ier) = (data[21], data[22], data[23] & !IRQ_ANY, data[24] & !IRQ_ANY);
+        (self.ca1, self.cb1) = (data[25] != 0, data[26] != 0);
+        self.drive();
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use crate::debugger::MinCPU;
+use crate::Memory;
+
+/*
+ *  Expressions over registers, flags and memory, used for conditional breakpoints:
+ *      A == $10 && [$0200] > 3
+ *  Registers: A X Y SP PC P, flags: N V B D I Z C (0 or 1), memory: [addr] (one byte).
+ *  Numbers are $hex, %binary or decimal. Comparisons and logic give 1 or 0, anything non-zero
+ *  is true. Arithmetic is 64-bit and wraps rather than overflowing.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(Register),
+    // status register bit mask
+    Flag(u8),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 19] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "(", ")", "[", "]", "=",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c == '$' || c == '%' || c.is_ascii_digit() {
+            let radix = match c {
+                '$' => 16,
+                '%' => 2,
+                _ => 10,
+            };
+            let start = if c.is_ascii_digit() { i } else { i + 1 };
+            let mut end = start;
+            while end < chars.len() && chars[end].is_digit(radix) {
+                end += 1;
+            }
+            let digits: String = chars[start..end].iter().collect();
+            let value = i64::from_str_radix(&digits, radix)
+                .map_err(|_| format!("Invalid number at '{}'", chars[i..].iter().collect::<String>()))?;
+            tokens.push(Token::Number(value));
+            i = end;
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() {
+            let mut end = i;
+            while end < chars.len() && chars[end].is_ascii_alphanumeric() {
+                end += 1;
+            }
+            tokens.push(Token::Ident(chars[i..end].iter().collect::<String>().to_ascii_uppercase()));
+            i = end;
+            continue;
+        }
+
+        let rest: String = chars[i..].iter().collect();
+        let op = OPERATORS
+            .iter()
+            .find(|op| rest.starts_with(**op))
+            .ok_or_else(|| format!("Unexpected character '{}'", c))?;
+        // a lone '=' is accepted as '=='
+        tokens.push(Token::Op(if *op == "=" { "==" } else { op }));
+        i += op.len();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+// lowest precedence first
+const LEVELS: [&[(&str, BinaryOp)]; 7] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Eq),
+        ("!=", BinaryOp::Ne),
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+];
+
+impl Parser {
+    fn peek_op (&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect (&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}'", op))
+        }
+    }
+
+    fn binary (&mut self, level: usize) -> Result<Expr, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(&(_, op)) = self
+            .peek_op()
+            .and_then(|token| LEVELS[level].iter().find(|(text, _)| *text == token))
+        {
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary (&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary (&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Op("(") => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Op("[") => {
+                let inner = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(inner)))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "A" => Ok(Expr::Register(Register::A)),
+                "X" => Ok(Expr::Register(Register::X)),
+                "Y" => Ok(Expr::Register(Register::Y)),
+                "SP" | "S" => Ok(Expr::Register(Register::SP)),
+                "PC" => Ok(Expr::Register(Register::PC)),
+                "P" => Ok(Expr::Register(Register::P)),
+                "N" => Ok(Expr::Flag(crate::cpu::NEGATIVE_FLAG)),
+                "V" => Ok(Expr::Flag(crate::cpu::OVERFLOW_FLAG)),
+                "B" => Ok(Expr::Flag(crate::cpu::BREAK_FLAG)),
+                "D" => Ok(Expr::Flag(crate::cpu::DECIMAL_FLAG)),
+                "I" => Ok(Expr::Flag(crate::cpu::INTERRUPT_FLAG)),
+                "Z" => Ok(Expr::Flag(crate::cpu::ZERO_FLAG)),
+                "C" => Ok(Expr::Flag(crate::cpu::CARRY_FLAG)),
+                _ => Err(format!("Unknown register or flag: {}", name)),
+            },
+            Token::Op(op) => Err(format!("Unexpected '{}'", op)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse (text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+        let expr = parser.binary(0)?;
+
+        if parser.pos != parser.tokens.len() {
+            return Err(format!("Unexpected input after expression: {}", text).into());
+        }
+        Ok(expr)
+    }
+
+    pub fn eval (&self, cpu: &MinCPU, memory: &Memory) -> i64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => match register {
+                Register::A => cpu.a as i64,
+                Register::X => cpu.x as i64,
+                Register::Y => cpu.y as i64,
+                Register::SP => cpu.sp as i64,
+                Register::PC => cpu.pc as i64,
+                Register::P => cpu.status as i64,
+            },
+            Expr::Flag(mask) => (cpu.status & mask != 0) as i64,
+            Expr::Memory(addr) => memory.peek(addr.eval(cpu, memory) as u16) as i64,
+            Expr::Not(inner) => (inner.eval(cpu, memory) == 0) as i64,
+            Expr::Negate(inner) => inner.eval(cpu, memory).wrapping_neg(),
+            Expr::Binary(op, left, right) => {
+                let l = left.eval(cpu, memory);
+                // short circuit so [..] on the right isn't read needlessly
+                match op {
+                    BinaryOp::Or if l != 0 => return 1,
+                    BinaryOp::And if l == 0 => return 0,
+                    _ => {}
+                }
+                let r = right.eval(cpu, memory);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (r != 0) as i64,
+                    BinaryOp::Eq => (l == r) as i64,
+                    BinaryOp::Ne => (l != r) as i64,
+                    BinaryOp::Lt => (l < r) as i64,
+                    BinaryOp::Le => (l <= r) as i64,
+                    BinaryOp::Gt => (l > r) as i64,
+                    BinaryOp::Ge => (l >= r) as i64,
+                    BinaryOp::BitOr => l | r,
+                    BinaryOp::BitXor => l ^ r,
+                    BinaryOp::BitAnd => l & r,
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                }
+            }
+        }
+    }
+
+    pub fn is_true (&self, cpu: &MinCPU, memory: &Memory) -> bool {
+        self.eval(cpu, memory) != 0
+    }
+}
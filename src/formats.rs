@@ -0,0 +1,160 @@
+use crate::linker::LinkedImage;
+use crate::loader::Assembly;
+
+/*
+ *  Writers for assembled or linked programs.
+ *  raw and prg are flat (gaps between blocks are zero filled), hex and srec keep addresses per
+ *  record, image is the whole 64 KiB address space.
+ */
+
+// bytes that belong at a fixed address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub addr: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Raw,
+    // Commodore PRG: little endian load address followed by the program
+    Prg,
+    IntelHex,
+    SRecord,
+    MemoryImage,
+}
+
+const RECORD_SIZE: usize = 16;
+
+impl OutputFormat {
+    pub fn from_name (name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" | "bin" => Some(OutputFormat::Raw),
+            "prg" => Some(OutputFormat::Prg),
+            "hex" | "ihex" => Some(OutputFormat::IntelHex),
+            "srec" | "s19" => Some(OutputFormat::SRecord),
+            "image" => Some(OutputFormat::MemoryImage),
+            _ => None,
+        }
+    }
+
+    pub fn extension (self) -> &'static str {
+        match self {
+            OutputFormat::Raw => "bin",
+            OutputFormat::Prg => "prg",
+            OutputFormat::IntelHex => "hex",
+            OutputFormat::SRecord => "s19",
+            OutputFormat::MemoryImage => "img",
+        }
+    }
+}
+
+impl Assembly {
+    pub fn blocks (&self) -> Vec<Block> {
+        vec![Block { addr: self.origin, data: self.bytes.clone() }]
+    }
+}
+
+impl LinkedImage {
+    pub fn blocks (&self) -> Vec<Block> {
+        self.areas
+            .iter()
+            .map(|area| Block { addr: area.start, data: area.data.clone() })
+            .collect()
+    }
+}
+
+pub fn write_output(format: OutputFormat, blocks: &[Block]) -> Vec<u8> {
+    match format {
+        OutputFormat::Raw => flatten(blocks).1,
+        OutputFormat::Prg => {
+            let (start, data) = flatten(blocks);
+            let mut out = start.to_le_bytes().to_vec();
+            out.extend(data);
+            out
+        }
+        OutputFormat::IntelHex => write_intel_hex(blocks).into_bytes(),
+        OutputFormat::SRecord => write_srecord(blocks).into_bytes(),
+        OutputFormat::MemoryImage => {
+            let mut image = vec![0; 0x10000];
+            for block in blocks {
+                for (offset, &byte) in block.data.iter().enumerate() {
+                    image[(block.addr as usize + offset) & 0xFFFF] = byte;
+                }
+            }
+            image
+        }
+    }
+}
+
+// lowest address and the bytes from there to the end of the highest block
+fn flatten(blocks: &[Block]) -> (u16, Vec<u8>) {
+    let start = blocks.iter().map(|block| block.addr).min().unwrap_or(0);
+    let mut data = Vec::new();
+
+    for block in blocks {
+        let offset = (block.addr - start) as usize;
+        if data.len() < offset + block.data.len() {
+            data.resize(offset + block.data.len(), 0);
+        }
+        data[offset..offset + block.data.len()].copy_from_slice(&block.data);
+    }
+
+    (start, data)
+}
+
+pub fn write_intel_hex(blocks: &[Block]) -> String {
+    let mut out = String::new();
+
+    for block in blocks {
+        for (index, chunk) in block.data.chunks(RECORD_SIZE).enumerate() {
+            let addr = block.addr.wrapping_add((index * RECORD_SIZE) as u16);
+            out.push_str(&intel_hex_record(0x00, addr, chunk));
+        }
+    }
+    out.push_str(&intel_hex_record(0x01, 0, &[]));
+
+    out
+}
+
+// :LLAAAATT DD.. CC, checksum is the two's complement of the sum of every other byte
+fn intel_hex_record(record_type: u8, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (addr >> 8) as u8, (addr & 0xFF) as u8, record_type];
+    bytes.extend_from_slice(data);
+
+    let sum = bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    bytes.push(sum.wrapping_neg());
+
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", hex)
+}
+
+// S0 header, S1 data records, S9 with the start address (the first block)
+pub fn write_srecord(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    out.push_str(&srecord('0', 0, b"HDR"));
+
+    for block in blocks {
+        for (index, chunk) in block.data.chunks(RECORD_SIZE).enumerate() {
+            let addr = block.addr.wrapping_add((index * RECORD_SIZE) as u16);
+            out.push_str(&srecord('1', addr, chunk));
+        }
+    }
+
+    let entry = blocks.first().map_or(0, |block| block.addr);
+    out.push_str(&srecord('9', entry, &[]));
+
+    out
+}
+
+// STCCAAAA DD.. SS, count covers address, data and checksum; checksum is the ones' complement
+fn srecord(record_type: char, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![(data.len() + 3) as u8, (addr >> 8) as u8, (addr & 0xFF) as u8];
+    bytes.extend_from_slice(data);
+
+    let sum = bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+    bytes.push(!sum);
+
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", record_type, hex)
+}
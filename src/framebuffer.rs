@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::Path;
+
+/*
+ *  An RGB image of a machine's screen, with PPM and PNG encoders for snapshots. The PNG
+ *  writer stores its image data uncompressed, which keeps it dependency free; screens here
+ *  are small.
+ */
+
+pub type Rgb = [u8; 3];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Rgb>,
+}
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+// the most a stored deflate block can hold
+const STORED_BLOCK_MAX: usize = 0xFFFF;
+
+impl Framebuffer {
+    pub fn new (width: usize, height: usize) -> Self {
+        Framebuffer { width, height, pixels: vec![[0, 0, 0]; width * height] }
+    }
+
+    pub fn pixel (&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel (&mut self, x: usize, y: usize, color: Rgb) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // rows of pixels, top to bottom
+    pub fn rows (&self) -> impl Iterator<Item = &[Rgb]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    // each pixel becomes a factor x factor square
+    pub fn scaled (&self, factor: usize) -> Framebuffer {
+        let mut scaled = Framebuffer::new(self.width * factor, self.height * factor);
+        for y in 0..scaled.height {
+            for x in 0..scaled.width {
+                scaled.set_pixel(x, y, self.pixel(x / factor, y / factor));
+            }
+        }
+        scaled
+    }
+
+    // binary PPM (P6)
+    pub fn to_ppm (&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        out.extend(self.pixels.iter().flatten());
+        out
+    }
+
+    // 8-bit truecolor PNG
+    pub fn to_png (&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // bit depth 8, color type 2 (RGB), default compression, filter and interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        // every scanline starts with filter type 0
+        let mut scanlines = Vec::with_capacity(self.height * (self.width * 3 + 1));
+        for row in self.rows() {
+            scanlines.push(0);
+            scanlines.extend(row.iter().flatten());
+        }
+
+        let mut out = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut out, b"IHDR", &header);
+        png_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+        png_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    // PNG or PPM, by the file's extension
+    pub fn save (&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("png") => self.to_png(),
+            Some(ext) if ext.eq_ignore_ascii_case("ppm") => self.to_ppm(),
+            _ => return Err(format!("Screenshots are .png or .ppm: {}", path).into()),
+        };
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+fn png_chunk (out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// a zlib stream of uncompressed deflate blocks
+fn zlib_stored (data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(STORED_BLOCK_MAX).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32 (data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32 (data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
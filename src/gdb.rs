@@ -0,0 +1,428 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::cpu::CPU;
+use crate::debugger::{BreakOn, StopReason, WatchKind};
+use crate::Memory;
+
+/*
+ *  GDB remote serial protocol stub over TCP, mapped onto the CPU's Debugger.
+ *
+ *  Registers, in 'g' packet order: A X Y SP P as one byte each, then PC as two bytes little
+ *  endian. target.xml (served through qXfer) describes the same layout so frontends without
+ *  a built in 6502 architecture can still show them.
+ *  Z0/Z1 become address breakpoints, Z2/Z3/Z4 write/read/access watchpoints.
+ */
+
+pub const DEFAULT_PORT: u16 = 6502;
+
+// instructions run between checks for a Ctrl-C from the client
+const POLL_INTERVAL: usize = 10_000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.mos.6502">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// SIGTRAP for breakpoints and steps, SIGINT when the client interrupts, SIGILL at an opcode the CPU can't run
+const SIGTRAP: &str = "S05";
+const SIGINT: &str = "S02";
+const SIGILL: &str = "S04";
+
+pub struct GdbSession {
+    // (Z type, addr, length) -> debugger id, so z packets can remove what Z added
+    points: HashMap<(u8, u16, u16), usize>,
+    pub no_ack: bool,
+    pub finished: bool,
+}
+
+impl Default for GdbSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GdbSession {
+    pub fn new () -> Self {
+        GdbSession {
+            points: HashMap::new(),
+            no_ack: false,
+            finished: false,
+        }
+    }
+
+    /*
+     *  Handles one packet body (without the $ and checksum) and returns the reply body, or
+     *  None when no reply is sent (k). interrupted is polled while continuing.
+     */
+    pub fn handle (&mut self, cpu: &mut CPU, memory: &mut Memory, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Option<String> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => SIGTRAP.to_string(),
+            Some(b'g') => encode_registers(cpu),
+            Some(b'G') => ok_or_error(decode_registers(cpu, &packet[1..])),
+            Some(b'p') => data_or_error(read_register(cpu, &packet[1..])),
+            Some(b'P') => ok_or_error(write_register(cpu, &packet[1..])),
+            Some(b'm') => data_or_error(read_memory(memory, &packet[1..])),
+            Some(b'M') => ok_or_error(write_memory(memory, &packet[1..])),
+            Some(b'c') => self.resume(cpu, memory, &packet[1..], false, interrupted),
+            Some(b's') => self.resume(cpu, memory, &packet[1..], true, interrupted),
+            Some(b'Z') => ok_or_error(self.insert_point(cpu, &packet[1..])),
+            Some(b'z') => ok_or_error(self.remove_point(cpu, &packet[1..])),
+            Some(b'H') => String::from("OK"),
+            Some(b'D') => {
+                self.finished = true;
+                String::from("OK")
+            }
+            Some(b'k') => {
+                self.finished = true;
+                return None;
+            }
+            _ => self.query(packet),
+        };
+        Some(reply)
+    }
+
+    fn query (&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return String::from("PacketSize=1000;qXfer:features:read+;QStartNoAckMode+");
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return read_annex(TARGET_XML, range).unwrap_or_else(|| String::from("E01"));
+        }
+
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                String::from("OK")
+            }
+            "qAttached" => String::from("1"),
+            "qC" => String::from("QC1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            // anything unsupported gets an empty reply, which the protocol reads as "not supported"
+            _ => String::new(),
+        }
+    }
+
+    fn resume (&mut self, cpu: &mut CPU, memory: &mut Memory, addr: &str, single: bool, interrupted: &mut dyn FnMut() -> bool) -> String {
+        if let Ok(addr) = u16::from_str_radix(addr, 16) {
+            cpu.pc = addr;
+        }
+
+        if single {
+            if !cpu.implements(memory.peek(cpu.pc)) {
+                return SIGILL.to_string();
+            }
+            return cpu.step(memory).map_or_else(|| SIGTRAP.to_string(), |reason| stop_reply(cpu, reason));
+        }
+
+        loop {
+            for _ in 0..POLL_INTERVAL {
+                if !cpu.implements(memory.peek(cpu.pc)) {
+                    return SIGILL.to_string();
+                }
+                if let Some(reason) = cpu.step(memory) {
+                    return stop_reply(cpu, reason);
+                }
+            }
+            if interrupted() {
+                return SIGINT.to_string();
+            }
+        }
+    }
+
+    // type,addr,kind
+    fn parse_point (text: &str) -> Option<(u8, u16, u16)> {
+        let mut fields = text.split(',');
+        let kind = fields.next()?.parse::<u8>().ok()?;
+        let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let length = u16::from_str_radix(fields.next()?.split(';').next()?, 16).ok()?;
+        Some((kind, addr, length))
+    }
+
+    fn insert_point (&mut self, cpu: &mut CPU, text: &str) -> Result<(), ()> {
+        let key = Self::parse_point(text).ok_or(())?;
+        if self.points.contains_key(&key) {
+            return Ok(());
+        }
+
+        let (kind, addr, length) = key;
+        let end = addr.saturating_add(length.max(1) - 1);
+        let id = match kind {
+            0 | 1 => cpu.debugger.add_breakpoint(BreakOn::Address(addr), None),
+            2 => cpu.debugger.add_watchpoint(WatchKind::Write, addr, end, None),
+            3 => cpu.debugger.add_watchpoint(WatchKind::Read, addr, end, None),
+            4 => cpu.debugger.add_watchpoint(WatchKind::Access, addr, end, None),
+            _ => return Err(()),
+        };
+        self.points.insert(key, id);
+        Ok(())
+    }
+
+    fn remove_point (&mut self, cpu: &mut CPU, text: &str) -> Result<(), ()> {
+        let key = Self::parse_point(text).ok_or(())?;
+        let id = self.points.remove(&key).ok_or(())?;
+        if key.0 <= 1 {
+            cpu.debugger.remove_breakpoint(id);
+        } else {
+            cpu.debugger.remove_watchpoint(id);
+        }
+        Ok(())
+    }
+}
+
+// packets that either return data or fail with a generic error
+fn data_or_error (result: Result<String, ()>) -> String {
+    result.unwrap_or_else(|_| String::from("E01"))
+}
+
+// packets whose reply is just OK
+fn ok_or_error (result: Result<(), ()>) -> String {
+    data_or_error(result.map(|_| String::from("OK")))
+}
+
+fn stop_reply (cpu: &CPU, reason: StopReason) -> String {
+    match reason {
+        StopReason::Breakpoint(_) => SIGTRAP.to_string(),
+        StopReason::Watchpoint(hit) => {
+            let kind = cpu
+                .debugger
+                .watchpoints()
+                .iter()
+                .find(|watchpoint| watchpoint.id == hit.id)
+                .map_or(WatchKind::Write, |watchpoint| watchpoint.kind);
+            let name = match kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            format!("T05{}:{:x};", name, hit.addr)
+        }
+    }
+}
+
+fn encode_registers (cpu: &CPU) -> String {
+    let [pc_low, pc_high] = cpu.pc.to_le_bytes();
+    [cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status, pc_low, pc_high]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn decode_hex (text: &str) -> Result<Vec<u8>, ()> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| ()))
+        .collect()
+}
+
+fn decode_registers (cpu: &mut CPU, text: &str) -> Result<(), ()> {
+    let bytes = decode_hex(text)?;
+    let [a, x, y, sp, status, pc_low, pc_high] = bytes[..] else {
+        return Err(());
+    };
+    (cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status) = (a, x, y, sp, status);
+    cpu.pc = u16::from_le_bytes([pc_low, pc_high]);
+    Ok(())
+}
+
+fn read_register (cpu: &CPU, text: &str) -> Result<String, ()> {
+    let register = usize::from_str_radix(text, 16).map_err(|_| ())?;
+    let registers = encode_registers(cpu);
+    match register {
+        0..=4 => Ok(registers[register * 2..register * 2 + 2].to_string()),
+        5 => Ok(registers[10..].to_string()),
+        _ => Err(()),
+    }
+}
+
+fn write_register (cpu: &mut CPU, text: &str) -> Result<(), ()> {
+    let (register, value) = text.split_once('=').ok_or(())?;
+    let register = usize::from_str_radix(register, 16).map_err(|_| ())?;
+    let bytes = decode_hex(value)?;
+
+    match (register, &bytes[..]) {
+        (0, &[value]) => cpu.a = value,
+        (1, &[value]) => cpu.x = value,
+        (2, &[value]) => cpu.y = value,
+        (3, &[value]) => cpu.sp = value,
+        (4, &[value]) => cpu.status = value,
+        (5, &[low, high]) => cpu.pc = u16::from_le_bytes([low, high]),
+        _ => return Err(()),
+    }
+    Ok(())
+}
+
+// addr,length
+fn parse_range (text: &str) -> Result<(u16, usize), ()> {
+    let (addr, length) = text.split_once(',').ok_or(())?;
+    let addr = u16::from_str_radix(addr, 16).map_err(|_| ())?;
+    let length = usize::from_str_radix(length, 16).map_err(|_| ())?;
+    Ok((addr, length))
+}
+
+fn read_memory (memory: &Memory, text: &str) -> Result<String, ()> {
+    let (addr, length) = parse_range(text)?;
+    Ok((0..length.min(0x10000))
+        .map(|i| format!("{:02x}", memory.peek(addr.wrapping_add(i as u16))))
+        .collect())
+}
+
+fn write_memory (memory: &mut Memory, text: &str) -> Result<(), ()> {
+    let (range, data) = text.split_once(':').ok_or(())?;
+    let (addr, length) = parse_range(range)?;
+    let bytes = decode_hex(data)?;
+    if bytes.len() != length {
+        return Err(());
+    }
+    for (i, byte) in bytes.into_iter().enumerate() {
+        memory.write(addr.wrapping_add(i as u16), byte);
+    }
+    Ok(())
+}
+
+// offset,length into an annex; m means more follows, l means last
+fn read_annex (annex: &str, range: &str) -> Option<String> {
+    let (offset, length) = range.split_once(',')?;
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+    let length = usize::from_str_radix(length, 16).ok()?;
+    let start = offset.min(annex.len());
+    let end = (start + length).min(annex.len());
+    let marker = if end < annex.len() { 'm' } else { 'l' };
+    Some(format!("{}{}", marker, &annex[start..end]))
+}
+
+pub fn checksum (body: &str) -> u8 {
+    body.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))
+}
+
+// frames a reply body as $body#xx
+pub fn frame (body: &str) -> String {
+    format!("${}#{:02x}", body, checksum(body))
+}
+
+struct Connection {
+    stream: TcpStream,
+    pending: VecDeque<u8>,
+    // the client hung up while the CPU was running
+    closed: bool,
+}
+
+impl Connection {
+    fn read_byte (&mut self) -> io::Result<Option<u8>> {
+        if self.pending.is_empty() {
+            let mut buffer = [0; 1024];
+            let count = self.stream.read(&mut buffer)?;
+            self.pending.extend(&buffer[..count]);
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    // true if the client sent a Ctrl-C (0x03) since we last looked, or hung up
+    fn interrupted (&mut self) -> bool {
+        let mut buffer = [0; 1024];
+        if self.stream.set_nonblocking(true).is_ok() {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(count) => self.pending.extend(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => self.closed = true,
+            }
+            let _ = self.stream.set_nonblocking(false);
+        }
+        if self.closed {
+            return true;
+        }
+
+        match self.pending.iter().position(|&byte| byte == 0x03) {
+            Some(index) => {
+                self.pending.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the next packet body, acking it unless no-ack mode is on; None once the client hangs up
+    fn read_packet (&mut self, no_ack: bool) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                // acks, and interrupts that arrive while stopped
+                Some(_) => continue,
+            }
+
+            let mut body = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => body.push(byte),
+                }
+            }
+            let mut digits = [0; 2];
+            for digit in digits.iter_mut() {
+                *digit = self.read_byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+            }
+
+            let body = String::from_utf8_lossy(&body).into_owned();
+            let valid = std::str::from_utf8(&digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                == Some(checksum(&body));
+
+            if !no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(body));
+            }
+        }
+    }
+}
+
+// serves one client on an accepted stream until it detaches, kills or disconnects
+pub fn serve_stream (cpu: &mut CPU, memory: &mut Memory, stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut connection = Connection { stream, pending: VecDeque::new(), closed: false };
+    let mut session = GdbSession::new();
+
+    while !session.finished {
+        let no_ack = session.no_ack;
+        let Some(packet) = connection.read_packet(no_ack)? else {
+            break;
+        };
+
+        let reply = {
+            let connection = &mut connection;
+            session.handle(cpu, memory, &packet, &mut || connection.interrupted())
+        };
+        if connection.closed {
+            break;
+        }
+        if let Some(reply) = reply {
+            connection.stream.write_all(frame(&reply).as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// waits for one client on the listener and serves it
+pub fn serve (cpu: &mut CPU, memory: &mut Memory, listener: &TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    serve_stream(cpu, memory, stream)
+}
@@ -0,0 +1,336 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::formats::Block;
+use crate::{Memory, CPU};
+
+/*
+ *  Reads program images into memory and points the CPU at them.
+ *  Formats with an explicit start address (PRG load address, HEX start record, S9 record) set
+ *  PC to it; ROM images (iNES, or any other image covering $FFFC) start from the reset vector
+ *  they bring. The rest start at their lowest address, which for a raw binary is load_addr.
+ */
+
+pub const DEFAULT_LOAD_ADDR: u16 = 0x0600;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+
+const INES_MAGIC: &[u8; 4] = b"NES\x1A";
+const INES_HEADER_SIZE: usize = 16;
+const INES_TRAINER_SIZE: usize = 512;
+const INES_PRG_BANK_SIZE: usize = 0x4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Raw,
+    Prg,
+    IntelHex,
+    SRecord,
+    INes,
+}
+
+// where PC came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySource {
+    LoadAddress,
+    StartRecord,
+    ResetVector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    pub format: ImageFormat,
+    pub regions: Vec<Region>,
+    pub entry: u16,
+    pub entry_source: EntrySource,
+}
+
+impl Region {
+    // last address written
+    pub fn end (&self) -> u16 {
+        self.start.wrapping_add((self.len as u16).wrapping_sub(1))
+    }
+
+    pub fn contains (&self, addr: u16) -> bool {
+        let offset = addr.wrapping_sub(self.start) as usize;
+        offset < self.len
+    }
+}
+
+impl ImageFormat {
+    pub fn from_name (name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" | "bin" => Some(ImageFormat::Raw),
+            "prg" => Some(ImageFormat::Prg),
+            "hex" | "ihex" => Some(ImageFormat::IntelHex),
+            "srec" | "s19" => Some(ImageFormat::SRecord),
+            "nes" | "ines" => Some(ImageFormat::INes),
+            _ => None,
+        }
+    }
+
+    // by magic number or record syntax first, then by extension, raw otherwise
+    pub fn detect (path: &Path, bytes: &[u8]) -> Self {
+        if bytes.starts_with(INES_MAGIC) {
+            return ImageFormat::INes;
+        }
+
+        let text_start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(0);
+        let text = &bytes[text_start..];
+        let is_text = bytes.iter().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+
+        if is_text && text.starts_with(b":") {
+            return ImageFormat::IntelHex;
+        }
+        if is_text && text.len() > 1 && text[0] == b'S' && text[1].is_ascii_digit() {
+            return ImageFormat::SRecord;
+        }
+
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ImageFormat::from_name)
+            .unwrap_or(ImageFormat::Raw)
+    }
+}
+
+fn invalid(message: String) -> Box<dyn std::error::Error> {
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+fn parse_hex_bytes(text: &str, line: usize) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(invalid(format!("Odd number of hex digits on line {}", line)));
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| invalid(format!("Invalid hex digits on line {}", line)))
+        })
+        .collect()
+}
+
+fn decode_intel_hex(text: &str) -> Result<(Vec<Block>, Option<u16>), Box<dyn std::error::Error>> {
+    let mut blocks = Vec::new();
+    let mut entry = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let record = line
+            .strip_prefix(':')
+            .ok_or_else(|| invalid(format!("Intel HEX record without ':' on line {}", line_number)))?;
+        let bytes = parse_hex_bytes(record, line_number)?;
+
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(invalid(format!("Bad Intel HEX record length on line {}", line_number)));
+        }
+        if bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != 0 {
+            return Err(invalid(format!("Intel HEX checksum mismatch on line {}", line_number)));
+        }
+
+        let addr = ((bytes[1] as u16) << 8) | bytes[2] as u16;
+        let data = &bytes[4..bytes.len() - 1];
+
+        match bytes[3] {
+            0x00 => blocks.push(Block { addr, data: data.to_vec() }),
+            0x01 => break,
+            // extended segment / linear address, only the first 64 KiB exists here
+            0x02 | 0x04 => {
+                if data.iter().any(|&b| b != 0) {
+                    return Err(invalid(format!("Intel HEX address above $FFFF on line {}", line_number)));
+                }
+            }
+            // start segment / linear address, the low 16 bits are the entry point
+            0x03 | 0x05 if data.len() == 4 => {
+                entry = Some(((data[2] as u16) << 8) | data[3] as u16);
+            }
+            record_type => {
+                return Err(invalid(format!("Unsupported Intel HEX record type {:02X} on line {}", record_type, line_number)));
+            }
+        }
+    }
+
+    Ok((blocks, entry))
+}
+
+fn decode_srecord(text: &str) -> Result<(Vec<Block>, Option<u16>), Box<dyn std::error::Error>> {
+    let mut blocks = Vec::new();
+    let mut entry = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.len() < 4 || !line.starts_with('S') {
+            return Err(invalid(format!("Invalid S-record on line {}", line_number)));
+        }
+        let record_type = line.as_bytes()[1];
+        let bytes = parse_hex_bytes(&line[2..], line_number)?;
+
+        if bytes.len() < 4 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(invalid(format!("Bad S-record length on line {}", line_number)));
+        }
+        if bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != 0xFF {
+            return Err(invalid(format!("S-record checksum mismatch on line {}", line_number)));
+        }
+
+        let addr = ((bytes[1] as u16) << 8) | bytes[2] as u16;
+        let data = &bytes[3..bytes.len() - 1];
+
+        match record_type {
+            b'0' | b'5' | b'6' => {}
+            b'1' => blocks.push(Block { addr, data: data.to_vec() }),
+            b'9' => entry = Some(addr),
+            _ => {
+                return Err(invalid(format!("Unsupported S-record type S{} on line {}", record_type as char, line_number)));
+            }
+        }
+    }
+
+    Ok((blocks, entry))
+}
+
+// NROM only: 16 KiB of PRG-ROM is mirrored at $8000 and $C000, 32 KiB fills $8000-$FFFF
+fn decode_ines(bytes: &[u8]) -> Result<Vec<Block>, Box<dyn std::error::Error>> {
+    if bytes.len() < INES_HEADER_SIZE || !bytes.starts_with(INES_MAGIC) {
+        return Err(invalid(String::from("Not an iNES image")));
+    }
+
+    let prg_size = bytes[4] as usize * INES_PRG_BANK_SIZE;
+    let mapper = (bytes[6] >> 4) | (bytes[7] & 0xF0);
+    let has_trainer = bytes[6] & 0b0000_0100 != 0;
+
+    if mapper != 0 {
+        return Err(invalid(format!("Unsupported iNES mapper {}", mapper)));
+    }
+    if prg_size == 0 || prg_size > 2 * INES_PRG_BANK_SIZE {
+        return Err(invalid(format!("Unsupported PRG-ROM size {} bytes", prg_size)));
+    }
+
+    let start = INES_HEADER_SIZE + if has_trainer { INES_TRAINER_SIZE } else { 0 };
+    let prg = bytes
+        .get(start..start + prg_size)
+        .ok_or_else(|| invalid(String::from("iNES image is shorter than its PRG-ROM size")))?;
+
+    let mut blocks = vec![Block { addr: 0x8000, data: prg.to_vec() }];
+    if prg_size == INES_PRG_BANK_SIZE {
+        blocks.push(Block { addr: 0xC000, data: prg.to_vec() });
+    }
+    Ok(blocks)
+}
+
+/*
+ *  Decodes an image into blocks plus an explicit entry point, if the format has one.
+ *  load_addr is only used by raw binaries, which have none.
+ */
+pub fn decode_image(
+    format: ImageFormat,
+    bytes: &[u8],
+    load_addr: u16,
+) -> Result<(Vec<Block>, Option<u16>), Box<dyn std::error::Error>> {
+    match format {
+        ImageFormat::Raw => Ok((vec![Block { addr: load_addr, data: bytes.to_vec() }], None)),
+        ImageFormat::Prg => {
+            if bytes.len() < 2 {
+                return Err(invalid(String::from("PRG file is missing its load address")));
+            }
+            let addr = bytes[0] as u16 | ((bytes[1] as u16) << 8);
+            Ok((vec![Block { addr, data: bytes[2..].to_vec() }], Some(addr)))
+        }
+        ImageFormat::IntelHex | ImageFormat::SRecord => {
+            let text = std::str::from_utf8(bytes).map_err(|_| invalid(String::from("Image is not valid text")))?;
+            if format == ImageFormat::IntelHex {
+                decode_intel_hex(text)
+            } else {
+                decode_srecord(text)
+            }
+        }
+        ImageFormat::INes => Ok((decode_ines(bytes)?, None)),
+    }
+}
+
+pub fn read_vector(memory: &Memory, addr: u16) -> u16 {
+    memory.peek(addr) as u16 | ((memory.peek(addr.wrapping_add(1)) as u16) << 8)
+}
+
+// merges touching blocks into the regions that were written
+fn regions(blocks: &[Block]) -> Vec<Region> {
+    let mut spans: Vec<(usize, usize)> = blocks
+        .iter()
+        .filter(|block| !block.data.is_empty())
+        .map(|block| (block.addr as usize, block.addr as usize + block.data.len()))
+        .collect();
+    spans.sort();
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|(start, end)| Region { start: start as u16, len: (end - start).min(0x10000) })
+        .collect()
+}
+
+pub fn load_image(
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    format: ImageFormat,
+    bytes: &[u8],
+    load_addr: u16,
+) -> Result<LoadReport, Box<dyn std::error::Error>> {
+    let (blocks, entry) = decode_image(format, bytes, load_addr)?;
+
+    for block in &blocks {
+        for (offset, &byte) in block.data.iter().enumerate() {
+            memory.write(block.addr.wrapping_add(offset as u16), byte);
+        }
+    }
+
+    let regions = regions(&blocks);
+    let covers_vector = regions.iter().any(|region| region.contains(RESET_VECTOR) && region.contains(RESET_VECTOR + 1));
+
+    let (entry, entry_source) = match (format, entry) {
+        (ImageFormat::INes, _) => (read_vector(memory, RESET_VECTOR), EntrySource::ResetVector),
+        (ImageFormat::Prg, Some(entry)) => (entry, EntrySource::LoadAddress),
+        (_, Some(entry)) => (entry, EntrySource::StartRecord),
+        (_, None) if covers_vector => (read_vector(memory, RESET_VECTOR), EntrySource::ResetVector),
+        (_, None) => (regions.first().map_or(load_addr, |region| region.start), EntrySource::LoadAddress),
+    };
+
+    cpu.pc = entry;
+
+    Ok(LoadReport { format, regions, entry, entry_source })
+}
+
+// detects the format unless one is given
+pub fn load_file(
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    path: &str,
+    format: Option<ImageFormat>,
+    load_addr: u16,
+) -> Result<LoadReport, Box<dyn std::error::Error>> {
+    let bytes = fs::read(path)?;
+    let format = format.unwrap_or_else(|| ImageFormat::detect(Path::new(path), &bytes));
+    load_image(cpu, memory, format, &bytes, load_addr)
+}
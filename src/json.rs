@@ -0,0 +1,288 @@
+use std::fmt;
+
+/*
+ *  Minimal JSON value, parser and writer, enough for the debug adapter protocol.
+ *  Objects keep their keys in insertion order.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse (text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(format!("Unexpected input after JSON value at {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    pub fn object<K: Into<String>, const N: usize> (entries: [(K, Json); N]) -> Json {
+        Json::Object(entries.into_iter().map(|(key, value)| (key.into(), value)).collect())
+    }
+
+    pub fn get (&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str (&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64 (&self) -> Option<i64> {
+        match self {
+            Json::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool (&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array (&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from (value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from (value: i64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<usize> for Json {
+    fn from (value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from (value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from (value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from (items: Vec<Json>) -> Self {
+        Json::Array(items)
+    }
+}
+
+fn write_string (f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => write!(f, "{}", *number as i64),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(text) => write_string(f, text),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace (&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect (&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.chars.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at {}", c, self.pos))
+        }
+    }
+
+    fn literal (&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(format!("Invalid literal at {}", self.pos))
+        }
+    }
+
+    fn value (&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.pos) == Some(&']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.get(self.pos) {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(format!("Expected ',' or ']' at {}", self.pos)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.pos) == Some(&'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.chars.get(self.pos) {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return Err(format!("Expected ',' or '}}' at {}", self.pos)),
+                    }
+                }
+            }
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                text.parse().map(Json::Number).map_err(|_| format!("Invalid number: {}", text))
+            }
+            _ => Err(format!("Unexpected input at {}", self.pos)),
+        }
+    }
+
+    fn string (&mut self) -> Result<String, String> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            return Err(format!("Expected a string at {}", self.pos));
+        }
+        self.pos += 1;
+
+        let mut text = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escape = *self.chars.get(self.pos).ok_or("Unterminated string")?;
+                    self.pos += 1;
+                    match escape {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let digits: String = self.chars.get(self.pos..self.pos + 4).ok_or("Bad escape")?.iter().collect();
+                            self.pos += 4;
+                            let code = u32::from_str_radix(&digits, 16).map_err(|_| "Bad escape")?;
+                            text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        other => text.push(other),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+}
@@ -1,10 +1,31 @@
 use std::borrow::Cow;
 
 pub mod cpu;
+pub mod dap;
 pub mod debugger;
+pub mod devices;
+pub mod expression;
+pub mod formats;
+pub mod framebuffer;
+pub mod gdb;
+pub mod image_loader;
+pub mod json;
+pub mod linker;
 pub mod loader;
+pub mod machines;
 pub mod memory;
+pub mod monitor;
+pub mod object;
 pub mod op_code;
+pub mod profiler;
+pub mod runner;
+pub mod savestate;
+pub mod scheduler;
+pub mod serial;
+pub mod source_map;
+pub mod symbols;
+pub mod terminal;
+pub mod toml;
 
 pub use cpu::CPU;
 pub use memory::Memory;
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::object::{ObjectFile, RelocationKind, RelocationTarget};
+use crate::symbols::SymbolTable;
+
+/*
+ *  Places the segments of one or more object files into memory and resolves relocations.
+ *  Placement is driven by a memory config in a subset of the ld65 syntax:
+ *
+ *      MEMORY {
+ *          ZP:  start = $0000, size = $0100;
+ *          RAM: start = $0200, size = $3E00;
+ *          ROM: start = $8000, size = $8000, fill = yes, fillval = $FF;
+ *      }
+ *      SEGMENTS {
+ *          ZEROPAGE: load = ZP,  type = zp;
+ *          CODE:     load = ROM, type = ro;
+ *          DATA:     load = RAM, type = rw;
+ *          BSS:      load = RAM, type = bss;
+ *          VECTORS:  load = ROM, type = ro, start = $FFFA;
+ *      }
+ *
+ *  Segments are laid out in the order they are listed, each object's piece of a segment after
+ *  the previous object's.
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryArea {
+    pub name: String,
+    pub start: u16,
+    pub size: u32,
+    pub fill: bool,
+    pub fill_value: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentType {
+    ReadOnly,
+    ReadWrite,
+    // space is reserved but nothing is written to the output
+    Bss,
+    ZeroPage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentRule {
+    pub name: String,
+    pub load: String,
+    pub kind: SegmentType,
+    pub start: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryConfig {
+    pub areas: Vec<MemoryArea>,
+    pub segments: Vec<SegmentRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedArea {
+    pub name: String,
+    pub start: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkedImage {
+    pub areas: Vec<LinkedArea>,
+    pub symbols: SymbolTable,
+}
+
+fn config_error(message: String) -> Box<dyn std::error::Error> {
+    io::Error::new(io::ErrorKind::InvalidInput, message).into()
+}
+
+fn link_error(message: String) -> Box<dyn std::error::Error> {
+    io::Error::new(io::ErrorKind::InvalidData, message).into()
+}
+
+fn parse_value(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('$') {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse::<u32>().ok()
+    }
+}
+
+type ConfigEntry = (String, Vec<(String, String)>);
+
+// "NAME: key = value, key = value;" -> (NAME, [(key, value)])
+fn parse_entries(body: &str) -> Result<Vec<ConfigEntry>, Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+
+    for entry in body.split(';') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let (name, attributes) = entry
+            .split_once(':')
+            .ok_or_else(|| config_error(format!("Expected 'name:' in memory config entry: {}", entry)))?;
+
+        let mut pairs = Vec::new();
+        for attribute in attributes.split(',') {
+            let (key, value) = attribute
+                .split_once('=')
+                .ok_or_else(|| config_error(format!("Expected 'key = value' in memory config: {}", attribute.trim())))?;
+            pairs.push((key.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        entries.push((name.trim().to_string(), pairs));
+    }
+
+    Ok(entries)
+}
+
+impl MemoryConfig {
+    pub fn parse (text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let text: String = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let mut config = MemoryConfig { areas: Vec::new(), segments: Vec::new() };
+        let mut rest = text.as_str();
+
+        while let Some(open) = rest.find('{') {
+            let block = rest[..open].trim().to_ascii_uppercase();
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| config_error(format!("Unterminated {} block in memory config", block)))?
+                + open;
+            let entries = parse_entries(&rest[open + 1..close])?;
+
+            match block.as_str() {
+                "MEMORY" => {
+                    for (name, attributes) in entries {
+                        let mut area = MemoryArea { name: name.clone(), start: 0, size: 0, fill: false, fill_value: 0 };
+                        let mut has_start = false;
+                        let mut has_size = false;
+
+                        for (key, value) in attributes {
+                            let number = parse_value(&value);
+                            match key.as_str() {
+                                "start" => {
+                                    area.start = number.filter(|&n| n <= 0xFFFF).ok_or_else(|| {
+                                        config_error(format!("Invalid start for memory area {}: {}", name, value))
+                                    })? as u16;
+                                    has_start = true;
+                                }
+                                "size" => {
+                                    area.size = number.filter(|&n| n <= 0x10000).ok_or_else(|| {
+                                        config_error(format!("Invalid size for memory area {}: {}", name, value))
+                                    })?;
+                                    has_size = true;
+                                }
+                                "fill" => area.fill = value.eq_ignore_ascii_case("yes"),
+                                "fillval" => {
+                                    area.fill_value = number.filter(|&n| n <= 0xFF).ok_or_else(|| {
+                                        config_error(format!("Invalid fillval for memory area {}: {}", name, value))
+                                    })? as u8;
+                                }
+                                // ld65 attributes we don't need (type, file, ...)
+                                _ => {}
+                            }
+                        }
+
+                        if !has_start || !has_size {
+                            return Err(config_error(format!("Memory area {} needs both start and size", name)));
+                        }
+                        if area.start as u32 + area.size > 0x10000 {
+                            return Err(config_error(format!("Memory area {} runs past $FFFF", name)));
+                        }
+                        config.areas.push(area);
+                    }
+                }
+                "SEGMENTS" => {
+                    for (name, attributes) in entries {
+                        let mut rule = SegmentRule { name: name.clone(), load: String::new(), kind: SegmentType::ReadOnly, start: None };
+
+                        for (key, value) in attributes {
+                            match key.as_str() {
+                                "load" => rule.load = value,
+                                "type" => {
+                                    rule.kind = match value.to_ascii_lowercase().as_str() {
+                                        "ro" => SegmentType::ReadOnly,
+                                        "rw" => SegmentType::ReadWrite,
+                                        "bss" => SegmentType::Bss,
+                                        "zp" => SegmentType::ZeroPage,
+                                        _ => return Err(config_error(format!("Unknown segment type for {}: {}", name, value))),
+                                    }
+                                }
+                                "start" => {
+                                    rule.start = Some(parse_value(&value).filter(|&n| n <= 0xFFFF).ok_or_else(|| {
+                                        config_error(format!("Invalid start for segment {}: {}", name, value))
+                                    })? as u16);
+                                }
+                                _ => {}
+                            }
+                        }
+
+                        if !config.areas.iter().any(|area| area.name == rule.load) {
+                            return Err(config_error(format!("Segment {} is loaded into unknown memory area '{}'", name, rule.load)));
+                        }
+                        config.segments.push(rule);
+                    }
+                }
+                _ => return Err(config_error(format!("Unknown block in memory config: {}", block))),
+            }
+
+            rest = &rest[close + 1..];
+        }
+
+        Ok(config)
+    }
+}
+
+// where a segment of an object was placed; segments the config doesn't place have no address
+fn placed(bases: &HashMap<(usize, &str), u16>, index: usize, segment: &str) -> Result<u16, Box<dyn std::error::Error>> {
+    bases
+        .get(&(index, segment))
+        .copied()
+        .ok_or_else(|| link_error(format!("Segment {} is missing from the memory config", segment)))
+}
+
+pub fn link(objects: &[ObjectFile], config: &MemoryConfig) -> Result<LinkedImage, Box<dyn std::error::Error>> {
+    for object in objects {
+        for segment in &object.segments {
+            if !segment.data.is_empty() && !config.segments.iter().any(|rule| rule.name == segment.name) {
+                return Err(link_error(format!("Segment {} is missing from the memory config", segment.name)));
+            }
+        }
+    }
+
+    // place every (object, segment) piece
+    let mut cursors: HashMap<&str, u32> = config.areas.iter().map(|area| (area.name.as_str(), area.start as u32)).collect();
+    let mut used: HashMap<&str, u32> = HashMap::new();
+    let mut bases: HashMap<(usize, &str), u16> = HashMap::new();
+
+    for rule in &config.segments {
+        let area = config.areas.iter().find(|area| area.name == rule.load).unwrap();
+        let cursor = cursors.get_mut(area.name.as_str()).unwrap();
+
+        if let Some(start) = rule.start {
+            if (start as u32) < *cursor {
+                return Err(link_error(format!("Segment {} at ${:04X} overlaps earlier segments in {}", rule.name, start, area.name)));
+            }
+            *cursor = start as u32;
+        }
+
+        for (index, object) in objects.iter().enumerate() {
+            let Some(segment) = object.segment(&rule.name) else { continue };
+            let end = *cursor + segment.data.len() as u32;
+
+            if end > area.start as u32 + area.size {
+                return Err(link_error(format!("Segment {} does not fit in memory area {}", rule.name, area.name)));
+            }
+            if rule.kind == SegmentType::ZeroPage && end > 0x100 {
+                return Err(link_error(format!("Zero page segment {} extends past $00FF", rule.name)));
+            }
+
+            bases.insert((index, segment.name.as_str()), *cursor as u16);
+            *cursor = end;
+        }
+
+        if rule.kind != SegmentType::Bss {
+            let extent = used.entry(area.name.as_str()).or_insert(area.start as u32);
+            *extent = (*extent).max(*cursor);
+        }
+    }
+
+    // exported symbols
+    let mut symbols = SymbolTable::new();
+    for (index, object) in objects.iter().enumerate() {
+        for export in &object.exports {
+            if symbols.contains(&export.name) {
+                return Err(link_error(format!("Duplicate exported symbol: {}", export.name)));
+            }
+            let base = placed(&bases, index, &export.segment)?;
+            symbols.insert(&export.name, base.wrapping_add(export.offset));
+        }
+    }
+
+    // copy segment data into the memory areas and patch relocations
+    let mut buffers: HashMap<&str, Vec<u8>> = config
+        .areas
+        .iter()
+        .map(|area| (area.name.as_str(), vec![area.fill_value; area.size as usize]))
+        .collect();
+
+    for rule in &config.segments {
+        if rule.kind == SegmentType::Bss {
+            continue;
+        }
+        let area = config.areas.iter().find(|area| area.name == rule.load).unwrap();
+        let buffer = buffers.get_mut(area.name.as_str()).unwrap();
+
+        for (index, object) in objects.iter().enumerate() {
+            let Some(segment) = object.segment(&rule.name) else { continue };
+            let offset = (bases[&(index, segment.name.as_str())] - area.start) as usize;
+            buffer[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+        }
+    }
+
+    for (index, object) in objects.iter().enumerate() {
+        for relocation in &object.relocations {
+            let target = match &relocation.target {
+                RelocationTarget::Segment(name) => placed(&bases, index, name)?,
+                RelocationTarget::Import(name) => symbols
+                    .get(name)
+                    .ok_or_else(|| link_error(format!("Unresolved import: {}", name)))?,
+            };
+            let value = target.wrapping_add(relocation.addend);
+
+            let base = placed(&bases, index, &relocation.segment)?;
+            let field = base.wrapping_add(relocation.offset);
+
+            let bytes = match relocation.kind {
+                RelocationKind::Word => value.to_le_bytes().to_vec(),
+                RelocationKind::LowByte => vec![(value & 0xFF) as u8],
+                RelocationKind::HighByte => vec![(value >> 8) as u8],
+                RelocationKind::Relative => {
+                    let offset = value as i32 - (field as i32 + 1);
+                    if !(-128..=127).contains(&offset) {
+                        return Err(link_error(format!("Branch at ${:04X} is out of range of its target ${:04X}", field, value)));
+                    }
+                    vec![offset as i8 as u8]
+                }
+            };
+
+            let rule = config.segments.iter().find(|rule| rule.name == relocation.segment).unwrap();
+            if rule.kind == SegmentType::Bss {
+                continue;
+            }
+            let area = config.areas.iter().find(|area| area.name == rule.load).unwrap();
+            let buffer = buffers.get_mut(area.name.as_str()).unwrap();
+            let offset = (field as usize).wrapping_sub(area.start as usize);
+            match buffer.get_mut(offset..offset.saturating_add(bytes.len())) {
+                Some(slot) => slot.copy_from_slice(&bytes),
+                None => return Err(link_error(format!("Relocation at ${:04X} falls outside memory area {}", field, area.name))),
+            }
+        }
+    }
+
+    let mut areas = Vec::new();
+    for area in &config.areas {
+        let mut data = buffers.remove(area.name.as_str()).unwrap();
+        if !area.fill {
+            let extent = used.get(area.name.as_str()).copied().unwrap_or(area.start as u32);
+            data.truncate((extent - area.start as u32) as usize);
+        }
+        if !data.is_empty() {
+            areas.push(LinkedArea { name: area.name.clone(), start: area.start, data });
+        }
+    }
+
+    Ok(LinkedImage { areas, symbols })
+}
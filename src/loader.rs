@@ -3,6 +3,14 @@ use std::fs;
 use std::io;
 use std::path::Path;
 
+use crate::object::{Export, ObjectFile, ObjectSegment, Relocation, RelocationKind, RelocationTarget};
+use crate::source_map::SourceMap;
+use crate::symbols::SymbolTable;
+use crate::Memory;
+
+// where code is placed when the source has no .org directive (matches lib::load_program)
+pub const DEFAULT_ORIGIN: u16 = 0x0600;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum AddressingMode {
     Implied,
@@ -12,13 +20,25 @@ enum AddressingMode {
     Relative,
     Accumulator,
     Indirect,
+    ZeroPageX,
+    ZeroPageY,
+    AbsoluteX,
+    AbsoluteY,
+    IndirectX,
+    IndirectY,
 }
 
 fn operand_size(mode: AddressingMode) -> usize {
     match mode {
         AddressingMode::Implied | AddressingMode::Accumulator => 0,
-        AddressingMode::Immediate | AddressingMode::ZeroPage | AddressingMode::Relative => 1,
-        AddressingMode::Absolute | AddressingMode::Indirect => 2,
+        AddressingMode::Immediate
+        | AddressingMode::ZeroPage
+        | AddressingMode::ZeroPageX
+        | AddressingMode::ZeroPageY
+        | AddressingMode::IndirectX
+        | AddressingMode::IndirectY
+        | AddressingMode::Relative => 1,
+        AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::Indirect => 2,
     }
 }
 
@@ -29,17 +49,47 @@ fn is_branch_instruction(mnemonic: &str) -> bool {
     )
 }
 
+// operand value, resolved to bytes once every label has an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Bytes(Vec<u8>),
+    Label(String),
+    LowByte(String),
+    HighByte(String),
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 fn parse_operand(
     mnemonic: &str,
     operand: Option<&str>,
-) -> Result<(AddressingMode, Vec<u8>), Box<dyn std::error::Error>> {
+) -> Result<(AddressingMode, Value), Box<dyn std::error::Error>> {
     match operand {
-        None => Ok((AddressingMode::Implied, Vec::new())),
+        None => Ok((AddressingMode::Implied, Value::Bytes(Vec::new()))),
         Some(raw_operand) => {
             let operand = raw_operand.trim().trim_end_matches(',');
 
             if operand.is_empty() {
-                return Ok((AddressingMode::Implied, Vec::new()));
+                return Ok((AddressingMode::Implied, Value::Bytes(Vec::new())));
+            }
+
+            if let Some(label) = operand.strip_prefix("#<") {
+                if is_identifier(label) {
+                    return Ok((AddressingMode::Immediate, Value::LowByte(label.to_string())));
+                }
+            }
+
+            if let Some(label) = operand.strip_prefix("#>") {
+                if is_identifier(label) {
+                    return Ok((AddressingMode::Immediate, Value::HighByte(label.to_string())));
+                }
             }
 
             if operand.starts_with('#') {
@@ -53,11 +103,56 @@ fn parse_operand(
                         format!("Invalid immediate operand: {}", operand),
                     )
                 })?;
-                return Ok((AddressingMode::Immediate, vec![value]));
+                return Ok((AddressingMode::Immediate, Value::Bytes(vec![value])));
             }
 
             if operand.eq_ignore_ascii_case("A") {
-                return Ok((AddressingMode::Accumulator, Vec::new()));
+                return Ok((AddressingMode::Accumulator, Value::Bytes(Vec::new())));
+            }
+
+            // indexed operands, spaces around the comma are allowed
+            let compact: String = operand.chars().filter(|c| !c.is_whitespace()).collect();
+            let upper = compact.to_ascii_uppercase();
+
+            if upper.starts_with('(') && upper.ends_with(",X)") {
+                let value = parse_zero_page(&compact[1..compact.len() - 3], operand)?;
+                return Ok((AddressingMode::IndirectX, Value::Bytes(vec![value])));
+            }
+
+            if upper.starts_with('(') && upper.ends_with("),Y") {
+                let value = parse_zero_page(&compact[1..compact.len() - 3], operand)?;
+                return Ok((AddressingMode::IndirectY, Value::Bytes(vec![value])));
+            }
+
+            let indexed = if upper.ends_with(",X") {
+                Some((AddressingMode::ZeroPageX, AddressingMode::AbsoluteX))
+            } else if upper.ends_with(",Y") {
+                Some((AddressingMode::ZeroPageY, AddressingMode::AbsoluteY))
+            } else {
+                None
+            };
+            if let Some((zero_page_mode, absolute_mode)) = indexed {
+                let base = &compact[..compact.len() - 2];
+                if is_identifier(base) {
+                    return Ok((absolute_mode, Value::Label(base.to_string())));
+                }
+                let value_str = base.strip_prefix('$').ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid operand: {}", operand))
+                })?;
+                let value = u16::from_str_radix(value_str, 16).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid operand: {}", operand))
+                })?;
+                if value_str.len() <= 2 {
+                    return Ok((zero_page_mode, Value::Bytes(vec![value as u8])));
+                }
+                return Ok((absolute_mode, Value::Bytes(vec![(value & 0xFF) as u8, (value >> 8) as u8])));
+            }
+
+            if operand.starts_with('(') && operand.ends_with(')') {
+                let inner = &operand[1..operand.len() - 1];
+                if is_identifier(inner) {
+                    return Ok((AddressingMode::Indirect, Value::Label(inner.to_string())));
+                }
             }
 
             if operand.starts_with("($") && operand.ends_with(')') {
@@ -70,7 +165,7 @@ fn parse_operand(
                 })?;
                 return Ok((
                     AddressingMode::Indirect,
-                    vec![(value & 0xFF) as u8, (value >> 8) as u8],
+                    Value::Bytes(vec![(value & 0xFF) as u8, (value >> 8) as u8]),
                 ));
             }
 
@@ -84,19 +179,27 @@ fn parse_operand(
                 })?;
 
                 if is_branch_instruction(mnemonic) {
-                    return Ok((AddressingMode::Relative, vec![value as u8]));
+                    return Ok((AddressingMode::Relative, Value::Bytes(vec![value as u8])));
                 }
 
                 if value_str.len() <= 2 {
-                    return Ok((AddressingMode::ZeroPage, vec![value as u8]));
+                    return Ok((AddressingMode::ZeroPage, Value::Bytes(vec![value as u8])));
                 }
 
                 return Ok((
                     AddressingMode::Absolute,
-                    vec![(value & 0xFF) as u8, (value >> 8) as u8],
+                    Value::Bytes(vec![(value & 0xFF) as u8, (value >> 8) as u8]),
                 ));
             }
 
+            // labels are always 16 bit so that pass one knows the instruction size up front
+            if is_identifier(operand) {
+                if is_branch_instruction(mnemonic) {
+                    return Ok((AddressingMode::Relative, Value::Label(operand.to_string())));
+                }
+                return Ok((AddressingMode::Absolute, Value::Label(operand.to_string())));
+            }
+
             Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!("Unsupported operand format: {}", operand),
@@ -106,6 +209,48 @@ fn parse_operand(
     }
 }
 
+// the pointer of an indirect indexed operand lives in zero page
+fn parse_zero_page(text: &str, operand: &str) -> Result<u8, io::Error> {
+    text.strip_prefix('$')
+        .filter(|hex| hex.len() <= 2)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid indirect operand, expected a zero page address: {}", operand),
+            )
+        })
+}
+
+// zero page operands fall back to absolute when the instruction has no zero page form, e.g. LDA $10,Y
+fn widen_zero_page(
+    opcode_table: &HashMap<(String, AddressingMode), u8>,
+    mnemonic: &str,
+    mode: AddressingMode,
+    value: Value,
+) -> (AddressingMode, Value) {
+    let wider = match mode {
+        AddressingMode::ZeroPage => AddressingMode::Absolute,
+        AddressingMode::ZeroPageX => AddressingMode::AbsoluteX,
+        AddressingMode::ZeroPageY => AddressingMode::AbsoluteY,
+        _ => return (mode, value),
+    };
+    let has = |mode| opcode_table.contains_key(&(mnemonic.to_string(), mode));
+    match value {
+        Value::Bytes(bytes) if !has(mode) && has(wider) => (wider, Value::Bytes(vec![bytes[0], 0])),
+        value => (mode, value),
+    }
+}
+
+fn value_size(value: &Value, mode: AddressingMode) -> usize {
+    match value {
+        Value::Bytes(bytes) => bytes.len(),
+        Value::Label(_) if mode == AddressingMode::Relative => 1,
+        Value::Label(_) => 2,
+        Value::LowByte(_) | Value::HighByte(_) => 1,
+    }
+}
+
 pub fn read_file(file_path: String) -> String {
     let path = Path::new(&file_path);
 
@@ -124,122 +269,541 @@ pub fn read_file(file_path: String) -> String {
     contents
 }
 
-pub fn assemble(file_path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let opcode_entries = [
-        // Load and Store Instructions
-        ("LDA", AddressingMode::Immediate, 0xA9),
-        ("LDA", AddressingMode::ZeroPage, 0xA5),
-        ("LDA", AddressingMode::Absolute, 0xAD),
-        ("STA", AddressingMode::ZeroPage, 0x85),
-        ("STA", AddressingMode::Absolute, 0x8D),
-        ("LDX", AddressingMode::Immediate, 0xA2),
-        ("LDX", AddressingMode::ZeroPage, 0xA6),
-        ("LDX", AddressingMode::Absolute, 0xAE),
-        ("STX", AddressingMode::ZeroPage, 0x86),
-        ("STX", AddressingMode::Absolute, 0x8E),
-        ("LDY", AddressingMode::Immediate, 0xA0),
-        ("LDY", AddressingMode::ZeroPage, 0xA4),
-        ("LDY", AddressingMode::Absolute, 0xAC),
-        ("STY", AddressingMode::ZeroPage, 0x84),
-        ("STY", AddressingMode::Absolute, 0x8C),
-        // Transfer Instructions
-        ("TAX", AddressingMode::Implied, 0xAA),
-        ("TXA", AddressingMode::Implied, 0x8A),
-        ("TAY", AddressingMode::Implied, 0xA8),
-        ("TYA", AddressingMode::Implied, 0x98),
-        // Stack Instructions
-        ("TSX", AddressingMode::Implied, 0xBA),
-        ("TXS", AddressingMode::Implied, 0x9A),
-        ("PHA", AddressingMode::Implied, 0x48),
-        ("PLA", AddressingMode::Implied, 0x68),
-        ("PHP", AddressingMode::Implied, 0x08),
-        ("PLP", AddressingMode::Implied, 0x28),
-        // Arithmetic and Logic Instructions
-        ("ADC", AddressingMode::Immediate, 0x69),
-        ("ADC", AddressingMode::ZeroPage, 0x65),
-        ("ADC", AddressingMode::Absolute, 0x6D),
-        ("SBC", AddressingMode::Immediate, 0xE9),
-        ("SBC", AddressingMode::ZeroPage, 0xE5),
-        ("SBC", AddressingMode::Absolute, 0xED),
-        ("CMP", AddressingMode::Immediate, 0xC9),
-        ("CMP", AddressingMode::ZeroPage, 0xC5),
-        ("CMP", AddressingMode::Absolute, 0xCD),
-        ("CPX", AddressingMode::Immediate, 0xE0),
-        ("CPX", AddressingMode::ZeroPage, 0xE4),
-        ("CPX", AddressingMode::Absolute, 0xEC),
-        ("CPY", AddressingMode::Immediate, 0xC0),
-        ("CPY", AddressingMode::ZeroPage, 0xC4),
-        ("CPY", AddressingMode::Absolute, 0xCC),
-        // Increment and Decrement Instructions
-        ("INC", AddressingMode::ZeroPage, 0xE6),
-        ("INC", AddressingMode::Absolute, 0xEE),
-        ("INX", AddressingMode::Implied, 0xE8),
-        ("INY", AddressingMode::Implied, 0xC8),
-        ("DEC", AddressingMode::ZeroPage, 0xC6),
-        ("DEC", AddressingMode::Absolute, 0xCE),
-        ("DEX", AddressingMode::Implied, 0xCA),
-        ("DEY", AddressingMode::Implied, 0x88),
-        // Shift and Rotate Instructions
-        ("ASL", AddressingMode::Accumulator, 0x0A),
-        ("ASL", AddressingMode::ZeroPage, 0x06),
-        ("ASL", AddressingMode::Absolute, 0x0E),
-        ("LSR", AddressingMode::Accumulator, 0x4A),
-        ("LSR", AddressingMode::ZeroPage, 0x46),
-        ("LSR", AddressingMode::Absolute, 0x4E),
-        ("ROL", AddressingMode::Accumulator, 0x2A),
-        ("ROL", AddressingMode::ZeroPage, 0x26),
-        ("ROL", AddressingMode::Absolute, 0x2E),
-        ("ROR", AddressingMode::Accumulator, 0x6A),
-        ("ROR", AddressingMode::ZeroPage, 0x66),
-        ("ROR", AddressingMode::Absolute, 0x6E),
-        // Jump and Branch Instructions
-        ("JMP", AddressingMode::Absolute, 0x4C),
-        ("JMP", AddressingMode::Indirect, 0x6C),
-        ("JSR", AddressingMode::Absolute, 0x20),
-        ("RTS", AddressingMode::Implied, 0x60),
-        ("RTI", AddressingMode::Implied, 0x40),
-        ("BCC", AddressingMode::Relative, 0x90),
-        ("BCS", AddressingMode::Relative, 0xB0),
-        ("BEQ", AddressingMode::Relative, 0xF0),
-        ("BMI", AddressingMode::Relative, 0x30),
-        ("BNE", AddressingMode::Relative, 0xD0),
-        ("BPL", AddressingMode::Relative, 0x10),
-        ("BVC", AddressingMode::Relative, 0x50),
-        ("BVS", AddressingMode::Relative, 0x70),
-        // Flag Instructions
-        ("CLC", AddressingMode::Implied, 0x18),
-        ("SEC", AddressingMode::Implied, 0x38),
-        ("CLI", AddressingMode::Implied, 0x58),
-        ("SEI", AddressingMode::Implied, 0x78),
-        ("CLV", AddressingMode::Implied, 0xB8),
-        ("CLD", AddressingMode::Implied, 0xD8),
-        ("SED", AddressingMode::Implied, 0xF8),
-        // No Operation
-        ("NOP", AddressingMode::Implied, 0xEA),
-    ];
+// every documented opcode
+const OPCODE_ENTRIES: [(&str, AddressingMode, u8); 151] = [
+    // Load and Store Instructions
+    ("LDA", AddressingMode::Immediate, 0xA9),
+    ("LDA", AddressingMode::ZeroPage, 0xA5),
+    ("LDA", AddressingMode::ZeroPageX, 0xB5),
+    ("LDA", AddressingMode::Absolute, 0xAD),
+    ("LDA", AddressingMode::AbsoluteX, 0xBD),
+    ("LDA", AddressingMode::AbsoluteY, 0xB9),
+    ("LDA", AddressingMode::IndirectX, 0xA1),
+    ("LDA", AddressingMode::IndirectY, 0xB1),
+    ("STA", AddressingMode::ZeroPage, 0x85),
+    ("STA", AddressingMode::ZeroPageX, 0x95),
+    ("STA", AddressingMode::Absolute, 0x8D),
+    ("STA", AddressingMode::AbsoluteX, 0x9D),
+    ("STA", AddressingMode::AbsoluteY, 0x99),
+    ("STA", AddressingMode::IndirectX, 0x81),
+    ("STA", AddressingMode::IndirectY, 0x91),
+    ("LDX", AddressingMode::Immediate, 0xA2),
+    ("LDX", AddressingMode::ZeroPage, 0xA6),
+    ("LDX", AddressingMode::ZeroPageY, 0xB6),
+    ("LDX", AddressingMode::Absolute, 0xAE),
+    ("LDX", AddressingMode::AbsoluteY, 0xBE),
+    ("STX", AddressingMode::ZeroPage, 0x86),
+    ("STX", AddressingMode::ZeroPageY, 0x96),
+    ("STX", AddressingMode::Absolute, 0x8E),
+    ("LDY", AddressingMode::Immediate, 0xA0),
+    ("LDY", AddressingMode::ZeroPage, 0xA4),
+    ("LDY", AddressingMode::ZeroPageX, 0xB4),
+    ("LDY", AddressingMode::Absolute, 0xAC),
+    ("LDY", AddressingMode::AbsoluteX, 0xBC),
+    ("STY", AddressingMode::ZeroPage, 0x84),
+    ("STY", AddressingMode::ZeroPageX, 0x94),
+    ("STY", AddressingMode::Absolute, 0x8C),
+    // Transfer Instructions
+    ("TAX", AddressingMode::Implied, 0xAA),
+    ("TXA", AddressingMode::Implied, 0x8A),
+    ("TAY", AddressingMode::Implied, 0xA8),
+    ("TYA", AddressingMode::Implied, 0x98),
+    // Stack Instructions
+    ("TSX", AddressingMode::Implied, 0xBA),
+    ("TXS", AddressingMode::Implied, 0x9A),
+    ("PHA", AddressingMode::Implied, 0x48),
+    ("PLA", AddressingMode::Implied, 0x68),
+    ("PHP", AddressingMode::Implied, 0x08),
+    ("PLP", AddressingMode::Implied, 0x28),
+    // Arithmetic and Logic Instructions
+    ("ADC", AddressingMode::Immediate, 0x69),
+    ("ADC", AddressingMode::ZeroPage, 0x65),
+    ("ADC", AddressingMode::ZeroPageX, 0x75),
+    ("ADC", AddressingMode::Absolute, 0x6D),
+    ("ADC", AddressingMode::AbsoluteX, 0x7D),
+    ("ADC", AddressingMode::AbsoluteY, 0x79),
+    ("ADC", AddressingMode::IndirectX, 0x61),
+    ("ADC", AddressingMode::IndirectY, 0x71),
+    ("SBC", AddressingMode::Immediate, 0xE9),
+    ("SBC", AddressingMode::ZeroPage, 0xE5),
+    ("SBC", AddressingMode::ZeroPageX, 0xF5),
+    ("SBC", AddressingMode::Absolute, 0xED),
+    ("SBC", AddressingMode::AbsoluteX, 0xFD),
+    ("SBC", AddressingMode::AbsoluteY, 0xF9),
+    ("SBC", AddressingMode::IndirectX, 0xE1),
+    ("SBC", AddressingMode::IndirectY, 0xF1),
+    ("AND", AddressingMode::Immediate, 0x29),
+    ("AND", AddressingMode::ZeroPage, 0x25),
+    ("AND", AddressingMode::ZeroPageX, 0x35),
+    ("AND", AddressingMode::Absolute, 0x2D),
+    ("AND", AddressingMode::AbsoluteX, 0x3D),
+    ("AND", AddressingMode::AbsoluteY, 0x39),
+    ("AND", AddressingMode::IndirectX, 0x21),
+    ("AND", AddressingMode::IndirectY, 0x31),
+    ("ORA", AddressingMode::Immediate, 0x09),
+    ("ORA", AddressingMode::ZeroPage, 0x05),
+    ("ORA", AddressingMode::ZeroPageX, 0x15),
+    ("ORA", AddressingMode::Absolute, 0x0D),
+    ("ORA", AddressingMode::AbsoluteX, 0x1D),
+    ("ORA", AddressingMode::AbsoluteY, 0x19),
+    ("ORA", AddressingMode::IndirectX, 0x01),
+    ("ORA", AddressingMode::IndirectY, 0x11),
+    ("EOR", AddressingMode::Immediate, 0x49),
+    ("EOR", AddressingMode::ZeroPage, 0x45),
+    ("EOR", AddressingMode::ZeroPageX, 0x55),
+    ("EOR", AddressingMode::Absolute, 0x4D),
+    ("EOR", AddressingMode::AbsoluteX, 0x5D),
+    ("EOR", AddressingMode::AbsoluteY, 0x59),
+    ("EOR", AddressingMode::IndirectX, 0x41),
+    ("EOR", AddressingMode::IndirectY, 0x51),
+    ("BIT", AddressingMode::ZeroPage, 0x24),
+    ("BIT", AddressingMode::Absolute, 0x2C),
+    ("CMP", AddressingMode::Immediate, 0xC9),
+    ("CMP", AddressingMode::ZeroPage, 0xC5),
+    ("CMP", AddressingMode::ZeroPageX, 0xD5),
+    ("CMP", AddressingMode::Absolute, 0xCD),
+    ("CMP", AddressingMode::AbsoluteX, 0xDD),
+    ("CMP", AddressingMode::AbsoluteY, 0xD9),
+    ("CMP", AddressingMode::IndirectX, 0xC1),
+    ("CMP", AddressingMode::IndirectY, 0xD1),
+    ("CPX", AddressingMode::Immediate, 0xE0),
+    ("CPX", AddressingMode::ZeroPage, 0xE4),
+    ("CPX", AddressingMode::Absolute, 0xEC),
+    ("CPY", AddressingMode::Immediate, 0xC0),
+    ("CPY", AddressingMode::ZeroPage, 0xC4),
+    ("CPY", AddressingMode::Absolute, 0xCC),
+    // Increment and Decrement Instructions
+    ("INC", AddressingMode::ZeroPage, 0xE6),
+    ("INC", AddressingMode::ZeroPageX, 0xF6),
+    ("INC", AddressingMode::Absolute, 0xEE),
+    ("INC", AddressingMode::AbsoluteX, 0xFE),
+    ("INX", AddressingMode::Implied, 0xE8),
+    ("INY", AddressingMode::Implied, 0xC8),
+    ("DEC", AddressingMode::ZeroPage, 0xC6),
+    ("DEC", AddressingMode::ZeroPageX, 0xD6),
+    ("DEC", AddressingMode::Absolute, 0xCE),
+    ("DEC", AddressingMode::AbsoluteX, 0xDE),
+    ("DEX", AddressingMode::Implied, 0xCA),
+    ("DEY", AddressingMode::Implied, 0x88),
+    // Shift and Rotate Instructions
+    ("ASL", AddressingMode::Accumulator, 0x0A),
+    ("ASL", AddressingMode::ZeroPage, 0x06),
+    ("ASL", AddressingMode::ZeroPageX, 0x16),
+    ("ASL", AddressingMode::Absolute, 0x0E),
+    ("ASL", AddressingMode::AbsoluteX, 0x1E),
+    ("LSR", AddressingMode::Accumulator, 0x4A),
+    ("LSR", AddressingMode::ZeroPage, 0x46),
+    ("LSR", AddressingMode::ZeroPageX, 0x56),
+    ("LSR", AddressingMode::Absolute, 0x4E),
+    ("LSR", AddressingMode::AbsoluteX, 0x5E),
+    ("ROL", AddressingMode::Accumulator, 0x2A),
+    ("ROL", AddressingMode::ZeroPage, 0x26),
+    ("ROL", AddressingMode::ZeroPageX, 0x36),
+    ("ROL", AddressingMode::Absolute, 0x2E),
+    ("ROL", AddressingMode::AbsoluteX, 0x3E),
+    ("ROR", AddressingMode::Accumulator, 0x6A),
+    ("ROR", AddressingMode::ZeroPage, 0x66),
+    ("ROR", AddressingMode::ZeroPageX, 0x76),
+    ("ROR", AddressingMode::Absolute, 0x6E),
+    ("ROR", AddressingMode::AbsoluteX, 0x7E),
+    // Jump and Branch Instructions
+    ("JMP", AddressingMode::Absolute, 0x4C),
+    ("JMP", AddressingMode::Indirect, 0x6C),
+    ("JSR", AddressingMode::Absolute, 0x20),
+    ("RTS", AddressingMode::Implied, 0x60),
+    ("RTI", AddressingMode::Implied, 0x40),
+    ("BRK", AddressingMode::Implied, 0x00),
+    ("BCC", AddressingMode::Relative, 0x90),
+    ("BCS", AddressingMode::Relative, 0xB0),
+    ("BEQ", AddressingMode::Relative, 0xF0),
+    ("BMI", AddressingMode::Relative, 0x30),
+    ("BNE", AddressingMode::Relative, 0xD0),
+    ("BPL", AddressingMode::Relative, 0x10),
+    ("BVC", AddressingMode::Relative, 0x50),
+    ("BVS", AddressingMode::Relative, 0x70),
+    // Flag Instructions
+    ("CLC", AddressingMode::Implied, 0x18),
+    ("SEC", AddressingMode::Implied, 0x38),
+    ("CLI", AddressingMode::Implied, 0x58),
+    ("SEI", AddressingMode::Implied, 0x78),
+    ("CLV", AddressingMode::Implied, 0xB8),
+    ("CLD", AddressingMode::Implied, 0xD8),
+    ("SED", AddressingMode::Implied, 0xF8),
+    // No Operation
+    ("NOP", AddressingMode::Implied, 0xEA),
+];
+
+// mnemonic for a documented opcode
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    OPCODE_ENTRIES
+        .iter()
+        .find(|&&(_, _, code)| code == opcode)
+        .map(|&(mnemonic, _, _)| mnemonic)
+}
+
+// opcode plus operand bytes
+pub fn instruction_length(opcode: u8) -> Option<u16> {
+    OPCODE_ENTRIES
+        .iter()
+        .find(|&&(_, _, code)| code == opcode)
+        .map(|&(_, mode, _)| 1 + operand_size(mode) as u16)
+}
+
+pub fn is_mnemonic(name: &str) -> bool {
+    OPCODE_ENTRIES.iter().any(|&(mnemonic, _, _)| mnemonic.eq_ignore_ascii_case(name))
+}
+
+/*
+ *  Disassembles the instruction at addr in the assembler's own syntax, returning it and its
+ *  length. Undocumented opcodes come out as a one byte .byte.
+ */
+pub fn disassemble(memory: &Memory, addr: u16) -> (String, u16) {
+    let opcode = memory.peek(addr);
+    let Some(&(mnemonic, mode, _)) = OPCODE_ENTRIES.iter().find(|&&(_, _, code)| code == opcode) else {
+        return (format!(".byte ${:02X}", opcode), 1);
+    };
 
+    let low = memory.peek(addr.wrapping_add(1));
+    let word = u16::from_le_bytes([low, memory.peek(addr.wrapping_add(2))]);
+    let text = match mode {
+        AddressingMode::Implied => mnemonic.to_string(),
+        AddressingMode::Accumulator => format!("{} A", mnemonic),
+        AddressingMode::Immediate => format!("{} #${:02X}", mnemonic, low),
+        AddressingMode::ZeroPage => format!("{} ${:02X}", mnemonic, low),
+        AddressingMode::Absolute => format!("{} ${:04X}", mnemonic, word),
+        AddressingMode::Indirect => format!("{} (${:04X})", mnemonic, word),
+        AddressingMode::ZeroPageX => format!("{} ${:02X},X", mnemonic, low),
+        AddressingMode::ZeroPageY => format!("{} ${:02X},Y", mnemonic, low),
+        AddressingMode::AbsoluteX => format!("{} ${:04X},X", mnemonic, word),
+        AddressingMode::AbsoluteY => format!("{} ${:04X},Y", mnemonic, word),
+        AddressingMode::IndirectX => format!("{} (${:02X},X)", mnemonic, low),
+        AddressingMode::IndirectY => format!("{} (${:02X}),Y", mnemonic, low),
+        AddressingMode::Relative => {
+            let target = addr.wrapping_add(2).wrapping_add(low as i8 as u16);
+            format!("{} ${:04X}", mnemonic, target)
+        }
+    };
+    (text, 1 + operand_size(mode) as u16)
+}
+
+fn opcode_table() -> HashMap<(String, AddressingMode), u8> {
     let mut opcode_table: HashMap<(String, AddressingMode), u8> = HashMap::new();
-    for (mnemonic, mode, opcode) in opcode_entries.into_iter() {
+    for (mnemonic, mode, opcode) in OPCODE_ENTRIES.into_iter() {
         opcode_table.insert((mnemonic.to_string(), mode), opcode);
     }
+    opcode_table
+}
+
+/*
+ *  Result of assembling a source file.
+ *  bytes are contiguous starting at origin; listing has one entry per source line and
+ *  source_map maps each instruction's address to its line.
+ */
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub symbols: SymbolTable,
+    pub listing: Vec<ListingLine>,
+    pub source_map: SourceMap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub line: usize,
+    pub label: Option<String>,
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub source: String,
+}
+
+impl Assembly {
+    // address, emitted bytes and the original source line side by side
+    pub fn listing_text (&self) -> String {
+        let mut out = String::new();
 
-    let mut program = Vec::new();
-    let contents = read_file(file_path);
+        for entry in &self.listing {
+            let bytes: Vec<String> = entry.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+
+            let address = if entry.bytes.is_empty() && entry.label.is_none() {
+                String::from("    ")
+            } else {
+                format!("{:04X}", entry.address)
+            };
 
-    for line in contents.lines() {
-        let line = line.split(';').next().unwrap().trim();
-        if line.is_empty() {
-            continue;
+            let line = format!("{:>5}  {}  {:<8}  {}", entry.line, address, bytes.join(" "), entry.source);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+enum Statement {
+    Instruction {
+        mnemonic: String,
+        mode: AddressingMode,
+        value: Value,
+    },
+    Org,
+    Data {
+        width: usize,
+        values: Vec<Value>,
+    },
+    Reserve(u16),
+    Segment,
+    Export,
+    Import,
+}
+
+struct ParsedLine {
+    line: usize,
+    label: Option<String>,
+    segment: usize,
+    // absolute address when assembling a program, offset into the segment for an object file
+    address: u16,
+    statement: Option<Statement>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    segment: usize,
+    address: u16,
+}
+
+// output of pass one
+struct Program {
+    object: bool,
+    origin: Option<u16>,
+    lines: Vec<ParsedLine>,
+    segments: Vec<String>,
+    labels: HashMap<String, Location>,
+    exports: Vec<String>,
+    imports: Vec<String>,
+}
+
+enum Symbol {
+    Address(u16),
+    Relocatable(RelocationTarget, u16),
+}
+
+fn error(line: usize, message: &str) -> Box<dyn std::error::Error> {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{} on line {}", message, line),
+    )
+    .into()
+}
+
+// $hex, %binary or decimal
+fn parse_number(text: &str) -> Option<u16> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('$') {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%') {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse::<u16>().ok()
+    }
+}
+
+// comma separated operands, commas inside quotes don't split
+fn split_list(operand: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in operand.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ',' if !in_quotes => {
+                items.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+fn parse_data(width: usize, operand: Option<&str>, line: usize) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+    let mut values = Vec::new();
+
+    for item in split_list(operand.unwrap_or("")) {
+        if width == 1 && item.len() >= 2 && item.starts_with('"') && item.ends_with('"') {
+            for byte in item[1..item.len() - 1].bytes() {
+                values.push(Value::Bytes(vec![byte]));
+            }
+        } else if let Some(number) = parse_number(&item) {
+            if width == 1 && number > 0xFF {
+                return Err(error(line, &format!("Byte value out of range: {}", item)));
+            }
+            values.push(Value::Bytes(number.to_le_bytes()[..width].to_vec()));
+        } else if let (1, Some(label)) = (width, item.strip_prefix('<')) {
+            values.push(Value::LowByte(label.trim().to_string()));
+        } else if let (1, Some(label)) = (width, item.strip_prefix('>')) {
+            values.push(Value::HighByte(label.trim().to_string()));
+        } else if is_identifier(&item) && width == 1 {
+            values.push(Value::LowByte(item));
+        } else if is_identifier(&item) {
+            values.push(Value::Label(item));
+        } else {
+            return Err(error(line, &format!("Invalid data value: {}", item)));
+        }
+    }
+
+    if values.is_empty() {
+        return Err(error(line, "Missing data values"));
+    }
+    Ok(values)
+}
+
+fn parse_names(operand: Option<&str>, line: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let names = split_list(operand.unwrap_or(""));
+    if names.is_empty() {
+        return Err(error(line, "Missing symbol name"));
+    }
+    for name in &names {
+        if !is_identifier(name) {
+            return Err(error(line, &format!("Invalid symbol name: {}", name)));
+        }
+    }
+    Ok(names)
+}
+
+fn statement_size(statement: &Statement) -> u16 {
+    match statement {
+        Statement::Instruction { mode, .. } => 1 + operand_size(*mode) as u16,
+        Statement::Data { width, values } => (width * values.len()) as u16,
+        Statement::Reserve(count) => *count,
+        _ => 0,
+    }
+}
+
+// pass one: split off labels, parse statements and work out every line's address
+fn parse_program(contents: &str, object: bool) -> Result<Program, Box<dyn std::error::Error>> {
+    let opcode_table = opcode_table();
+
+    let mut program = Program {
+        object,
+        origin: None,
+        lines: Vec::new(),
+        segments: vec![String::from("CODE")],
+        labels: HashMap::new(),
+        exports: Vec::new(),
+        imports: Vec::new(),
+    };
+    let mut addresses = vec![if object { 0 } else { DEFAULT_ORIGIN }];
+    let mut segment = 0;
+    // set once code ends exactly at $FFFF, where the next address would wrap to $0000
+    let mut at_top = false;
+    // labels seen before the first .org or emitted byte, bound once the origin is known
+    let mut pending_labels: Vec<String> = Vec::new();
+
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let mut line = raw_line.split(';').next().unwrap().trim();
+        let mut label_name = None;
+
+        if let Some((label, remainder)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_identifier(label) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid label on line {}: {}", line_number, label),
+                )
+                .into());
+            }
+            if program.labels.contains_key(label) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Duplicate label on line {}: {}", line_number, label),
+                )
+                .into());
+            }
+            program.labels.insert(label.to_string(), Location { segment, address: addresses[segment] });
+            if !object && program.origin.is_none() {
+                pending_labels.push(label.to_string());
+            }
+            label_name = Some(label.to_string());
+            line = remainder.trim();
         }
 
         let mut parts = line.splitn(2, |c: char| c.is_whitespace());
-        if let Some(instruction) = parts.next() {
+        let instruction = parts.next().unwrap_or("");
+        let operand_str = parts.next().map(str::trim);
+
+        let statement = if line.is_empty() {
+            None
+        } else if instruction.starts_with('.') {
+            let directive = instruction.to_ascii_lowercase();
+            let statement = match directive.as_str() {
+                ".org" => {
+                    if object {
+                        return Err(error(line_number, ".org is not allowed in an object file, use .segment"));
+                    }
+                    let target = operand_str
+                        .and_then(parse_number)
+                        .ok_or_else(|| error(line_number, "Invalid .org address"))?;
+                    if program.origin.is_some() && (at_top || target < addresses[segment]) {
+                        return Err(error(line_number, ".org cannot move backwards"));
+                    }
+                    program.origin.get_or_insert(target);
+                    addresses[segment] = target;
+                    program.bind_pending(&mut pending_labels, target);
+                    Statement::Org
+                }
+                ".byte" => Statement::Data { width: 1, values: parse_data(1, operand_str, line_number)? },
+                ".word" => Statement::Data { width: 2, values: parse_data(2, operand_str, line_number)? },
+                ".res" => {
+                    let count = operand_str
+                        .and_then(parse_number)
+                        .ok_or_else(|| error(line_number, "Invalid .res size"))?;
+                    Statement::Reserve(count)
+                }
+                ".segment" | ".export" | ".import" if !object => {
+                    return Err(error(line_number, &format!("{} is only valid when assembling an object file", directive)));
+                }
+                ".segment" => {
+                    let name = operand_str.unwrap_or("").trim_matches('"');
+                    if !is_identifier(name) {
+                        return Err(error(line_number, &format!("Invalid segment name: {}", name)));
+                    }
+                    segment = match program.segments.iter().position(|s| s == name) {
+                        Some(existing) => existing,
+                        None => {
+                            program.segments.push(name.to_string());
+                            addresses.push(0);
+                            program.segments.len() - 1
+                        }
+                    };
+                    Statement::Segment
+                }
+                ".export" => {
+                    program.exports.extend(parse_names(operand_str, line_number)?);
+                    Statement::Export
+                }
+                ".import" => {
+                    program.imports.extend(parse_names(operand_str, line_number)?);
+                    Statement::Import
+                }
+                _ => return Err(error(line_number, &format!("Unknown directive {}", instruction))),
+            };
+            Some(statement)
+        } else {
             let mnemonic = instruction.to_ascii_uppercase();
-            let operand_str = parts.next().map(str::trim);
-            let (addressing_mode, operand_bytes) = parse_operand(&mnemonic, operand_str)?;
+            let (addressing_mode, value) = parse_operand(&mnemonic, operand_str)?;
+            let (addressing_mode, value) = widen_zero_page(&opcode_table, &mnemonic, addressing_mode, value);
 
             let expected_operand_size = operand_size(addressing_mode);
-            if operand_bytes.len() != expected_operand_size {
+            if value_size(&value, addressing_mode) != expected_operand_size {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
                     format!(
@@ -247,16 +811,13 @@ pub fn assemble(file_path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>
                         mnemonic,
                         addressing_mode,
                         expected_operand_size,
-                        operand_bytes.len()
+                        value_size(&value, addressing_mode)
                     ),
                 )
                 .into());
             }
 
-            if let Some(&opcode) = opcode_table.get(&(mnemonic.clone(), addressing_mode)) {
-                program.push(opcode);
-                program.extend_from_slice(&operand_bytes);
-            } else {
+            if !opcode_table.contains_key(&(mnemonic.clone(), addressing_mode)) {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
                     format!(
@@ -266,8 +827,271 @@ pub fn assemble(file_path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>
                 )
                 .into());
             }
+
+            Some(Statement::Instruction { mnemonic, mode: addressing_mode, value })
+        };
+
+        let size = statement.as_ref().map_or(0, statement_size);
+        if size > 0 && !object {
+            let end = addresses[segment] as u32 + size as u32;
+            if at_top || end > 0x10000 {
+                return Err(error(line_number, "Code runs past $FFFF"));
+            }
+            at_top = end == 0x10000;
+            program.origin.get_or_insert(addresses[segment]);
+            program.bind_pending(&mut pending_labels, addresses[segment]);
+        }
+
+        program.lines.push(ParsedLine {
+            line: line_number,
+            label: label_name,
+            segment,
+            address: addresses[segment],
+            statement,
+        });
+        addresses[segment] = addresses[segment].wrapping_add(size);
+    }
+
+    for import in &program.imports {
+        if program.labels.contains_key(import) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Imported symbol is also defined locally: {}", import),
+            )
+            .into());
         }
     }
 
     Ok(program)
 }
+
+impl Program {
+    fn bind_pending (&mut self, pending: &mut Vec<String>, address: u16) {
+        for line in &mut self.lines {
+            if line.label.as_ref().is_some_and(|label| pending.contains(label)) {
+                line.address = address;
+            }
+        }
+        for label in pending.drain(..) {
+            if let Some(location) = self.labels.get_mut(&label) {
+                location.address = address;
+            }
+        }
+    }
+
+    fn lookup (&self, label: &str, line: usize) -> Result<Symbol, Box<dyn std::error::Error>> {
+        if let Some(location) = self.labels.get(label) {
+            if !self.object {
+                return Ok(Symbol::Address(location.address));
+            }
+            let segment = self.segments[location.segment].clone();
+            return Ok(Symbol::Relocatable(RelocationTarget::Segment(segment), location.address));
+        }
+
+        if self.imports.iter().any(|import| import == label) {
+            return Ok(Symbol::Relocatable(RelocationTarget::Import(label.to_string()), 0));
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Undefined label on line {}: {}", line, label),
+        )
+        .into())
+    }
+
+    /*
+     *  Turns one operand or data value into bytes.
+     *  addr is where the field itself goes; anything that can't be known until link time
+     *  comes back as zeroes plus a relocation.
+     */
+    fn encode (
+        &self,
+        value: &Value,
+        kind: RelocationKind,
+        segment: usize,
+        addr: u16,
+        line: usize,
+    ) -> Result<(Vec<u8>, Option<Relocation>), Box<dyn std::error::Error>> {
+        let label = match value {
+            Value::Bytes(bytes) => return Ok((bytes.clone(), None)),
+            Value::Label(label) | Value::LowByte(label) | Value::HighByte(label) => label,
+        };
+
+        let target = match self.lookup(label, line)? {
+            Symbol::Address(target) => target,
+            // a branch within one segment doesn't care where the segment ends up
+            Symbol::Relocatable(RelocationTarget::Segment(name), addend)
+                if kind == RelocationKind::Relative && name == self.segments[segment] => addend,
+            Symbol::Relocatable(target, addend) => {
+                let relocation = Relocation {
+                    segment: self.segments[segment].clone(),
+                    offset: addr,
+                    kind,
+                    target,
+                    addend,
+                };
+                return Ok((vec![0; kind.size()], Some(relocation)));
+            }
+        };
+
+        match kind {
+            RelocationKind::Word => Ok((target.to_le_bytes().to_vec(), None)),
+            RelocationKind::LowByte => Ok((vec![(target & 0xFF) as u8], None)),
+            RelocationKind::HighByte => Ok((vec![(target >> 8) as u8], None)),
+            RelocationKind::Relative => {
+                let offset = target as i32 - (addr as i32 + 1);
+                if !(-128..=127).contains(&offset) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Branch target out of range on line {}: {}", line, label),
+                    )
+                    .into());
+                }
+                Ok((vec![offset as i8 as u8], None))
+            }
+        }
+    }
+}
+
+fn value_kind(value: &Value, mode: Option<AddressingMode>) -> RelocationKind {
+    match value {
+        Value::LowByte(_) => RelocationKind::LowByte,
+        Value::HighByte(_) => RelocationKind::HighByte,
+        _ if mode == Some(AddressingMode::Relative) => RelocationKind::Relative,
+        _ => RelocationKind::Word,
+    }
+}
+
+// pass two: every label is known, emit bytes (and relocations) for each line
+fn emit_line(
+    program: &Program,
+    opcode_table: &HashMap<(String, AddressingMode), u8>,
+    parsed_line: &ParsedLine,
+) -> Result<(Vec<u8>, Vec<Relocation>), Box<dyn std::error::Error>> {
+    let mut emitted = Vec::new();
+    let mut relocations = Vec::new();
+    let segment = parsed_line.segment;
+    let line = parsed_line.line;
+
+    match &parsed_line.statement {
+        Some(Statement::Instruction { mnemonic, mode, value }) => {
+            emitted.push(opcode_table[&(mnemonic.clone(), *mode)]);
+            let field_addr = parsed_line.address.wrapping_add(1);
+            let (bytes, relocation) = program.encode(value, value_kind(value, Some(*mode)), segment, field_addr, line)?;
+            emitted.extend(bytes);
+            relocations.extend(relocation);
+        }
+        Some(Statement::Data { values, .. }) => {
+            for value in values {
+                let field_addr = parsed_line.address.wrapping_add(emitted.len() as u16);
+                let (bytes, relocation) = program.encode(value, value_kind(value, None), segment, field_addr, line)?;
+                emitted.extend(bytes);
+                relocations.extend(relocation);
+            }
+        }
+        Some(Statement::Reserve(count)) => emitted.resize(*count as usize, 0),
+        _ => {}
+    }
+
+    Ok((emitted, relocations))
+}
+
+pub fn assemble_source(contents: &str) -> Result<Assembly, Box<dyn std::error::Error>> {
+    assemble_named(contents, "<source>")
+}
+
+// file is the name recorded in the source map
+pub fn assemble_named(contents: &str, file: &str) -> Result<Assembly, Box<dyn std::error::Error>> {
+    let opcode_table = opcode_table();
+    let program = parse_program(contents, false)?;
+
+    let origin = program.origin.unwrap_or(DEFAULT_ORIGIN);
+    let source_lines: Vec<&str> = contents.lines().collect();
+    let mut bytes = Vec::new();
+    let mut listing = Vec::new();
+    let mut source_map = SourceMap::new();
+    source_map.add_source(file, contents);
+
+    for parsed_line in &program.lines {
+        let (emitted, _) = emit_line(&program, &opcode_table, parsed_line)?;
+
+        if matches!(parsed_line.statement, Some(Statement::Instruction { .. })) {
+            source_map.insert(parsed_line.address, file, parsed_line.line);
+        }
+
+        if !emitted.is_empty() || matches!(parsed_line.statement, Some(Statement::Org)) {
+            // fill any .org gap so bytes stay contiguous from origin
+            bytes.resize((parsed_line.address - origin) as usize, 0);
+            bytes.extend_from_slice(&emitted);
+        }
+
+        listing.push(ListingLine {
+            line: parsed_line.line,
+            label: parsed_line.label.clone(),
+            address: parsed_line.address,
+            bytes: emitted,
+            source: source_lines[parsed_line.line - 1].to_string(),
+        });
+    }
+
+    let mut symbols = SymbolTable::new();
+    for (name, location) in &program.labels {
+        symbols.insert(name, location.address);
+    }
+
+    Ok(Assembly {
+        origin,
+        bytes,
+        symbols,
+        listing,
+        source_map,
+    })
+}
+
+/*
+ *  Assembles a source file into a relocatable object.
+ *  .segment switches segment (CODE by default), .export/.import name symbols shared with other
+ *  objects, and the linker decides where each segment lives.
+ */
+pub fn assemble_object(contents: &str) -> Result<ObjectFile, Box<dyn std::error::Error>> {
+    let opcode_table = opcode_table();
+    let program = parse_program(contents, true)?;
+
+    let mut object = ObjectFile::new();
+    for name in &program.segments {
+        object.segments.push(ObjectSegment { name: name.clone(), data: Vec::new() });
+    }
+
+    for parsed_line in &program.lines {
+        let (emitted, relocations) = emit_line(&program, &opcode_table, parsed_line)?;
+        object.segments[parsed_line.segment].data.extend(emitted);
+        object.relocations.extend(relocations);
+    }
+
+    for name in &program.exports {
+        let location = program.labels.get(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Exported symbol is not defined: {}", name),
+            )
+        })?;
+        object.exports.push(Export {
+            name: name.clone(),
+            segment: program.segments[location.segment].clone(),
+            offset: location.address,
+        });
+    }
+
+    object.imports = program.imports.clone();
+
+    Ok(object)
+}
+
+pub fn assemble_file(file_path: String) -> Result<Assembly, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(&file_path).map_err(|e| format!("Can't read {}: {}", file_path, e))?;
+    assemble_named(&contents, &file_path)
+}
+
+pub fn assemble(file_path: String) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(assemble_file(file_path)?.bytes)
+}
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::devices::pia::Pia;
+use crate::devices::rom::Rom;
+use crate::devices::via::Pins;
+use crate::machines::config::{Line, Wired};
+use crate::Memory;
+
+/*
+ *  The Apple I: RAM everywhere below the monitor ROM, and a 6820 PIA for the keyboard and
+ *  the terminal.
+ *      $D010  KBD     the last key, with bit 7 set
+ *      $D011  KBDCR   bit 7 is set when a key is waiting
+ *      $D012  DSP     bits 0-6 go to the display; bit 7 reads 1 while it is busy
+ *      $D013  DSPCR
+ *      $FF00  the 256-byte monitor ROM (WozMon), which holds the reset vector
+ *
+ *  The display only knows upper case, and takes a carriage return as a new line. The PIA's
+ *  interrupt outputs aren't connected, so the keyboard strobe never interrupts the CPU even
+ *  though WozMon enables it and clears the I flag.
+ */
+
+pub const PIA_START: u16 = 0xD010;
+pub const PIA_END: u16 = 0xD013;
+pub const ROM_START: u16 = 0xFF00;
+pub const ROM_SIZE: usize = 0x100;
+pub const DEFAULT_ROM: &str = "roms/wozmon.bin";
+
+const CARRIAGE_RETURN: u8 = 0x0D;
+
+// the keyboard on port A and the display on port B
+struct Terminal {
+    output: Box<dyn Write>,
+    key: u8,
+    shown: u8,
+}
+
+impl Pins for Terminal {
+    fn drive (&mut self, _port_a: u8, port_b: u8) {
+        self.shown = port_b & 0x7F;
+    }
+
+    // PA7 is tied high, and the display is never busy
+    fn sense (&mut self) -> (u8, u8) {
+        (self.key | 0x80, 0x00)
+    }
+
+    // the PIA pulls CB2 low when a character is written
+    fn cb2_out (&mut self, level: bool) {
+        if level {
+            return;
+        }
+        let _ = match self.shown {
+            CARRIAGE_RETURN => self.output.write_all(b"\r\n"),
+            0x20..=0x7E => self.output.write_all(&[self.shown]),
+            _ => Ok(()),
+        };
+        let _ = self.output.flush();
+    }
+}
+
+pub struct Apple1 {
+    pia: Rc<RefCell<Pia>>,
+    terminal: Rc<RefCell<Terminal>>,
+}
+
+impl Apple1 {
+    // keys are upper-cased, and a new line becomes a carriage return
+    pub fn press_key (&self, key: u8) {
+        let key = match key {
+            b'\n' => CARRIAGE_RETURN,
+            key => key.to_ascii_uppercase() & 0x7F,
+        };
+        self.terminal.borrow_mut().key = key;
+        // the keyboard strobe pulses CA1
+        let mut pia = self.pia.borrow_mut();
+        pia.set_ca1(false);
+        pia.set_ca1(true);
+    }
+}
+
+/*
+ *  Attaches the PIA and the ROM, which must be the full 256 bytes so the vectors are in it.
+ *  Reset the CPU through its vector afterwards to start the monitor.
+ */
+pub fn setup (memory: &mut Memory, rom: Vec<u8>, output: impl Write + 'static) -> Result<Apple1, String> {
+    if rom.len() != ROM_SIZE {
+        return Err(format!("The Apple I monitor ROM must be {} bytes, got {}", ROM_SIZE, rom.len()));
+    }
+
+    let terminal = Rc::new(RefCell::new(Terminal { output: Box::new(output), key: 0, shown: 0 }));
+    let pia = Rc::new(RefCell::new(Pia::new()));
+    pia.borrow_mut().connect(terminal.clone());
+    memory.attach(PIA_START, PIA_END, Wired { device: pia.clone(), line: Line::None });
+    memory.attach(ROM_START, 0xFFFF, Rom::new(rom));
+    Ok(Apple1 { pia, terminal })
+}
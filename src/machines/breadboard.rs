@@ -0,0 +1,36 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::devices::lcd::{Lcd, LcdPins, BREADBOARD_WIRING};
+use crate::devices::rom::Rom;
+use crate::devices::via::Via;
+use crate::Memory;
+
+/*
+ *  The breadboard 6502 from Ben Eater's videos:
+ *      $0000-$3FFF  16 KiB RAM
+ *      $6000-$7FFF  6522 VIA, its sixteen registers repeated
+ *      $8000-$FFFF  32 KiB ROM, vectors included
+ *  A 16x2 HD44780 LCD hangs off the VIA: data on port B, and E, RW and RS on PA7-PA5.
+ *  Nothing else decodes $4000-$5FFF, which stays plain RAM here.
+ */
+
+pub const RAM_END: u16 = 0x3FFF;
+pub const VIA_START: u16 = 0x6000;
+pub const VIA_END: u16 = 0x7FFF;
+pub const ROM_START: u16 = 0x8000;
+pub const ROM_SIZE: usize = 0x8000;
+
+// attaches the VIA, the LCD and the ROM, which is loaded as-is; the LCD is returned for drawing
+pub fn setup (memory: &mut Memory, rom: Vec<u8>) -> Result<Rc<RefCell<Lcd>>, String> {
+    if rom.len() != ROM_SIZE {
+        return Err(format!("The breadboard computer's ROM must be {} bytes, got {}", ROM_SIZE, rom.len()));
+    }
+
+    let lcd = Rc::new(RefCell::new(Lcd::default()));
+    let mut via = Via::new();
+    via.connect(LcdPins::new(lcd.clone(), BREADBOARD_WIRING));
+    memory.attach(VIA_START, VIA_END, via);
+    memory.attach(ROM_START, 0xFFFF, Rom::new(rom));
+    Ok(lcd)
+}
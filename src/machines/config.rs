@@ -0,0 +1,430 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::devices::acia::Acia;
+use crate::devices::console::Console;
+use crate::devices::lcd::{Lcd, LcdPins, ViaWiring, BREADBOARD_4BIT_WIRING, BREADBOARD_WIRING, DEFAULT_COLUMNS, DEFAULT_ROWS};
+use crate::devices::pia::Pia;
+use crate::devices::random::Random;
+use crate::devices::rom::Rom;
+use crate::devices::via::Via;
+use crate::devices::Device;
+use crate::image_loader::DEFAULT_LOAD_ADDR;
+use crate::scheduler::parse_clock;
+use crate::serial::{self, SerialBackend};
+use crate::toml::Toml;
+use crate::{Memory, CPU};
+
+use super::Layout;
+
+/*
+ *  Machines described in a file rather than in code, in the TOML subset of crate::toml:
+ *
+ *      name = "breadboard"
+ *      cpu = "6502"            # the only variant emulated
+ *      clock = "1MHz"          # as --clock takes it
+ *      reset = "vector"        # "load" (the default) starts at the program, or an address
+ *      load_addr = 0x0200      # where programs go, $0600 if unset
+ *      exit_port = 0xF002      # optional, as --exit-port
+ *
+ *      [[region]]
+ *      type = "rom"            # or "ram", to preload or fill part of RAM
+ *      start = 0x8000
+ *      end = 0xFFFF            # optional for an image, which must fit
+ *      file = "rom.bin"        # relative to the machine file
+ *
+ *      [[device]]
+ *      type = "via"            # via, pia, acia, lcd, console or random
+ *      start = 0x6000
+ *      end = 0x7FFF            # optional, just its registers if unset
+ *      irq = "irq"             # the line it drives: irq (the default), nmi or none
+ *      lcd = { wiring = "breadboard", columns = 16, rows = 2 }
+ *
+ *  A VIA can carry an LCD ("breadboard" or "breadboard-4bit" wiring), an ACIA takes serial
+ *  as --serial does, an lcd device takes columns and rows, and random a seed. Everything not
+ *  covered by a region or a device is plain RAM.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reset {
+    // PC is wherever the program loader puts it
+    Load,
+    // through $FFFC, as the reset line does
+    Vector,
+    At(u16),
+}
+
+impl Reset {
+    // call once the program is loaded
+    pub fn apply (self, cpu: &mut CPU, memory: &mut Memory) {
+        match self {
+            Reset::Load => {}
+            Reset::Vector => cpu.reset_to_vector(memory),
+            Reset::At(addr) => cpu.pc = addr,
+        }
+    }
+}
+
+// which CPU input a device's interrupt output is wired to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line {
+    Irq,
+    Nmi,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Ram,
+    Rom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub start: u16,
+    pub end: Option<u16>,
+    pub file: Option<PathBuf>,
+    pub fill: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcdConfig {
+    pub wiring: ViaWiring,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceKind {
+    Via { lcd: Option<LcdConfig> },
+    Pia,
+    Acia { serial: SerialBackend },
+    Lcd { columns: usize, rows: usize },
+    Console,
+    Random { seed: Option<u32> },
+}
+
+impl DeviceKind {
+    fn registers (&self) -> u16 {
+        match self {
+            DeviceKind::Via { .. } => 16,
+            DeviceKind::Pia | DeviceKind::Acia { .. } => 4,
+            DeviceKind::Lcd { .. } | DeviceKind::Console => 2,
+            DeviceKind::Random { .. } => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceConfig {
+    pub kind: DeviceKind,
+    pub start: u16,
+    pub end: u16,
+    pub line: Line,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineConfig {
+    pub name: String,
+    pub clock_hz: Option<u64>,
+    pub reset: Reset,
+    pub layout: Layout,
+    pub regions: Vec<Region>,
+    pub devices: Vec<DeviceConfig>,
+}
+
+// a built machine: what the front end needs from its description, and the LCDs to draw
+pub struct Machine {
+    pub name: String,
+    pub clock_hz: Option<u64>,
+    pub reset: Reset,
+    pub layout: Layout,
+    pub lcds: Vec<Rc<RefCell<Lcd>>>,
+}
+
+const TOP_KEYS: &[&str] = &["name", "cpu", "clock", "reset", "load_addr", "exit_port", "region", "device"];
+const REGION_KEYS: &[&str] = &["type", "start", "end", "file", "fill"];
+const DEVICE_KEYS: &[&str] = &["type", "start", "end", "irq", "lcd", "serial", "columns", "rows", "seed"];
+const LCD_KEYS: &[&str] = &["wiring", "columns", "rows"];
+
+// catches misspelt keys, which would otherwise be quietly ignored
+fn check_keys (table: &Toml, allowed: &[&str], what: &str) -> Result<(), String> {
+    let entries = table.as_table().ok_or(format!("{} must be a table", what))?;
+    match entries.iter().find(|(key, _)| !allowed.contains(&key.as_str())) {
+        Some((key, _)) => Err(format!("Unknown key '{}' in {}", key, what)),
+        None => Ok(()),
+    }
+}
+
+fn string<'a> (table: &'a Toml, key: &str, what: &str) -> Result<Option<&'a str>, String> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or(format!("{}.{} must be a string, not {}", what, key, value.type_name())),
+    }
+}
+
+fn number (table: &Toml, key: &str, what: &str, max: i64) -> Result<Option<i64>, String> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(value) => match value.as_i64() {
+            Some(number) if (0..=max).contains(&number) => Ok(Some(number)),
+            _ => Err(format!("{}.{} must be an integer from 0 to {:#X}", what, key, max)),
+        },
+    }
+}
+
+fn address (table: &Toml, key: &str, what: &str) -> Result<Option<u16>, String> {
+    Ok(number(table, key, what, 0xFFFF)?.map(|addr| addr as u16))
+}
+
+fn tables<'a> (root: &'a Toml, key: &str) -> Result<&'a [Toml], String> {
+    match root.get(key) {
+        None => Ok(&[]),
+        Some(value) => value.as_array().ok_or(format!("{} must be written as [[{}]] tables", key, key)),
+    }
+}
+
+impl MachineConfig {
+    // reads a machine file; the images it names are found relative to it
+    pub fn load (path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read machine file {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        MachineConfig::parse(&text, base).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse (text: &str, base: &Path) -> Result<Self, String> {
+        let root = Toml::parse(text)?;
+        check_keys(&root, TOP_KEYS, "the machine")?;
+
+        match string(&root, "cpu", "machine")? {
+            None | Some("6502") | Some("nmos6502") => {}
+            Some(other) => return Err(format!("CPU '{}' isn't emulated; only the NMOS 6502 is", other)),
+        }
+        let clock_hz = match string(&root, "clock", "machine")? {
+            Some(clock) => parse_clock(clock)?,
+            None => None,
+        };
+        let reset = match root.get("reset") {
+            None => Reset::Load,
+            Some(Toml::String(mode)) if mode == "load" => Reset::Load,
+            Some(Toml::String(mode)) if mode == "vector" => Reset::Vector,
+            Some(Toml::Integer(_)) => Reset::At(address(&root, "reset", "machine")?.expect("reset is set")),
+            Some(_) => return Err("reset must be \"load\", \"vector\" or an address".to_string()),
+        };
+        let layout = Layout {
+            load_addr: address(&root, "load_addr", "machine")?.unwrap_or(DEFAULT_LOAD_ADDR),
+            exit_port: address(&root, "exit_port", "machine")?,
+        };
+
+        let regions = tables(&root, "region")?.iter().map(|table| parse_region(table, base)).collect::<Result<_, _>>()?;
+        let devices = tables(&root, "device")?.iter().map(parse_device).collect::<Result<_, _>>()?;
+        Ok(MachineConfig {
+            name: string(&root, "name", "machine")?.unwrap_or("custom").to_string(),
+            clock_hz,
+            reset,
+            layout,
+            regions,
+            devices,
+        })
+    }
+
+    /*
+     *  Fills in the regions, then attaches the devices in file order, so a later device wins
+     *  where two overlap. Serial ports are opened here, and a TCP one waits for its client.
+     */
+    pub fn build (&self, memory: &mut Memory) -> Result<Machine, String> {
+        for region in &self.regions {
+            build_region(region, memory)?;
+        }
+
+        let mut lcds = Vec::new();
+        for device in &self.devices {
+            let (start, end, line) = (device.start, device.end, device.line);
+            match &device.kind {
+                DeviceKind::Via { lcd } => {
+                    let mut via = Via::new();
+                    if let Some(config) = lcd {
+                        let lcd = Rc::new(RefCell::new(Lcd::new(config.columns, config.rows)));
+                        via.connect(LcdPins::new(lcd.clone(), config.wiring));
+                        lcds.push(lcd);
+                    }
+                    memory.attach(start, end, Wired { device: via, line });
+                }
+                DeviceKind::Pia => memory.attach(start, end, Wired { device: Pia::new(), line }),
+                DeviceKind::Acia { serial } => {
+                    let port = serial::open(*serial).map_err(|e| format!("Cannot open the serial port: {}", e))?;
+                    memory.attach(start, end, Wired { device: Acia::new(port.output, port.input), line });
+                }
+                DeviceKind::Lcd { columns, rows } => {
+                    let lcd = Rc::new(RefCell::new(Lcd::new(*columns, *rows)));
+                    memory.attach(start, end, lcd.clone());
+                    lcds.push(lcd);
+                }
+                DeviceKind::Console => memory.attach(start, end, Console::stdio()),
+                DeviceKind::Random { seed } => {
+                    let random = seed.map_or_else(Random::from_time, Random::new);
+                    memory.attach(start, end, random);
+                }
+            }
+        }
+
+        // the busy flag lasts as many cycles as the real LCD would at this clock
+        if let Some(hz) = self.clock_hz {
+            for lcd in &lcds {
+                lcd.borrow_mut().set_clock_hz(hz);
+            }
+        }
+        Ok(Machine { name: self.name.clone(), clock_hz: self.clock_hz, reset: self.reset, layout: self.layout, lcds })
+    }
+}
+
+fn parse_region (table: &Toml, base: &Path) -> Result<Region, String> {
+    const WHAT: &str = "[[region]]";
+    check_keys(table, REGION_KEYS, WHAT)?;
+    let kind = match string(table, "type", WHAT)? {
+        Some("ram") => RegionKind::Ram,
+        Some("rom") => RegionKind::Rom,
+        _ => return Err("[[region]] type must be \"ram\" or \"rom\"".to_string()),
+    };
+    let region = Region {
+        kind,
+        start: address(table, "start", WHAT)?.ok_or("[[region]] needs a start address")?,
+        end: address(table, "end", WHAT)?,
+        file: string(table, "file", WHAT)?.map(|file| base.join(file)),
+        fill: number(table, "fill", WHAT, 0xFF)?.map(|fill| fill as u8),
+    };
+
+    if region.end.is_some_and(|end| end < region.start) {
+        return Err(format!("[[region]] at ${:04X} ends before it starts", region.start));
+    }
+    match (region.kind, &region.file, region.fill, region.end) {
+        (RegionKind::Rom, None, _, _) => Err(format!("The ROM at ${:04X} needs a file", region.start)),
+        (_, Some(_), Some(_), _) => Err(format!("[[region]] at ${:04X} takes a file or a fill, not both", region.start)),
+        (RegionKind::Ram, None, _, None) => Err(format!("The RAM at ${:04X} needs an end", region.start)),
+        _ => Ok(region),
+    }
+}
+
+fn parse_device (table: &Toml) -> Result<DeviceConfig, String> {
+    const WHAT: &str = "[[device]]";
+    check_keys(table, DEVICE_KEYS, WHAT)?;
+    let columns = number(table, "columns", WHAT, 40)?.map_or(DEFAULT_COLUMNS, |n| n as usize);
+    let rows = number(table, "rows", WHAT, 4)?.map_or(DEFAULT_ROWS, |n| n as usize);
+
+    let kind = match string(table, "type", WHAT)?.ok_or("[[device]] needs a type")? {
+        "via" => DeviceKind::Via { lcd: table.get("lcd").map(parse_lcd).transpose()? },
+        "pia" => DeviceKind::Pia,
+        "acia" => DeviceKind::Acia { serial: SerialBackend::parse(string(table, "serial", WHAT)?.unwrap_or("stdio"))? },
+        "lcd" => DeviceKind::Lcd { columns, rows },
+        "console" => DeviceKind::Console,
+        "random" => DeviceKind::Random { seed: number(table, "seed", WHAT, u32::MAX as i64)?.map(|seed| seed as u32) },
+        other => return Err(format!("Unknown device type '{}', expected via, pia, acia, lcd, console or random", other)),
+    };
+    let line = match string(table, "irq", WHAT)? {
+        None | Some("irq") => Line::Irq,
+        Some("nmi") => Line::Nmi,
+        Some("none") => Line::None,
+        Some(other) => return Err(format!("Unknown interrupt line '{}', expected irq, nmi or none", other)),
+    };
+
+    let start = address(table, "start", WHAT)?.ok_or("[[device]] needs a start address")?;
+    let end = match address(table, "end", WHAT)? {
+        Some(end) if end < start => return Err(format!("The device at ${:04X} ends before it starts", start)),
+        Some(end) => end,
+        None => start.checked_add(kind.registers() - 1).ok_or(format!("The device at ${:04X} runs past $FFFF", start))?,
+    };
+    Ok(DeviceConfig { kind, start, end, line })
+}
+
+fn parse_lcd (table: &Toml) -> Result<LcdConfig, String> {
+    const WHAT: &str = "the VIA's lcd";
+    check_keys(table, LCD_KEYS, WHAT)?;
+    let wiring = match string(table, "wiring", WHAT)?.unwrap_or("breadboard") {
+        "breadboard" => BREADBOARD_WIRING,
+        "breadboard-4bit" => BREADBOARD_4BIT_WIRING,
+        other => return Err(format!("Unknown LCD wiring '{}', expected breadboard or breadboard-4bit", other)),
+    };
+    Ok(LcdConfig {
+        wiring,
+        columns: number(table, "columns", WHAT, 40)?.map_or(DEFAULT_COLUMNS, |n| n as usize),
+        rows: number(table, "rows", WHAT, 4)?.map_or(DEFAULT_ROWS, |n| n as usize),
+    })
+}
+
+// ROMs are attached in front of RAM; RAM regions are written straight into it
+fn build_region (region: &Region, memory: &mut Memory) -> Result<(), String> {
+    let image = match &region.file {
+        Some(path) => fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?,
+        None => Vec::new(),
+    };
+    let start = region.start as usize;
+    let end = region.end.map_or(start + image.len().max(1) - 1, |end| end as usize);
+    if end > 0xFFFF || start + image.len() > end + 1 {
+        let name = region.file.as_ref().map_or(String::new(), |path| path.display().to_string());
+        return Err(format!("{} ({} bytes) doesn't fit at ${:04X}-${:04X}", name, image.len(), start, end.min(0xFFFF)));
+    }
+
+    match region.kind {
+        // reads past the end of a short image float high
+        RegionKind::Rom => memory.attach(region.start, end as u16, Rom::new(image)),
+        RegionKind::Ram => {
+            if let Some(fill) = region.fill {
+                for addr in start..=end {
+                    memory.poke(addr as u16, fill);
+                }
+            }
+            for (offset, &byte) in image.iter().enumerate() {
+                memory.poke((start + offset) as u16, byte);
+            }
+        }
+    }
+    Ok(())
+}
+
+// a device whose interrupt output goes to line
+pub(crate) struct Wired<D> {
+    pub(crate) device: D,
+    pub(crate) line: Line,
+}
+
+impl<D: Device> Device for Wired<D> {
+    fn name (&self) -> &'static str {
+        self.device.name()
+    }
+
+    fn read (&mut self, offset: u16) -> u8 {
+        self.device.read(offset)
+    }
+
+    fn peek (&self, offset: u16) -> u8 {
+        self.device.peek(offset)
+    }
+
+    fn write (&mut self, offset: u16, value: u8) {
+        self.device.write(offset, value)
+    }
+
+    fn tick (&mut self, cycles: u64) {
+        self.device.tick(cycles)
+    }
+
+    fn irq (&self) -> bool {
+        self.line == Line::Irq && self.device.irq()
+    }
+
+    fn nmi (&self) -> bool {
+        self.line == Line::Nmi && self.device.irq()
+    }
+
+    fn save_state (&self) -> Vec<u8> {
+        self.device.save_state()
+    }
+
+    fn load_state (&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.device.load_state(data)
+    }
+}
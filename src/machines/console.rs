@@ -0,0 +1,21 @@
+use crate::devices::console::Console;
+use crate::Memory;
+
+use super::Layout;
+
+/*
+ *  A minimal machine for test programs and scripts: RAM everywhere, plus
+ *      $F000  write a character to stdout
+ *      $F001  read the next stdin byte, 0 if none is waiting
+ *      $F002  write to exit, with the value as the exit status
+ */
+
+pub const OUTPUT_ADDR: u16 = 0xF000;
+pub const INPUT_ADDR: u16 = 0xF001;
+pub const EXIT_ADDR: u16 = 0xF002;
+pub const LOAD_ADDR: u16 = 0x0600;
+
+pub fn setup (memory: &mut Memory, console: Console) -> Layout {
+    memory.attach(OUTPUT_ADDR, INPUT_ADDR, console);
+    Layout { load_addr: LOAD_ADDR, exit_port: Some(EXIT_ADDR) }
+}
@@ -0,0 +1,62 @@
+use crate::devices::random::Random;
+use crate::framebuffer::{Framebuffer, Rgb};
+use crate::Memory;
+
+use super::Layout;
+
+/*
+ *  The machine from the easy6502 tutorial (and its snake game):
+ *      $0200-$05FF  32x32 screen, one byte per pixel, low nibble picks a palette color
+ *      $FE          a new random byte on every read
+ *      $FF          ASCII code of the last key pressed
+ *      $0600        where programs are loaded
+ */
+
+pub const SCREEN_START: u16 = 0x0200;
+pub const SCREEN_END: u16 = 0x05FF;
+pub const SCREEN_WIDTH: usize = 32;
+pub const SCREEN_HEIGHT: usize = 32;
+pub const RANDOM_ADDR: u16 = 0x00FE;
+pub const KEY_ADDR: u16 = 0x00FF;
+pub const LOAD_ADDR: u16 = 0x0600;
+
+pub const PALETTE: [Rgb; 16] = [
+    [0x00, 0x00, 0x00], // black
+    [0xFF, 0xFF, 0xFF], // white
+    [0x88, 0x00, 0x00], // red
+    [0xAA, 0xFF, 0xEE], // cyan
+    [0xCC, 0x44, 0xCC], // purple
+    [0x00, 0xCC, 0x55], // green
+    [0x00, 0x00, 0xAA], // blue
+    [0xEE, 0xEE, 0x77], // yellow
+    [0xDD, 0x88, 0x55], // orange
+    [0x66, 0x44, 0x00], // brown
+    [0xFF, 0x77, 0x77], // light red
+    [0x33, 0x33, 0x33], // dark grey
+    [0x77, 0x77, 0x77], // grey
+    [0xAA, 0xFF, 0x66], // light green
+    [0x00, 0x88, 0xFF], // light blue
+    [0xBB, 0xBB, 0xBB], // light grey
+];
+
+// a seed makes the random byte repeatable; without one it comes from the clock
+pub fn setup (memory: &mut Memory, seed: Option<u32>) -> Layout {
+    let random = seed.map_or_else(Random::from_time, Random::new);
+    memory.attach(RANDOM_ADDR, RANDOM_ADDR, random);
+    Layout { load_addr: LOAD_ADDR, exit_port: None }
+}
+
+pub fn press_key (memory: &mut Memory, key: u8) {
+    memory.poke(KEY_ADDR, key);
+}
+
+pub fn framebuffer (memory: &Memory) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let addr = SCREEN_START + (y * SCREEN_WIDTH + x) as u16;
+            framebuffer.set_pixel(x, y, PALETTE[(memory.peek(addr) & 0x0F) as usize]);
+        }
+    }
+    framebuffer
+}
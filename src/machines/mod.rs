@@ -0,0 +1,32 @@
+use crate::devices::console::Console;
+use crate::Memory;
+
+pub mod apple1;
+pub mod breadboard;
+pub mod config;
+pub mod console;
+pub mod easy6502;
+
+/*
+ *  Machine profiles: the devices and memory layout of a particular board, set up on a fresh
+ *  Memory before a program is loaded. Boards can also be described in a file, see config.
+ */
+
+pub const MACHINES: &[&str] = &["easy6502", "console"];
+
+// what a program run on the machine needs to know about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub load_addr: u16,
+    // a write here ends a run, see RunOptions::exit_port
+    pub exit_port: Option<u16>,
+}
+
+// attaches the machine's devices, with console I/O on stdin and stdout
+pub fn setup (name: &str, memory: &mut Memory) -> Result<Layout, String> {
+    match name {
+        "easy6502" => Ok(easy6502::setup(memory, None)),
+        "console" => Ok(console::setup(memory, Console::stdio())),
+        _ => Err(format!("Unknown machine '{}', expected one of: {}", name, MACHINES.join(", "))),
+    }
+}
@@ -1,107 +1,456 @@
-mod cpu;
-mod memory;
-mod op_code;
-mod debugger;
-mod loader;
-
-use crate::memory::Memory;
-use crate::cpu::CPU;
-use crate::debugger::Debugger;
-use loader::assemble;
+use rust_6502_emulator::{CPU, Memory};
+use rust_6502_emulator::devices::acia::Acia;
+use rust_6502_emulator::formats::{write_output, OutputFormat};
+use rust_6502_emulator::gdb;
+use rust_6502_emulator::image_loader::DEFAULT_LOAD_ADDR;
+use rust_6502_emulator::loader::assemble_file;
+use rust_6502_emulator::machines::config::{MachineConfig, Reset};
+use rust_6502_emulator::machines::{self, apple1, breadboard, easy6502, Layout};
+use rust_6502_emulator::monitor::{dump_memory, parse_address, parse_range, Monitor};
+use rust_6502_emulator::profiler::Profiler;
+use rust_6502_emulator::runner::{self, load_program_file, RunOptions, EXIT_ERROR};
+use rust_6502_emulator::scheduler::{parse_clock, run_scheduled, Scheduler, DEFAULT_FPS};
+use rust_6502_emulator::serial::{self, SerialBackend};
+use rust_6502_emulator::symbols::SymbolTable;
+use rust_6502_emulator::terminal::{self, PlayOptions};
+use std::fs;
 use std::io::{self, Write};
+use std::net::TcpListener;
+use std::path::Path;
 
-fn load_program(cpu: &mut CPU, memory: &mut Memory) {
-    let program: Result<Vec<u8>, Box<dyn std::error::Error>> = assemble (String::from("/Users/maxwellisaacs/Dropbox/dev/arch_dev/rust/rust_6502_emulator/src/example.asm"));
+const ASSEMBLE_USAGE: &str =
+    "Usage: assemble <file.asm> [-f raw|prg|hex|srec|image] [-o <output>] [--listing <file>] [--symbols <file>] [--debug-info <file>]";
 
-    let program = match program {
-        Ok(program) => program,
-        Err(e) => {
-            eprintln!("failed to parse program {}", e);
-            return;
+// assemble <file.asm> [-f format] [-o output] [--listing file] [--symbols file] [--debug-info file]
+fn assemble_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut input = None;
+    let mut output = None;
+    let mut format = OutputFormat::Raw;
+    let mut listing = None;
+    let mut symbols = None;
+    let mut debug_info = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().cloned(),
+            "-f" | "--format" => {
+                let name = args.next().ok_or(ASSEMBLE_USAGE)?;
+                format = OutputFormat::from_name(name).ok_or(format!("Unknown output format: {}", name))?;
+            }
+            "--listing" => listing = args.next().cloned(),
+            "--symbols" => symbols = args.next().cloned(),
+            "--debug-info" => debug_info = args.next().cloned(),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg.clone()),
+            _ => return Err(ASSEMBLE_USAGE.into()),
+        }
+    }
+
+    let input = input.ok_or(ASSEMBLE_USAGE)?;
+    let output = output.unwrap_or_else(|| {
+        Path::new(&input).with_extension(format.extension()).to_string_lossy().into_owned()
+    });
+
+    let assembly = assemble_file(input)?;
+    fs::write(&output, write_output(format, &assembly.blocks()))?;
+
+    if let Some(path) = listing {
+        fs::write(path, assembly.listing_text())?;
+    }
+    if let Some(path) = symbols {
+        fs::write(path, assembly.symbols.to_vice_labels())?;
+    }
+    if let Some(path) = debug_info {
+        fs::write(path, assembly.source_map.to_text())?;
+    }
+
+    Ok(())
+}
+
+const GDB_USAGE: &str = "Usage: gdb <program> [--port <port>]";
+
+// gdb <program> [--port port]: waits for one GDB client on localhost
+fn gdb_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut program = None;
+    let mut port = gdb::DEFAULT_PORT;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "--port" => port = args.next().ok_or(GDB_USAGE)?.parse()?,
+            _ if program.is_none() && !arg.starts_with('-') => program = Some(arg.clone()),
+            _ => return Err(GDB_USAGE.into()),
         }
+    }
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    cpu.reset();
+    load_program_file(&mut cpu, &mut memory, &program.ok_or(GDB_USAGE)?, DEFAULT_LOAD_ADDR)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for GDB on 127.0.0.1:{} (target remote :{})", port, port);
+    gdb::serve(&mut cpu, &mut memory, &listener)?;
+    Ok(())
+}
+
+const RUN_USAGE: &str = "\
+Usage: run [<program>] [--machine <name> | --config <machine.toml>] [--acia <addr>] [--serial stdio|pty|tcp:<port>] [--load-addr <addr>] [--entry <addr>] [--labels <file>] [--break <spec>]... [--trace] [--clock <rate>]
+           [--profile] [--profile-stacks <file>] [--dump-mem <start>-<end>]... [--screenshot <file.png|file.ppm>] [--max-cycles <n>] [--max-instructions <n>] [--stop-at <addr>] [--stop-on-brk] [--stop-on-loop] [--exit-port <addr>]";
+const DEBUG_USAGE: &str = "Usage: debug [<program>] [--machine <name> | --config <machine.toml>] [--acia <addr>] [--serial stdio|pty|tcp:<port>] [--load-addr <addr>] [--entry <addr>] [--labels <file>] [--break <spec>]...";
+
+struct LaunchArgs {
+    // optional with a machine file, whose ROMs may be all there is to run
+    program: Option<String>,
+    machine: Option<String>,
+    config: Option<String>,
+    // a 6551 at this address, connected to serial
+    acia: Option<u16>,
+    serial: SerialBackend,
+    // defaults to where the machine loads programs
+    load_addr: Option<u16>,
+    entry: Option<u16>,
+    // a VICE label file, for programs that weren't assembled from source
+    labels: Option<String>,
+    breakpoints: Vec<String>,
+    options: RunOptions,
+    dumps: Vec<(u16, u16)>,
+    screenshot: Option<String>,
+    // throttles run to this rate; unset runs flat out
+    clock_hz: Option<u64>,
+    // print a profile report, write the collapsed stacks to a file, or both
+    profile: bool,
+    profile_stacks: Option<String>,
+}
+
+// options shared by run and debug; the run-only ones are rejected for debug
+fn parse_launch_args(args: &[String], usage: &'static str, headless: bool) -> Result<LaunchArgs, Box<dyn std::error::Error>> {
+    let mut launch = LaunchArgs {
+        program: None,
+        machine: None,
+        config: None,
+        acia: None,
+        serial: SerialBackend::Stdio,
+        load_addr: None,
+        entry: None,
+        labels: None,
+        breakpoints: Vec::new(),
+        options: RunOptions::default(),
+        dumps: Vec::new(),
+        screenshot: None,
+        clock_hz: None,
+        profile: false,
+        profile_stacks: None,
     };
 
-    let start_addr = 0x0600;
-    for (i, &byte) in program.iter().enumerate() {
-        memory.write(start_addr + i as u16, byte);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(usage);
+        match arg.as_str() {
+            "-m" | "--machine" => launch.machine = Some(value()?.clone()),
+            "--config" => launch.config = Some(value()?.clone()),
+            "--acia" => launch.acia = Some(parse_address(value()?)?),
+            "--serial" => launch.serial = SerialBackend::parse(value()?)?,
+            "--load-addr" => launch.load_addr = Some(parse_address(value()?)?),
+            "--entry" => launch.entry = Some(parse_address(value()?)?),
+            "--labels" => launch.labels = Some(value()?.clone()),
+            "-b" | "--break" => launch.breakpoints.push(value()?.clone()),
+            "--max-cycles" if headless => launch.options.max_cycles = Some(value()?.parse()?),
+            "--max-instructions" if headless => launch.options.max_instructions = Some(value()?.parse()?),
+            "--stop-at" if headless => launch.options.stop_at = Some(parse_address(value()?)?),
+            "--stop-on-brk" if headless => launch.options.stop_on_brk = true,
+            "--stop-on-loop" if headless => launch.options.stop_on_self_jump = true,
+            "--exit-port" if headless => launch.options.exit_port = Some(parse_address(value()?)?),
+            "--trace" if headless => launch.options.trace = true,
+            "--dump-mem" if headless => launch.dumps.push(parse_range(value()?)?),
+            "--screenshot" if headless => launch.screenshot = Some(value()?.clone()),
+            "--clock" if headless => launch.clock_hz = parse_clock(value()?)?,
+            "--profile" if headless => launch.profile = true,
+            "--profile-stacks" if headless => launch.profile_stacks = Some(value()?.clone()),
+            _ if launch.program.is_none() && !arg.starts_with('-') => launch.program = Some(arg.clone()),
+            _ => return Err(usage.into()),
+        }
     }
 
-    cpu.pc = start_addr;
+    if launch.program.is_none() && launch.config.is_none() {
+        return Err(usage.into());
+    }
+    if launch.machine.is_some() && launch.config.is_some() {
+        return Err("--machine and --config both pick the machine; give one of them".into());
+    }
+    if launch.serial != SerialBackend::Stdio && launch.acia.is_none() {
+        return Err("--serial connects the ACIA, so it needs --acia <addr>".into());
+    }
+    if launch.screenshot.is_some() && launch.machine.as_deref() != Some("easy6502") {
+        return Err("--screenshot needs a machine with a screen (--machine easy6502)".into());
+    }
+    Ok(launch)
+}
 
+/*
+ *  Sets up the machine and loads the program; the machine's exit port and clock apply unless
+ *  others were given. A machine file's reset behaviour decides where the program starts.
+ */
+fn launch(launch: &mut LaunchArgs) -> Result<(CPU, Memory), Box<dyn std::error::Error>> {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    cpu.reset();
+    let (layout, reset) = match (&launch.machine, &launch.config) {
+        (Some(name), _) => (machines::setup(name, &mut memory)?, Reset::Load),
+        (None, Some(path)) => {
+            let machine = MachineConfig::load(Path::new(path))?.build(&mut memory)?;
+            launch.clock_hz = launch.clock_hz.or(machine.clock_hz);
+            (machine.layout, machine.reset)
+        }
+        (None, None) => (Layout { load_addr: DEFAULT_LOAD_ADDR, exit_port: None }, Reset::Load),
+    };
+    if let Some(addr) = launch.acia {
+        let port = serial::open(launch.serial)?;
+        memory.attach(addr, addr.wrapping_add(3), Acia::new(port.output, port.input));
+    }
+    launch.options.exit_port = launch.options.exit_port.or(layout.exit_port);
+    let load_addr = launch.load_addr.unwrap_or(layout.load_addr);
+    match &launch.program {
+        Some(program) => load_program_file(&mut cpu, &mut memory, program, load_addr)?,
+        None => cpu.pc = load_addr,
+    }
+    reset.apply(&mut cpu, &mut memory);
+
+    if let Some(path) = &launch.labels {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        cpu.debugger.symbols = SymbolTable::parse_vice_labels(&text)?;
+    }
+    if let Some(entry) = launch.entry {
+        cpu.pc = entry;
+    }
+    for spec in &launch.breakpoints {
+        let (on, condition) = cpu.debugger.parse_breakpoint(spec)?;
+        cpu.debugger.add_breakpoint(on, condition);
+    }
+    Ok((cpu, memory))
 }
 
+// screenshots of a 32x32 screen are too small to look at unscaled
+const SCREENSHOT_SCALE: usize = 8;
+// rows in each table of the profile report
+const PROFILE_LIMIT: usize = 20;
 
+// run <program> [options]: runs without a prompt and returns the exit status
+fn run_command(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut args = parse_launch_args(args, RUN_USAGE, true)?;
+    let (mut cpu, mut memory) = launch(&mut args)?;
+    // nothing can step back through a headless run, so skip recording it
+    cpu.debugger.history.set_limit(0);
+    if args.profile || args.profile_stacks.is_some() {
+        cpu.debugger.profiler = Some(Profiler::new());
+    }
 
-fn run_step_mode(cpu: &mut CPU, memory: &mut Memory) {
-    loop {
-        // Print a snapshot of the CPU state
-        println!("--- CPU State ---");
-        println!("PC:     {:#06X}", cpu.pc);
-        println!("A:      {:#04X}", cpu.a);
-        println!("X:      {:#04X}", cpu.x);
-        println!("Y:      {:#04X}", cpu.y);
-        println!("SP:     {:#04X}", cpu.sp);
-        println!("Status: {:#04X}", cpu.status);
-        println!("-----------------");
-
-        // Prompt the user for a command
-        print!("Enter command (n: next, s: stop, m <addr>: dump memory): ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).expect("Failed to read input");
-        let input = input.trim();
-
-        if input == "n" {
-            // Execute the next opcode
-            cpu.execute(memory);
-        } else if input == "s" {
-            println!("Stopping execution.");
-            cpu.pc = 0x2000;
-            break;
-        } else if input.starts_with("m") {
-            // Expecting a command like "m -8F" or "m 8F"
-            let parts: Vec<&str> = input.split_whitespace().collect();
-            if parts.len() < 2 {
-                println!("Usage: m <address in hex>");
-            } else {
-                // Remove any '-' that might be present
-                let hex_str = parts[1].trim_start_matches('-');
-                match u16::from_str_radix(hex_str, 16) {
-                    Ok(addr) => {
-                        let value = memory.read(addr);
-                        println!("Memory at {:#04X}: {:#04X}", addr, value);
-                    }
-                    Err(e) => println!("Invalid hex address: {}", e),
-                }
-            }
-        } else {
-            println!("Unknown command. Use 'n', 's', or 'm <address>'");
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let outcome = match args.clock_hz {
+        Some(hz) => {
+            let mut scheduler = Scheduler::new(Some(hz), DEFAULT_FPS);
+            let outcome = run_scheduled(&mut cpu, &mut memory, &args.options, &mut scheduler, &mut out, |_, _, _| Ok(true))?;
+            eprintln!("Ran at {:.3} MHz", scheduler.average_mhz());
+            outcome.expect("runs without a frame callback only end on a stop condition")
+        }
+        None => runner::run(&mut cpu, &mut memory, &args.options, &mut out)?,
+    };
+    for &(start, end) in &args.dumps {
+        dump_memory(&memory, start, end, &mut out)?;
+    }
+    out.flush()?;
+    if let Some(path) = &args.screenshot {
+        easy6502::framebuffer(&memory).scaled(SCREENSHOT_SCALE).save(path)?;
+    }
+    if let Some(profiler) = &cpu.debugger.profiler {
+        if args.profile {
+            profiler.write_report(&memory, &cpu.debugger.symbols, PROFILE_LIMIT, &mut io::stderr())?;
+        }
+        if let Some(path) = &args.profile_stacks {
+            fs::write(path, profiler.collapsed_stacks(&cpu.debugger.symbols))?;
         }
     }
+
+    eprintln!("{}", outcome);
+    Ok(outcome.exit_code())
 }
 
+// debug <program> [options]: loads the program and hands it to the monitor
+fn debug_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut cpu, mut memory) = launch(&mut parse_launch_args(args, DEBUG_USAGE, false)?)?;
+
+    let stdin = io::stdin();
+    Monitor::new().run(&mut cpu, &mut memory, stdin.lock(), &mut io::stdout())?;
+    Ok(())
+}
+
+const PLAY_USAGE: &str = "\
+Usage: play <program> [--clock <rate>] [--fps <n>] [--load-addr <addr>] [--entry <addr>] [--break <spec>]...
+            [--screenshot <file>] [--max-cycles <n>] [--stop-at <addr>] [--stop-on-brk] [--stop-on-loop] [--exit-port <addr>]";
+
+// play <program> [options]: runs an easy6502 program in real time on the terminal
+fn play_command(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut options = PlayOptions::default();
+    let mut launch_args = vec![String::from("--machine"), String::from("easy6502")];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clock" => options.clock_hz = parse_clock(args.next().ok_or(PLAY_USAGE)?)?,
+            "--fps" => options.fps = args.next().ok_or(PLAY_USAGE)?.parse()?,
+            _ => launch_args.push(arg.clone()),
+        }
+    }
 
-fn run_program(cpu: &mut CPU, memory: &mut Memory, debugger: &mut Debugger) {
-    println!("Starting program execution...\n");
+    let mut args = parse_launch_args(&launch_args, PLAY_USAGE, true)?;
+    if args.options.trace || !args.dumps.is_empty() || args.profile || args.profile_stacks.is_some() {
+        return Err(PLAY_USAGE.into());
+    }
+    let (mut cpu, mut memory) = launch(&mut args)?;
+    cpu.debugger.history.set_limit(0);
+    options.run = args.options.clone();
 
-    loop {
-        // Execute the opcode
-        run_step_mode(cpu, memory);
+    let outcome = terminal::play(&mut cpu, &mut memory, &options)?;
+    let screen = easy6502::framebuffer(&memory);
+    print!("{}", terminal::render_half_blocks(&screen));
+    if let Some(path) = &args.screenshot {
+        screen.scaled(SCREENSHOT_SCALE).save(path)?;
+    }
 
-        // Break if PC reaches the end of the program
-        if cpu.pc == 0x2000 {
-            break;
+    match outcome {
+        Some(outcome) => {
+            eprintln!("{}", outcome);
+            Ok(outcome.exit_code())
         }
+        None => Ok(0),
     }
 }
 
+const USAGE: &str = "\
+Usage: rust_6502_emulator <command> ...
+  run <program>       run headless until a stop condition
+  play <program>      run an easy6502 program on the terminal, with keyboard input
+  debug <program>     load a program into the interactive monitor
+  assemble <file.asm> assemble to a binary or image file
+  gdb <program>       serve a program to GDB over TCP
+  apple1              boot an Apple I into its monitor ROM on the terminal
+  breadboard <rom>    run a breadboard 6502 ROM, with its LCD on the terminal";
+
+const APPLE1_USAGE: &str = "Usage: apple1 [--rom <wozmon.bin>] [--clock <rate>]";
+// the Apple I's reset button
+const CTRL_R: u8 = 0x12;
+
+// apple1 [--rom file] [--clock rate]: boots the monitor ROM with the terminal as keyboard and display
+fn apple1_command(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut rom = apple1::DEFAULT_ROM.to_string();
+    let mut options = PlayOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rom" => rom = args.next().ok_or(APPLE1_USAGE)?.clone(),
+            "--clock" => options.clock_hz = parse_clock(args.next().ok_or(APPLE1_USAGE)?)?,
+            _ => return Err(APPLE1_USAGE.into()),
+        }
+    }
+
+    let image = fs::read(&rom).map_err(|e| format!("Can't read the monitor ROM {}: {} (WozMon isn't included, pass --rom <file>)", rom, e))?;
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let apple1 = apple1::setup(&mut memory, image, io::stdout())?;
+    cpu.reset_to_vector(&mut memory);
+    cpu.debugger.history.set_limit(0);
+    println!("Apple I, Ctrl-C quits, Ctrl-R resets");
+
+    let outcome = terminal::interact(&mut cpu, &mut memory, &options, |cpu, memory, key| match key {
+        CTRL_R => cpu.reset_to_vector(memory),
+        key => apple1.press_key(key),
+    }, |_| Ok(()))?;
+    println!();
+    Ok(match outcome {
+        Some(outcome) => {
+            println!("{}", outcome);
+            outcome.exit_code()
+        }
+        None => 0,
+    })
+}
+
+const BREADBOARD_USAGE: &str = "Usage: breadboard <rom.bin> [--clock <rate>]";
+
+// breadboard <rom.bin> [--clock rate]: runs a 32 KiB ROM image with its LCD drawn on the terminal
+fn breadboard_command(args: &[String]) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut rom = None;
+    let mut options = PlayOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clock" => options.clock_hz = parse_clock(args.next().ok_or(BREADBOARD_USAGE)?)?,
+            _ if rom.is_none() && !arg.starts_with('-') => rom = Some(arg.clone()),
+            _ => return Err(BREADBOARD_USAGE.into()),
+        }
+    }
+
+    let image = fs::read(rom.ok_or(BREADBOARD_USAGE)?)?;
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let lcd = breadboard::setup(&mut memory, image)?;
+    // the busy flag lasts as many cycles as the real LCD would at this clock
+    if let Some(hz) = options.clock_hz {
+        lcd.borrow_mut().set_clock_hz(hz);
+    }
+    cpu.reset_to_vector(&mut memory);
+    cpu.debugger.history.set_limit(0);
+    println!("Breadboard computer, Ctrl-C quits, Ctrl-R resets");
+
+    // the LCD is redrawn in place, and only when it changes
+    let mut shown: Option<Vec<String>> = None;
+    let outcome = terminal::interact(&mut cpu, &mut memory, &options, |cpu, memory, key| {
+        if key == CTRL_R {
+            cpu.reset_to_vector(memory);
+        }
+    }, |out| {
+        let lines = lcd.borrow().lines();
+        if shown.as_ref() != Some(&lines) {
+            if let Some(previous) = &shown {
+                write!(out, "\x1b[{}A", previous.len() + 2)?;
+            }
+            write!(out, "{}", terminal::render_text_panel(&lines))?;
+            shown = Some(lines);
+        }
+        Ok(())
+    })?;
+    Ok(match outcome {
+        Some(outcome) => {
+            println!("{}", outcome);
+            outcome.exit_code()
+        }
+        None => 0,
+    })
+}
+
 fn main() {
-    let mut cpu: CPU = CPU::new();
-    let mut memory: Memory = Memory::new();
-    let mut debugger: Debugger = Debugger::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let rest = args.get(1..).unwrap_or_default();
 
-    cpu.reset();
-    load_program(&mut cpu, &mut memory);
-    run_program(&mut cpu, &mut memory, &mut debugger);
+    let result = match args.first().map(String::as_str) {
+        Some("run") => run_command(rest),
+        Some("play") => play_command(rest),
+        Some("debug") => debug_command(rest).map(|_| 0),
+        Some("assemble") => assemble_command(rest).map(|_| 0),
+        Some("gdb") => gdb_command(rest).map(|_| 0),
+        Some("apple1") => apple1_command(rest),
+        Some("breadboard") => breadboard_command(rest),
+        _ => Err(USAGE.into()),
+    };
+
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
 }
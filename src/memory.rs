@@ -1,17 +1,146 @@
+use crate::devices::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+// one bus access; for reads old and value are the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub addr: u16,
+    pub old: u8,
+    pub value: u8,
+}
+
+// a device and the range it answers for, start..=end
+struct Mapping {
+    start: u16,
+    end: u16,
+    device: Box<dyn Device>,
+}
+
 pub struct Memory {
     data: [u8; 0x10000],
+    // only recorded while something (the debugger) is listening
+    accesses: Option<Vec<Access>>,
+    devices: Vec<Mapping>,
 }
 
 impl Memory {
     pub fn new () -> Self {
-        Memory {data: [0; 0x10000] }
+        Memory {data: [0; 0x10000], accesses: None, devices: Vec::new() }
+    }
+
+    pub fn read (&mut self, addr: u16) -> u8 {
+        let value = match self.mapping(addr) {
+            Some(i) => {
+                let mapping = &mut self.devices[i];
+                mapping.device.read(addr - mapping.start)
+            }
+            None => self.data[addr as usize],
+        };
+        if let Some(accesses) = &mut self.accesses {
+            accesses.push(Access { kind: AccessKind::Read, addr, old: value, value });
+        }
+        value
     }
 
-    pub fn read (&self, addr: u16) -> u8 {
-        self.data[addr as usize]
+    // read without it counting as a bus access, for debuggers and renderers
+    pub fn peek (&self, addr: u16) -> u8 {
+        match self.mapping(addr) {
+            Some(i) => self.devices[i].device.peek(addr - self.devices[i].start),
+            None => self.data[addr as usize],
+        }
     }
 
     pub fn write (&mut self, addr: u16, value: u8) {
+        if self.accesses.is_some() {
+            let old = self.peek(addr);
+            if let Some(accesses) = &mut self.accesses {
+                accesses.push(Access { kind: AccessKind::Write, addr, old, value });
+            }
+        }
+        match self.mapping(addr) {
+            Some(i) => {
+                let mapping = &mut self.devices[i];
+                mapping.device.write(addr - mapping.start, value);
+            }
+            None => self.data[addr as usize] = value,
+        }
+    }
+
+    // writes RAM directly, for front ends feeding input in between instructions
+    pub fn poke (&mut self, addr: u16, value: u8) {
         self.data[addr as usize] = value;
     }
+
+    /*
+     *  Maps a device over start..=end, in front of the RAM there. Devices attached later win
+     *  where ranges overlap.
+     */
+    pub fn attach<D: Device + 'static> (&mut self, start: u16, end: u16, device: D) {
+        self.devices.insert(0, Mapping { start, end, device: Box::new(device) });
+    }
+
+    // whether a device answers at addr rather than RAM
+    pub fn is_device (&self, addr: u16) -> bool {
+        self.mapping(addr).is_some()
+    }
+
+    fn mapping (&self, addr: u16) -> Option<usize> {
+        self.devices.iter().position(|mapping| (mapping.start..=mapping.end).contains(&addr))
+    }
+
+    // attached devices, most recently attached first
+    pub fn devices (&self) -> impl Iterator<Item = &dyn Device> {
+        self.devices.iter().map(|mapping| mapping.device.as_ref())
+    }
+
+    pub fn devices_mut (&mut self) -> impl Iterator<Item = &mut dyn Device> {
+        self.devices.iter_mut().map(|mapping| mapping.device.as_mut() as &mut dyn Device)
+    }
+
+    // clocks every device with the cycles the last instruction took
+    pub fn tick (&mut self, cycles: u64) {
+        for mapping in &mut self.devices {
+            mapping.device.tick(cycles);
+        }
+    }
+
+    // whether any device is holding the IRQ line
+    pub fn irq (&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.irq())
+    }
+
+    // whether any device is pulling NMI low
+    pub fn nmi (&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.nmi())
+    }
+
+    // the whole address space, for save states
+    pub fn contents (&self) -> &[u8] {
+        &self.data
+    }
+
+    // replaces the address space without it counting as bus writes
+    pub fn set_contents (&mut self, data: &[u8]) {
+        let len = data.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+
+    pub fn set_tracing (&mut self, enabled: bool) {
+        if enabled && self.accesses.is_none() {
+            self.accesses = Some(Vec::new());
+        } else if !enabled {
+            self.accesses = None;
+        }
+    }
+
+    // accesses since the last call
+    pub fn take_accesses (&mut self) -> Vec<Access> {
+        self.accesses.as_mut().map(std::mem::take).unwrap_or_default()
+    }
 }
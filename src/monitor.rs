@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use crate::cpu::CPU;
+use crate::debugger::{flags, StopReason, WatchKind, Watchpoint};
+use crate::loader::disassemble;
+use crate::runner::RunOutcome;
+use crate::savestate::Snapshot;
+use crate::Memory;
+
+/*
+ *  Interactive monitor for stepping through a program. Addresses and bytes are hex, with or
+ *  without a $ or 0x prefix; counts are decimal. An empty line repeats the last command, and
+ *  `m` or `d` on their own carry on from where the last dump ended.
+ */
+
+const HELP: &str = "\
+n [count]               step count instructions (default 1)
+o                       step over a JSR
+out                     run until the current subroutine returns
+c                       continue until a breakpoint or watchpoint
+u <addr>                run until PC reaches addr
+rs / rc                 step back / continue backward
+h [count]               recently executed instructions and what they changed
+r [<reg> <value>]       show registers, or set A X Y SP PC or P
+m [<start> [<end>|+<len>]]  hex and ASCII dump
+e <addr> <byte>...      write bytes starting at addr
+f <start> <end> <byte>  fill start..=end with byte
+d [<addr> [count]]      disassemble count instructions (default 10)
+b <spec>                break on $addr, file:line, a mnemonic or `opcode $xx`, optionally `if <cond>`
+w/wr/wa <range>         watch writes / reads / both, e.g. `w $0200-$02FF == $01`
+bl                      list breakpoints and watchpoints
+bd/be/bx <id>           delete / enable / disable
+bi <id> <count>         ignore the next count hits
+mark/goto <name>        bookmark the machine state / return to a bookmark
+save/load <file>        write / read a save state file
+history, !<n>, !!       list, rerun command n, rerun the last command
+q                       quit";
+
+// run commands stop after this many instructions so a tight loop can't hang the prompt
+pub const RUN_LIMIT: u64 = 10_000_000;
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+
+const DUMP_BYTES: u16 = 64;
+const DISASSEMBLE_COUNT: usize = 10;
+
+type CommandResult = Result<bool, Box<dyn Error>>;
+
+pub struct Monitor {
+    history: Vec<String>,
+    // where a bare m or d continues from
+    next_dump: u16,
+    next_disassemble: Option<u16>,
+    bookmarks: HashMap<String, Snapshot>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Monitor {
+    pub fn new () -> Self {
+        Monitor {
+            history: Vec::new(),
+            next_dump: 0,
+            next_disassemble: None,
+            bookmarks: HashMap::new(),
+        }
+    }
+
+    pub fn history (&self) -> &[String] {
+        &self.history
+    }
+
+    // reads commands until q or the end of input
+    pub fn run<R: BufRead, W: Write> (&mut self, cpu: &mut CPU, memory: &mut Memory, input: R, out: &mut W) -> std::io::Result<()> {
+        show_state(cpu, memory, out)?;
+        let mut lines = input.lines();
+
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+
+            let Some(line) = lines.next() else {
+                break;
+            };
+            if !self.command(cpu, memory, line?.trim(), out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // runs one command line; false means the user asked to quit
+    pub fn command<W: Write> (&mut self, cpu: &mut CPU, memory: &mut Memory, line: &str, out: &mut W) -> std::io::Result<bool> {
+        let line = match self.expand(line) {
+            Ok(Some(line)) => line,
+            Ok(None) => return Ok(true),
+            Err(e) => {
+                writeln!(out, "{}", e)?;
+                return Ok(true);
+            }
+        };
+
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        match self.dispatch(cpu, memory, &line, out) {
+            Ok(keep_going) => Ok(keep_going),
+            Err(e) => {
+                writeln!(out, "{}", e)?;
+                Ok(true)
+            }
+        }
+    }
+
+    // resolves empty input and ! references to the command to run
+    fn expand (&self, line: &str) -> Result<Option<String>, String> {
+        if line.is_empty() {
+            // repeating a dump continues it instead of printing the same bytes again
+            return Ok(self.history.last().map(|last| match last.split_whitespace().next() {
+                Some(verb @ ("m" | "d")) => verb.to_string(),
+                _ => last.clone(),
+            }));
+        }
+
+        let Some(reference) = line.strip_prefix('!') else {
+            return Ok(Some(line.to_string()));
+        };
+        let entry = if reference == "!" {
+            self.history.last()
+        } else {
+            reference.parse::<usize>().ok().and_then(|n| self.history.get(n.wrapping_sub(1)))
+        };
+        entry.cloned().map(Some).ok_or_else(|| format!("No command {} in history", line))
+    }
+
+    fn dispatch<W: Write> (&mut self, cpu: &mut CPU, memory: &mut Memory, line: &str, out: &mut W) -> CommandResult {
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let args: Vec<&str> = rest.split_whitespace().collect();
+
+        match verb {
+            "n" | "step" => {
+                let count = parse_count(args.first(), 1)?;
+                for _ in 0..count {
+                    if report_illegal(cpu, memory, out)? {
+                        break;
+                    }
+                    if let Some(reason) = cpu.step(memory) {
+                        report_stop(cpu, reason, out)?;
+                        break;
+                    }
+                }
+                show_state(cpu, memory, out)?;
+            }
+            "o" | "over" => {
+                if memory.peek(cpu.pc) == JSR {
+                    let (ret, sp) = (cpu.pc.wrapping_add(3), cpu.sp);
+                    run_until(cpu, memory, out, |cpu, _| cpu.pc == ret && cpu.sp == sp)?;
+                } else {
+                    return self.dispatch(cpu, memory, "n", out);
+                }
+            }
+            "out" | "finish" => {
+                // the RTS that pops this frame leaves SP above where it is now
+                let sp = cpu.sp;
+                run_until(cpu, memory, out, |cpu, opcode| opcode == RTS && cpu.sp > sp)?;
+            }
+            "c" | "continue" => run_until(cpu, memory, out, |_, _| false)?,
+            "u" | "until" => {
+                let addr = parse_address(args.first().ok_or("Usage: u <addr>")?)?;
+                run_until(cpu, memory, out, |cpu, _| cpu.pc == addr)?;
+            }
+            "rs" => {
+                match cpu.step_back(memory) {
+                    Some(entry) => writeln!(out, "Undid {}", entry)?,
+                    None => writeln!(out, "No history to step back through.")?,
+                }
+                show_state(cpu, memory, out)?;
+            }
+            "rc" => {
+                match cpu.reverse_continue(memory) {
+                    Some(reason) => report_stop(cpu, reason, out)?,
+                    None => writeln!(out, "Reached the start of the history")?,
+                }
+                show_state(cpu, memory, out)?;
+            }
+            "h" => {
+                let count = parse_count(args.first(), 10)?;
+                for entry in cpu.debugger.history.iter().take(count) {
+                    writeln!(out, "{}", entry)?;
+                }
+            }
+            "r" | "reg" => {
+                if let [register, value] = args[..] {
+                    set_register(cpu, register, value)?;
+                } else if !args.is_empty() {
+                    return Err("Usage: r [<reg> <value>]".into());
+                }
+                show_state(cpu, memory, out)?;
+            }
+            "m" | "mem" => self.dump(memory, &args, out)?,
+            "e" | "edit" => {
+                let (addr, bytes) = args.split_first().ok_or("Usage: e <addr> <byte>...")?;
+                let addr = parse_address(addr)?;
+                let bytes = bytes.iter().map(|byte| parse_byte(byte)).collect::<Result<Vec<u8>, _>>()?;
+                for (i, byte) in bytes.into_iter().enumerate() {
+                    memory.write(addr.wrapping_add(i as u16), byte);
+                }
+            }
+            "f" | "fill" => {
+                let [start, end, byte] = args[..] else {
+                    return Err("Usage: f <start> <end> <byte>".into());
+                };
+                let (start, end, byte) = (parse_address(start)?, parse_address(end)?, parse_byte(byte)?);
+                if end < start {
+                    return Err("Fill range ends before it starts".into());
+                }
+                for addr in start..=end {
+                    memory.write(addr, byte);
+                }
+            }
+            "d" | "dis" => {
+                let start = match args.first() {
+                    Some(addr) => parse_address(addr)?,
+                    None => self.next_disassemble.unwrap_or(cpu.pc),
+                };
+                let count = parse_count(args.get(1), DISASSEMBLE_COUNT)?;
+                self.next_disassemble = Some(disassemble_lines(cpu, memory, start, count, out)?);
+            }
+            "b" => {
+                let (on, condition) = cpu.debugger.parse_breakpoint(rest)?;
+                let id = cpu.debugger.add_breakpoint(on, condition);
+                writeln!(out, "Breakpoint #{} set", id)?;
+            }
+            "w" | "wr" | "wa" => {
+                let kind = match verb {
+                    "wr" => WatchKind::Read,
+                    "wa" => WatchKind::Access,
+                    _ => WatchKind::Write,
+                };
+                let (start, end, value) = Watchpoint::parse_spec(rest)?;
+                let id = cpu.debugger.add_watchpoint(kind, start, end, value);
+                writeln!(out, "Watchpoint #{} set", id)?;
+            }
+            "bl" => {
+                if cpu.debugger.breakpoints().is_empty() && cpu.debugger.watchpoints().is_empty() {
+                    writeln!(out, "No breakpoints or watchpoints")?;
+                }
+                for breakpoint in cpu.debugger.breakpoints() {
+                    writeln!(out, "{}", breakpoint)?;
+                }
+                for watchpoint in cpu.debugger.watchpoints() {
+                    writeln!(out, "{}", watchpoint)?;
+                }
+            }
+            "bd" | "be" | "bx" | "bi" => manage_breakpoint(cpu, verb, &args, out)?,
+            "mark" => {
+                let name = args.first().ok_or("Usage: mark <name>")?;
+                self.bookmarks.insert(name.to_string(), Snapshot::capture(cpu, memory));
+                writeln!(out, "Marked '{}' at ${:04X}, cycle {}", name, cpu.pc, cpu.cycles)?;
+            }
+            "goto" => {
+                let name = args.first().ok_or("Usage: goto <name>")?;
+                let snapshot = self.bookmarks.get(*name).ok_or(format!("No bookmark '{}'", name))?;
+                snapshot.restore(cpu, memory)?;
+                show_state(cpu, memory, out)?;
+            }
+            "save" => {
+                let path = args.first().ok_or("Usage: save <file>")?;
+                Snapshot::capture(cpu, memory).save(path)?;
+                writeln!(out, "Saved state to {}", path)?;
+            }
+            "load" => {
+                let path = args.first().ok_or("Usage: load <file>")?;
+                Snapshot::load(path)?.restore(cpu, memory)?;
+                show_state(cpu, memory, out)?;
+            }
+            "history" => {
+                for (i, command) in self.history.iter().enumerate() {
+                    writeln!(out, "{:>4}  {}", i + 1, command)?;
+                }
+            }
+            "help" | "?" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" | "s" => return Ok(false),
+            _ => return Err(format!("Unknown command '{}', try help", verb).into()),
+        }
+
+        Ok(true)
+    }
+
+    // m [start [end | +len]], 16 bytes a line
+    fn dump<W: Write> (&mut self, memory: &Memory, args: &[&str], out: &mut W) -> Result<(), Box<dyn Error>> {
+        let start = match args.first() {
+            Some(addr) => parse_address(addr)?,
+            None => self.next_dump,
+        };
+        let end = match args.get(1) {
+            Some(len) if len.starts_with('+') => {
+                let len = parse_address(&len[1..])?;
+                start.saturating_add(len.max(1) - 1)
+            }
+            Some(end) => parse_address(end)?,
+            None => start.saturating_add(DUMP_BYTES - 1),
+        };
+        if end < start {
+            return Err("Dump range ends before it starts".into());
+        }
+
+        dump_memory(memory, start, end, out)?;
+        self.next_dump = end.wrapping_add(1);
+        Ok(())
+    }
+}
+
+// start..=end as hex and ASCII, 16 bytes a line
+pub fn dump_memory<W: Write> (memory: &Memory, start: u16, end: u16, out: &mut W) -> std::io::Result<()> {
+    let mut line_start = start as u32;
+    while line_start <= end as u32 {
+        let line_end = (line_start + 15).min(end as u32);
+        let bytes: Vec<u8> = (line_start..=line_end).map(|addr| memory.peek(addr as u16)).collect();
+
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| if (0x20..0x7F).contains(&byte) { byte as char } else { '.' })
+            .collect();
+        writeln!(out, "${:04X}  {:<47}  |{}|", line_start, hex.join(" "), ascii)?;
+
+        line_start += 16;
+    }
+    Ok(())
+}
+
+fn report_stop<W: Write> (cpu: &CPU, reason: StopReason, out: &mut W) -> std::io::Result<()> {
+    match reason {
+        StopReason::Breakpoint(id) => writeln!(out, "Hit breakpoint #{} at ${:04X}", id, cpu.pc),
+        StopReason::Watchpoint(hit) => writeln!(out, "Hit {}", hit),
+    }
+}
+
+// whether the next opcode is one the CPU can't run, saying so if it is, as run does
+fn report_illegal<W: Write> (cpu: &CPU, memory: &Memory, out: &mut W) -> std::io::Result<bool> {
+    let opcode = memory.peek(cpu.pc);
+    if cpu.implements(opcode) {
+        return Ok(false);
+    }
+    writeln!(out, "{}", RunOutcome::IllegalOpcode { pc: cpu.pc, opcode })?;
+    Ok(true)
+}
+
+// registers, flags and the instruction about to execute, with its source line when known
+fn show_state<W: Write> (cpu: &CPU, memory: &Memory, out: &mut W) -> std::io::Result<()> {
+    let (instruction, _) = disassemble(memory, cpu.pc);
+    writeln!(
+        out,
+        "PC=${:04X} A=${:02X} X=${:02X} Y=${:02X} SP=${:02X} P={} CYC={}  {}",
+        cpu.pc, cpu.a, cpu.x, cpu.y, cpu.sp, flags(cpu.status), cpu.cycles, instruction
+    )?;
+    if let Some(source) = cpu.debugger.source_map.annotate(cpu.pc) {
+        writeln!(out, "    {}", source)?;
+    }
+    Ok(())
+}
+
+/*
+ *  Steps until done(cpu, opcode just executed) holds, a breakpoint or watchpoint stops
+ *  execution, an illegal opcode is next, or RUN_LIMIT instructions have run.
+ */
+fn run_until<W: Write> (cpu: &mut CPU, memory: &mut Memory, out: &mut W, mut done: impl FnMut(&CPU, u8) -> bool) -> std::io::Result<()> {
+    for _ in 0..RUN_LIMIT {
+        let opcode = memory.peek(cpu.pc);
+        if report_illegal(cpu, memory, out)? {
+            return show_state(cpu, memory, out);
+        }
+        if let Some(reason) = cpu.step(memory) {
+            report_stop(cpu, reason, out)?;
+            return show_state(cpu, memory, out);
+        }
+        if done(cpu, opcode) {
+            return show_state(cpu, memory, out);
+        }
+    }
+
+    writeln!(out, "Stopped after {} instructions", RUN_LIMIT)?;
+    show_state(cpu, memory, out)
+}
+
+// returns the address after the last instruction shown
+fn disassemble_lines<W: Write> (cpu: &CPU, memory: &Memory, start: u16, count: usize, out: &mut W) -> std::io::Result<u16> {
+    let mut addr = start;
+    for _ in 0..count {
+        let (text, length) = disassemble(memory, addr);
+        let bytes: Vec<String> = (0..length).map(|i| format!("{:02X}", memory.peek(addr.wrapping_add(i)))).collect();
+        let marker = if addr == cpu.pc { ">" } else { " " };
+        writeln!(out, "{} ${:04X}  {:<8}  {}", marker, addr, bytes.join(" "), text)?;
+        addr = addr.wrapping_add(length);
+    }
+    Ok(addr)
+}
+
+fn manage_breakpoint<W: Write> (cpu: &mut CPU, verb: &str, args: &[&str], out: &mut W) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: bd <id> | be <id> | bx <id> | bi <id> <count>";
+    let id = args
+        .first()
+        .and_then(|id| id.trim_start_matches('#').parse::<usize>().ok())
+        .ok_or(USAGE)?;
+    let debugger = &mut cpu.debugger;
+
+    match verb {
+        "bd" if debugger.remove_breakpoint(id) || debugger.remove_watchpoint(id) => writeln!(out, "Deleted #{}", id)?,
+        "bi" => {
+            let count = args.get(1).and_then(|count| count.parse::<u32>().ok()).ok_or(USAGE)?;
+            let breakpoint = debugger.breakpoint_mut(id).ok_or(format!("No breakpoint #{}", id))?;
+            breakpoint.ignore_count = count;
+            writeln!(out, "Ignoring the next {} hits of breakpoint #{}", count, id)?;
+        }
+        "be" | "bx" => {
+            let enabled = verb == "be";
+            if let Some(breakpoint) = debugger.breakpoint_mut(id) {
+                breakpoint.enabled = enabled;
+            } else if let Some(watchpoint) = debugger.watchpoint_mut(id) {
+                watchpoint.enabled = enabled;
+            } else {
+                return Err(format!("No breakpoint or watchpoint #{}", id).into());
+            }
+        }
+        _ => return Err(format!("No breakpoint or watchpoint #{}", id).into()),
+    }
+    Ok(())
+}
+
+fn set_register (cpu: &mut CPU, register: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    if register.eq_ignore_ascii_case("pc") {
+        cpu.pc = parse_address(value)?;
+        return Ok(());
+    }
+
+    let value = parse_byte(value)?;
+    match register.to_ascii_uppercase().as_str() {
+        "A" => cpu.a = value,
+        "X" => cpu.x = value,
+        "Y" => cpu.y = value,
+        "SP" | "S" => cpu.sp = value,
+        "P" => cpu.status = value,
+        _ => return Err(format!("Unknown register: {}", register).into()),
+    }
+    Ok(())
+}
+
+fn strip_hex_prefix (text: &str) -> &str {
+    text.strip_prefix('$').or_else(|| text.strip_prefix("0x")).unwrap_or(text)
+}
+
+pub fn parse_address (text: &str) -> Result<u16, String> {
+    u16::from_str_radix(strip_hex_prefix(text), 16).map_err(|_| format!("Invalid address: {}", text))
+}
+
+pub fn parse_byte (text: &str) -> Result<u8, String> {
+    u8::from_str_radix(strip_hex_prefix(text), 16).map_err(|_| format!("Invalid byte: {}", text))
+}
+
+// start-end or start+len
+pub fn parse_range (text: &str) -> Result<(u16, u16), String> {
+    let (start, end) = if let Some((start, len)) = text.split_once('+') {
+        let start = parse_address(start)?;
+        (start, start.saturating_add(parse_address(len)?.max(1) - 1))
+    } else if let Some((start, end)) = text.split_once('-') {
+        (parse_address(start)?, parse_address(end)?)
+    } else {
+        return Err(format!("Invalid range: {}", text));
+    };
+
+    if end < start {
+        return Err(format!("Range ends before it starts: {}", text));
+    }
+    Ok((start, end))
+}
+
+fn parse_count (text: Option<&&str>, default: usize) -> Result<usize, String> {
+    text.map_or(Ok(default), |text| text.parse().map_err(|_| format!("Invalid count: {}", text)))
+}
@@ -0,0 +1,245 @@
+use std::io;
+
+/*
+ *  Relocatable object file produced by loader::assemble_object and consumed by the linker.
+ *  Segment contents are position independent: every byte that depends on where a segment
+ *  (or an imported symbol) ends up is listed as a relocation and left as zero in the data.
+ */
+
+const MAGIC: &[u8; 6] = b"O6502\0";
+pub const OBJECT_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    Word,
+    LowByte,
+    HighByte,
+    // branch operand: target - (field address + 1)
+    Relative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocationTarget {
+    // start of a segment in the same object file
+    Segment(String),
+    Import(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub segment: String,
+    pub offset: u16,
+    pub kind: RelocationKind,
+    pub target: RelocationTarget,
+    pub addend: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSegment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub segment: String,
+    pub offset: u16,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectFile {
+    pub segments: Vec<ObjectSegment>,
+    pub exports: Vec<Export>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl RelocationKind {
+    pub fn size (self) -> usize {
+        match self {
+            RelocationKind::Word => 2,
+            RelocationKind::LowByte | RelocationKind::HighByte | RelocationKind::Relative => 1,
+        }
+    }
+
+    fn to_byte (self) -> u8 {
+        match self {
+            RelocationKind::Word => 0,
+            RelocationKind::LowByte => 1,
+            RelocationKind::HighByte => 2,
+            RelocationKind::Relative => 3,
+        }
+    }
+
+    fn from_byte (value: u8) -> Option<Self> {
+        match value {
+            0 => Some(RelocationKind::Word),
+            1 => Some(RelocationKind::LowByte),
+            2 => Some(RelocationKind::HighByte),
+            3 => Some(RelocationKind::Relative),
+            _ => None,
+        }
+    }
+}
+
+impl ObjectFile {
+    pub fn new () -> Self {
+        ObjectFile::default()
+    }
+
+    pub fn segment (&self, name: &str) -> Option<&ObjectSegment> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    /*
+     *  Layout (all integers little endian):
+     *  magic, version,
+     *  u16 count + segments (name, u16 len, data),
+     *  u16 count + exports (name, segment, u16 offset),
+     *  u16 count + imports (name),
+     *  u16 count + relocations (segment, u16 offset, u8 kind, u8 target type, target name, u16 addend)
+     *  names are a u8 length followed by the bytes.
+     */
+    pub fn to_bytes (&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(OBJECT_VERSION);
+
+        write_u16(&mut out, self.segments.len() as u16);
+        for segment in &self.segments {
+            write_name(&mut out, &segment.name);
+            write_u16(&mut out, segment.data.len() as u16);
+            out.extend_from_slice(&segment.data);
+        }
+
+        write_u16(&mut out, self.exports.len() as u16);
+        for export in &self.exports {
+            write_name(&mut out, &export.name);
+            write_name(&mut out, &export.segment);
+            write_u16(&mut out, export.offset);
+        }
+
+        write_u16(&mut out, self.imports.len() as u16);
+        for import in &self.imports {
+            write_name(&mut out, import);
+        }
+
+        write_u16(&mut out, self.relocations.len() as u16);
+        for relocation in &self.relocations {
+            write_name(&mut out, &relocation.segment);
+            write_u16(&mut out, relocation.offset);
+            out.push(relocation.kind.to_byte());
+            match &relocation.target {
+                RelocationTarget::Segment(name) => {
+                    out.push(0);
+                    write_name(&mut out, name);
+                }
+                RelocationTarget::Import(name) => {
+                    out.push(1);
+                    write_name(&mut out, name);
+                }
+            }
+            write_u16(&mut out, relocation.addend);
+        }
+
+        out
+    }
+
+    pub fn from_bytes (bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("Not an object file"));
+        }
+        let version = reader.u8()?;
+        if version != OBJECT_VERSION {
+            return Err(invalid(&format!("Unsupported object file version {}", version)));
+        }
+
+        let mut object = ObjectFile::new();
+
+        for _ in 0..reader.u16()? {
+            let name = reader.name()?;
+            let len = reader.u16()? as usize;
+            let data = reader.take(len)?.to_vec();
+            object.segments.push(ObjectSegment { name, data });
+        }
+
+        for _ in 0..reader.u16()? {
+            let name = reader.name()?;
+            let segment = reader.name()?;
+            let offset = reader.u16()?;
+            object.exports.push(Export { name, segment, offset });
+        }
+
+        for _ in 0..reader.u16()? {
+            object.imports.push(reader.name()?);
+        }
+
+        for _ in 0..reader.u16()? {
+            let segment = reader.name()?;
+            let offset = reader.u16()?;
+            let kind = RelocationKind::from_byte(reader.u8()?)
+                .ok_or_else(|| invalid("Unknown relocation kind"))?;
+            let target = match reader.u8()? {
+                0 => RelocationTarget::Segment(reader.name()?),
+                1 => RelocationTarget::Import(reader.name()?),
+                _ => return Err(invalid("Unknown relocation target")),
+            };
+            let addend = reader.u16()?;
+            object.relocations.push(Relocation { segment, offset, kind, target, addend });
+        }
+
+        if reader.pos != bytes.len() {
+            return Err(invalid("Trailing bytes after object file"));
+        }
+
+        Ok(object)
+    }
+}
+
+fn invalid(message: &str) -> Box<dyn std::error::Error> {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string()).into()
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.push((value & 0xFF) as u8);
+    out.push((value >> 8) as u8);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    out.push(name.len() as u8);
+    out.extend_from_slice(name.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take (&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if self.pos + len > self.bytes.len() {
+            return Err(invalid("Truncated object file"));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8 (&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16 (&mut self) -> Result<u16, Box<dyn std::error::Error>> {
+        let bytes = self.take(2)?;
+        Ok(bytes[0] as u16 | ((bytes[1] as u16) << 8))
+    }
+
+    fn name (&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let len = self.u8()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("Invalid name in object file"))
+    }
+}
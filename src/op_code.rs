@@ -16,7 +16,7 @@ pub enum OpCodeHandler {
     // WithAddr(OpCodeHandlerWithAddr)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Immediate,
@@ -31,81 +31,189 @@ pub enum AddressingMode {
     Implied,
 }
 
-pub const OPCODE_DEFINITIONS: [(u8, OpCodeHandler); 64] = [
+impl AddressingMode {
+    // bytes after the opcode
+    pub fn operand_size (self) -> u16 {
+        match self {
+            AddressingMode::Implied => 0,
+            AddressingMode::Absolute | AddressingMode::Absolute_X | AddressingMode::Absolute_Y => 2,
+            _ => 1,
+        }
+    }
+}
+
+// every documented NMOS 6502 opcode; the undocumented ones are left out
+pub const OPCODE_DEFINITIONS: [(u8, OpCodeHandler); 151] = [
     // -- LDA --
-    (0xA9, OpCodeHandler::WithMem(CPU::lda_immediate)), // Immediate
-    (0xA5, OpCodeHandler::WithMem(CPU::lda_zero_page)), // Zero Page
-    (0xAD, OpCodeHandler::WithMem(CPU::lda_absolute)),  // Absolute
-    // -- STA --
-    (0x85, OpCodeHandler::WithMem(CPU::sta_zero_page)), // Zero Page
-    (0x8D, OpCodeHandler::WithMem(CPU::sta_absolute)),  // Absolute
+    (0xA9, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Immediate))), // Immediate
+    (0xA5, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xB5, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xAD, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Absolute))), // Absolute
+    (0xBD, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0xB9, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0xA1, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0xB1, OpCodeHandler::WithMem(|cpu, memory| cpu.lda(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
     // -- LDX --
-    (0xA2, OpCodeHandler::WithMem(CPU::ldx_immediate)), // Immediate
-    (0xA6, OpCodeHandler::WithMem(CPU::ldx_zero_page)), // Zero Page
-    (0xAE, OpCodeHandler::WithMem(CPU::ldx_absolute)),  // Absolute
-    // -- STX --
-    (0x86, OpCodeHandler::WithMem(CPU::stx_zero_page)), // Zero Page
-    (0x8E, OpCodeHandler::WithMem(CPU::stx_absolute)),  // Absolute
+    (0xA2, OpCodeHandler::WithMem(|cpu, memory| cpu.ldx(memory, AddressingMode::Immediate))), // Immediate
+    (0xA6, OpCodeHandler::WithMem(|cpu, memory| cpu.ldx(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xB6, OpCodeHandler::WithMem(|cpu, memory| cpu.ldx(memory, AddressingMode::ZeroPage_Y))), // Zero Page,Y
+    (0xAE, OpCodeHandler::WithMem(|cpu, memory| cpu.ldx(memory, AddressingMode::Absolute))), // Absolute
+    (0xBE, OpCodeHandler::WithMem(|cpu, memory| cpu.ldx(memory, AddressingMode::Absolute_Y))), // Absolute,Y
     // -- LDY --
-    (0xA0, OpCodeHandler::WithMem(CPU::ldy_immediate)), // Immediate
-    (0xA4, OpCodeHandler::WithMem(CPU::ldy_zero_page)), // Zero Page
-    (0xAC, OpCodeHandler::WithMem(CPU::ldy_absolute)),  // Absolute
+    (0xA0, OpCodeHandler::WithMem(|cpu, memory| cpu.ldy(memory, AddressingMode::Immediate))), // Immediate
+    (0xA4, OpCodeHandler::WithMem(|cpu, memory| cpu.ldy(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xB4, OpCodeHandler::WithMem(|cpu, memory| cpu.ldy(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xAC, OpCodeHandler::WithMem(|cpu, memory| cpu.ldy(memory, AddressingMode::Absolute))), // Absolute
+    (0xBC, OpCodeHandler::WithMem(|cpu, memory| cpu.ldy(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- STA --
+    (0x85, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x95, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x8D, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::Absolute))), // Absolute
+    (0x9D, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0x99, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0x81, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0x91, OpCodeHandler::WithMem(|cpu, memory| cpu.sta(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- STX --
+    (0x86, OpCodeHandler::WithMem(|cpu, memory| cpu.stx(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x96, OpCodeHandler::WithMem(|cpu, memory| cpu.stx(memory, AddressingMode::ZeroPage_Y))), // Zero Page,Y
+    (0x8E, OpCodeHandler::WithMem(|cpu, memory| cpu.stx(memory, AddressingMode::Absolute))), // Absolute
     // -- STY --
-    (0x84, OpCodeHandler::WithMem(CPU::sty_zero_page)), // Zero Page
-    (0x8C, OpCodeHandler::WithMem(CPU::sty_absolute)),  // Absolute
+    (0x84, OpCodeHandler::WithMem(|cpu, memory| cpu.sty(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x94, OpCodeHandler::WithMem(|cpu, memory| cpu.sty(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x8C, OpCodeHandler::WithMem(|cpu, memory| cpu.sty(memory, AddressingMode::Absolute))), // Absolute
+    // -- ADC --
+    (0x69, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Immediate))), // Immediate
+    (0x65, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x75, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x6D, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Absolute))), // Absolute
+    (0x7D, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0x79, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0x61, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0x71, OpCodeHandler::WithMem(|cpu, memory| cpu.adc(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- SBC --
+    (0xE9, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Immediate))), // Immediate
+    (0xE5, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xF5, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xED, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Absolute))), // Absolute
+    (0xFD, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0xF9, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0xE1, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0xF1, OpCodeHandler::WithMem(|cpu, memory| cpu.sbc(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- AND --
+    (0x29, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Immediate))), // Immediate
+    (0x25, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x35, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x2D, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Absolute))), // Absolute
+    (0x3D, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0x39, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0x21, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0x31, OpCodeHandler::WithMem(|cpu, memory| cpu.and(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- ORA --
+    (0x09, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Immediate))), // Immediate
+    (0x05, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x15, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x0D, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Absolute))), // Absolute
+    (0x1D, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0x19, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0x01, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0x11, OpCodeHandler::WithMem(|cpu, memory| cpu.ora(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- EOR --
+    (0x49, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Immediate))), // Immediate
+    (0x45, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x55, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x4D, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Absolute))), // Absolute
+    (0x5D, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0x59, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0x41, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0x51, OpCodeHandler::WithMem(|cpu, memory| cpu.eor(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- CMP --
+    (0xC9, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Immediate))), // Immediate
+    (0xC5, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xD5, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xCD, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Absolute))), // Absolute
+    (0xDD, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Absolute_X))), // Absolute,X
+    (0xD9, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Absolute_Y))), // Absolute,Y
+    (0xC1, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Indirect_X))), // (Indirect,X)
+    (0xD1, OpCodeHandler::WithMem(|cpu, memory| cpu.cmp(memory, AddressingMode::Indirect_Y))), // (Indirect),Y
+    // -- CPX --
+    (0xE0, OpCodeHandler::WithMem(|cpu, memory| cpu.cpx(memory, AddressingMode::Immediate))), // Immediate
+    (0xE4, OpCodeHandler::WithMem(|cpu, memory| cpu.cpx(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xEC, OpCodeHandler::WithMem(|cpu, memory| cpu.cpx(memory, AddressingMode::Absolute))), // Absolute
+    // -- CPY --
+    (0xC0, OpCodeHandler::WithMem(|cpu, memory| cpu.cpy(memory, AddressingMode::Immediate))), // Immediate
+    (0xC4, OpCodeHandler::WithMem(|cpu, memory| cpu.cpy(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xCC, OpCodeHandler::WithMem(|cpu, memory| cpu.cpy(memory, AddressingMode::Absolute))), // Absolute
+    // -- BIT --
+    (0x24, OpCodeHandler::WithMem(|cpu, memory| cpu.bit(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x2C, OpCodeHandler::WithMem(|cpu, memory| cpu.bit(memory, AddressingMode::Absolute))), // Absolute
+    // -- ASL --
+    (0x06, OpCodeHandler::WithMem(|cpu, memory| cpu.asl(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x16, OpCodeHandler::WithMem(|cpu, memory| cpu.asl(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x0E, OpCodeHandler::WithMem(|cpu, memory| cpu.asl(memory, AddressingMode::Absolute))), // Absolute
+    (0x1E, OpCodeHandler::WithMem(|cpu, memory| cpu.asl(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- LSR --
+    (0x46, OpCodeHandler::WithMem(|cpu, memory| cpu.lsr(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x56, OpCodeHandler::WithMem(|cpu, memory| cpu.lsr(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x4E, OpCodeHandler::WithMem(|cpu, memory| cpu.lsr(memory, AddressingMode::Absolute))), // Absolute
+    (0x5E, OpCodeHandler::WithMem(|cpu, memory| cpu.lsr(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- ROL --
+    (0x26, OpCodeHandler::WithMem(|cpu, memory| cpu.rol(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x36, OpCodeHandler::WithMem(|cpu, memory| cpu.rol(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x2E, OpCodeHandler::WithMem(|cpu, memory| cpu.rol(memory, AddressingMode::Absolute))), // Absolute
+    (0x3E, OpCodeHandler::WithMem(|cpu, memory| cpu.rol(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- ROR --
+    (0x66, OpCodeHandler::WithMem(|cpu, memory| cpu.ror(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0x76, OpCodeHandler::WithMem(|cpu, memory| cpu.ror(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0x6E, OpCodeHandler::WithMem(|cpu, memory| cpu.ror(memory, AddressingMode::Absolute))), // Absolute
+    (0x7E, OpCodeHandler::WithMem(|cpu, memory| cpu.ror(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- INC --
+    (0xE6, OpCodeHandler::WithMem(|cpu, memory| cpu.inc(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xF6, OpCodeHandler::WithMem(|cpu, memory| cpu.inc(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xEE, OpCodeHandler::WithMem(|cpu, memory| cpu.inc(memory, AddressingMode::Absolute))), // Absolute
+    (0xFE, OpCodeHandler::WithMem(|cpu, memory| cpu.inc(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- DEC --
+    (0xC6, OpCodeHandler::WithMem(|cpu, memory| cpu.dec(memory, AddressingMode::ZeroPage))), // Zero Page
+    (0xD6, OpCodeHandler::WithMem(|cpu, memory| cpu.dec(memory, AddressingMode::ZeroPage_X))), // Zero Page,X
+    (0xCE, OpCodeHandler::WithMem(|cpu, memory| cpu.dec(memory, AddressingMode::Absolute))), // Absolute
+    (0xDE, OpCodeHandler::WithMem(|cpu, memory| cpu.dec(memory, AddressingMode::Absolute_X))), // Absolute,X
+    // -- Accumulator Shifts --
+    (0x0A, OpCodeHandler::NoMem(CPU::asl_accumulator)), // ASL A
+    (0x4A, OpCodeHandler::NoMem(CPU::lsr_accumulator)), // LSR A
+    (0x2A, OpCodeHandler::NoMem(CPU::rol_accumulator)), // ROL A
+    (0x6A, OpCodeHandler::NoMem(CPU::ror_accumulator)), // ROR A
+    // -- Register Increments --
+    (0xE8, OpCodeHandler::NoMem(CPU::inx)), // INX
+    (0xC8, OpCodeHandler::NoMem(CPU::iny)), // INY
+    (0xCA, OpCodeHandler::NoMem(CPU::dex)), // DEX
+    (0x88, OpCodeHandler::NoMem(CPU::dey)), // DEY
     // -- Transfer Instructions --
-    (0xAA, OpCodeHandler::NoMem(CPU::tax)),            // Implied
-    (0xA8, OpCodeHandler::NoMem(CPU::tay)),            // Implied
-    (0xBA, OpCodeHandler::NoMem(CPU::tsx)),            // Implied
-    (0x8A, OpCodeHandler::NoMem(CPU::txa)),            // Implied
-    (0x9A, OpCodeHandler::NoMem(CPU::txs)),            // Implied
-    (0x98, OpCodeHandler::NoMem(CPU::tya)),            // Implied
+    (0xAA, OpCodeHandler::NoMem(CPU::tax)), // TAX
+    (0xA8, OpCodeHandler::NoMem(CPU::tay)), // TAY
+    (0xBA, OpCodeHandler::NoMem(CPU::tsx)), // TSX
+    (0x8A, OpCodeHandler::NoMem(CPU::txa)), // TXA
+    (0x9A, OpCodeHandler::NoMem(CPU::txs)), // TXS
+    (0x98, OpCodeHandler::NoMem(CPU::tya)), // TYA
+    // -- Flag Instructions --
+    (0x18, OpCodeHandler::NoMem(CPU::clc)), // CLC
+    (0xD8, OpCodeHandler::NoMem(CPU::cld)), // CLD
+    (0x58, OpCodeHandler::NoMem(CPU::cli)), // CLI
+    (0xB8, OpCodeHandler::NoMem(CPU::clv)), // CLV
+    (0x38, OpCodeHandler::NoMem(CPU::sec)), // SEC
+    (0xF8, OpCodeHandler::NoMem(CPU::sed)), // SED
+    (0x78, OpCodeHandler::NoMem(CPU::sei)), // SEI
     // -- Jump Instructions --
     (0x4C, OpCodeHandler::WithMem(CPU::jmp_absolute)), // Absolute
     (0x6C, OpCodeHandler::WithMem(CPU::jmp_indirect)), // Indirect
-    (0x20, OpCodeHandler::WithMem(CPU::jsr)),          // Absolute
-    // -- CMP --
-    (0xCD, OpCodeHandler::WithMem(CPU::cmp_immediate)),          // Immediate
-    (0xC5, OpCodeHandler::WithMem(CPU::cmp_zero_page)),          // Zero Page
-    (0xC9, OpCodeHandler::WithMem(CPU::cmp_absolute)),          // Absolute
-    // -- CPX --
-    (0xE0, OpCodeHandler::WithMem(CPU::cpx_immediate)),          // Immediate
-    (0xE4, OpCodeHandler::WithMem(CPU::cpx_zero_page)),          // Zero Page
-    (0xEC, OpCodeHandler::WithMem(CPU::cpx_absolute)),          // Absolute
-    // -- CPY --
-    (0xC0, OpCodeHandler::WithMem(CPU::cpy_immediate)),          // Immediate
-    (0xC4, OpCodeHandler::WithMem(CPU::cpy_zero_page)),          // Zero Page
-    (0xCC, OpCodeHandler::WithMem(CPU::cpy_absolute)),          // Absolute
-    // -- Clear Flag Instructions --
-    (0x18, OpCodeHandler::NoMem(CPU::clc)),            // Implied
-    (0xD8, OpCodeHandler::NoMem(CPU::cld)),            // Implied
-    (0x58, OpCodeHandler::NoMem(CPU::cli)),            // Implied
-    (0xB8, OpCodeHandler::NoMem(CPU::cly)),            // Implied
-    // -- Bitwise Operations --
-    // * AND *
-    (0x29, OpCodeHandler::WithMem(CPU::and_immediate)), // Absolute
-    (0x25, OpCodeHandler::WithMem(CPU::and_zero_page)), // Indirect
-    (0x2D, OpCodeHandler::WithMem(CPU::and_absolute)),          // Absolute
-    // * ORA *
-    (0x09, OpCodeHandler::WithMem(CPU::ora_immediate)), // Absolute
-    (0x05, OpCodeHandler::WithMem(CPU::ora_zero_page)), // Indirect
-    (0x0D, OpCodeHandler::WithMem(CPU::ora_absolute)),          // Absolute
-    // * EOR *
-    (0x49, OpCodeHandler::WithMem(CPU::eor_immediate)), // Absolute
-    (0x45, OpCodeHandler::WithMem(CPU::eor_zero_page)), // Indirect
-    (0x4D, OpCodeHandler::WithMem(CPU::eor_absolute)),          // Absolute
-    // * BIT *
-    (0x24, OpCodeHandler::WithMem(CPU::bit_zero_page)), // Indirect
-    (0x2C, OpCodeHandler::WithMem(CPU::bit_absolute)),          // Absolute
+    (0x20, OpCodeHandler::WithMem(CPU::jsr)), // JSR Absolute
     // -- Branch Instructions --
-    (0x90, OpCodeHandler::WithMem(CPU::bcc)), // Implied
-    (0xB0, OpCodeHandler::WithMem(CPU::bcs)), // Implied
-    (0xF0, OpCodeHandler::WithMem(CPU::beq)), // Implied
-    (0x30, OpCodeHandler::WithMem(CPU::bmi)), // Implied
-    (0xD0, OpCodeHandler::WithMem(CPU::bne)), // Implied
-    (0x10, OpCodeHandler::WithMem(CPU::bpl)), // Implied
-    (0x50, OpCodeHandler::WithMem(CPU::bvc)), // Implied
-    (0x70, OpCodeHandler::WithMem(CPU::bvs)), // Implied
+    (0x90, OpCodeHandler::WithMem(CPU::bcc)), // BCC
+    (0xB0, OpCodeHandler::WithMem(CPU::bcs)), // BCS
+    (0xF0, OpCodeHandler::WithMem(CPU::beq)), // BEQ
+    (0x30, OpCodeHandler::WithMem(CPU::bmi)), // BMI
+    (0xD0, OpCodeHandler::WithMem(CPU::bne)), // BNE
+    (0x10, OpCodeHandler::WithMem(CPU::bpl)), // BPL
+    (0x50, OpCodeHandler::WithMem(CPU::bvc)), // BVC
+    (0x70, OpCodeHandler::WithMem(CPU::bvs)), // BVS
     // -- Stack Instructions --
     (0x08, OpCodeHandler::WithMem(CPU::php)), // PHP - Push Processor Status
     (0x28, OpCodeHandler::WithMem(CPU::plp)), // PLP - Pull Processor Status
@@ -113,27 +221,40 @@ pub const OPCODE_DEFINITIONS: [(u8, OpCodeHandler); 64] = [
     (0x68, OpCodeHandler::WithMem(CPU::pla)), // PLA - Pull Accumulator
     (0x60, OpCodeHandler::WithMem(CPU::rts)), // RTS - Return from Subroutine
     (0x40, OpCodeHandler::WithMem(CPU::rti)), // RTI - Return from Interrupt
-    // -- ADC (Add with Carry) --
-    (0x69, OpCodeHandler::WithMem(CPU::adc)), // Immediate
-// (0x65, OpCodeHandler::WithMem(CPU::adc)), // Zero Page
-// (0x75, OpCodeHandler::WithMem(CPU::adc)), // Zero Page,X
-// (0x6D, OpCodeHandler::WithMem(CPU::adc)), // Absolute
-// (0x7D, OpCodeHandler::WithMem(CPU::adc)), // Absolute,X
-// (0x79, OpCodeHandler::WithMem(CPU::adc)), // Absolute,Y
-// (0x61, OpCodeHandler::WithMem(CPU::adc)), // (Indirect,X)
-// (0x71, OpCodeHandler::WithMem(CPU::adc)), // (Indirect),Y
+    (0x00, OpCodeHandler::WithMem(CPU::brk)), // BRK - Force Interrupt
+    // -- No Operation --
+    (0xEA, OpCodeHandler::NoMem(CPU::nop)), // NOP
+];
 
-// -- SBC (Subtract with Carry) --
-    (0xE9, OpCodeHandler::WithMem(CPU::sbc)), // Immediate
-    //(0xE5, OpCodeHandler::WithMem(CPU::sbc)), // // Zero Page
-    // (0xF5, OpCodeHandler::WithMem(CPU::sbc)), // Zero Page,X
-    // (0xED, OpCodeHandler::WithMem(CPU::sbc)), // Absolute
-    // (0xFD, OpCodeHandler::WithMem(CPU::sbc)), // Absolute,X
-    // (0xF9, OpCodeHandler::WithMem(CPU::sbc)), // Absolute,Y
-    // (0xE1, OpCodeHandler::WithMem(CPU::sbc)), // (Indirect,X)
-    // (0xF1, OpCodeHandler::WithMem(CPU::sbc)), // (Indirect),
+/*
+ *  Base cycle count for every NMOS 6502 opcode, undocumented ones included. The CPU adds the
+ *  rest: +1 for a taken branch and +1 more when it lands on another page, and +1 when a read
+ *  indexes across a page.
+ */
+pub const CYCLES: [u8; 256] = [
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 0x00
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x10
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 0x20
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x30
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 0x40
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x50
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 0x60
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x70
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0x80
+    2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 0x90
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0xA0
+    2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // 0xB0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xC0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xD0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xE0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xF0
 ];
 
+// conditional branches all have the low five bits 1_0000
+pub fn is_branch (opcode: u8) -> bool {
+    opcode & 0x1F == 0x10
+}
+
 #[derive(Clone, Default)]
 pub struct OpcodeEntry {
     pub handler: Option<OpCodeHandler>,
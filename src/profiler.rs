@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::debugger::MinCPU;
+use crate::loader::disassemble;
+use crate::symbols::SymbolTable;
+use crate::Memory;
+
+/*
+ *  Counts executions and cycles for every address, and follows the call stack to charge
+ *  cycles to subroutines. A JSR enters its target and an interrupt enters its handler. An
+ *  RTS or RTI leaves every frame whose stack pointer it has climbed past, which keeps the
+ *  stack right when code pops a return address to bail out early. Exclusive cycles are
+ *  spent in a subroutine's own instructions. Inclusive cycles also count its callees, once
+ *  however deeply it recurses. The first address profiled is the root of every stack.
+ */
+
+const JSR: u8 = 0x20;
+const RTS: u8 = 0x60;
+const RTI: u8 = 0x40;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+pub struct Profiler {
+    counts: Vec<u64>,
+    cycles: Vec<u64>,
+    // the entry address of each subroutine on the call stack, and the stack pointer just inside it
+    path: Vec<u16>,
+    stack: Vec<u8>,
+    functions: BTreeMap<u16, FunctionStats>,
+    stacks: HashMap<Vec<u16>, u64>,
+    instructions: u64,
+    interrupts: u64,
+    total_cycles: u64,
+}
+
+impl Default for Profiler {
+    fn default () -> Self {
+        Profiler::new()
+    }
+}
+
+impl Profiler {
+    pub fn new () -> Self {
+        Profiler {
+            counts: vec![0; 0x10000],
+            cycles: vec![0; 0x10000],
+            path: Vec::new(),
+            stack: Vec::new(),
+            functions: BTreeMap::new(),
+            stacks: HashMap::new(),
+            instructions: 0,
+            interrupts: 0,
+            total_cycles: 0,
+        }
+    }
+
+    // one CPU step: the instruction at pc ran, or an interrupt was taken before it; after is the CPU once it's done
+    pub fn record (&mut self, pc: u16, opcode: u8, interrupted: bool, cycles: u64, after: &MinCPU) {
+        if self.stack.is_empty() {
+            // the root never returns
+            self.enter(pc, u8::MAX);
+        }
+        if interrupted {
+            self.interrupts += 1;
+            self.enter(after.pc, after.sp);
+            self.charge(cycles);
+            return;
+        }
+
+        self.instructions += 1;
+        self.counts[pc as usize] += 1;
+        self.cycles[pc as usize] += cycles;
+        self.charge(cycles);
+        match opcode {
+            JSR => self.enter(after.pc, after.sp),
+            RTS | RTI => {
+                while self.stack.len() > 1 && self.stack.last().is_some_and(|&sp| sp < after.sp) {
+                    self.stack.pop();
+                    self.path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn enter (&mut self, entry: u16, sp: u8) {
+        self.stack.push(sp);
+        self.path.push(entry);
+        self.functions.entry(entry).or_default().calls += 1;
+    }
+
+    fn charge (&mut self, cycles: u64) {
+        self.total_cycles += cycles;
+        for (i, &entry) in self.path.iter().enumerate() {
+            let stats = self.functions.entry(entry).or_default();
+            if i + 1 == self.path.len() {
+                stats.exclusive += cycles;
+            }
+            if !self.path[..i].contains(&entry) {
+                stats.inclusive += cycles;
+            }
+        }
+        match self.stacks.get_mut(&self.path) {
+            Some(total) => *total += cycles,
+            None => {
+                self.stacks.insert(self.path.clone(), cycles);
+            }
+        }
+    }
+
+    pub fn instructions (&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn total_cycles (&self) -> u64 {
+        self.total_cycles
+    }
+
+    // executions and cycles of the instruction at addr
+    pub fn at (&self, addr: u16) -> (u64, u64) {
+        (self.counts[addr as usize], self.cycles[addr as usize])
+    }
+
+    // subroutines by entry address, the root included
+    pub fn functions (&self) -> &BTreeMap<u16, FunctionStats> {
+        &self.functions
+    }
+
+    // addresses that ran, most cycles first
+    pub fn hot_spots (&self) -> Vec<u16> {
+        let mut addrs: Vec<u16> = (0..=0xFFFF).filter(|&addr| self.counts[addr as usize] > 0).collect();
+        addrs.sort_by_key(|&addr| std::cmp::Reverse(self.cycles[addr as usize]));
+        addrs
+    }
+
+    /*
+     *  The hottest limit instructions, with their disassembly and nearest label, then the
+     *  subroutines by inclusive cycles:
+     *
+     *      Hot spots:
+     *          cycles      %      count  address  instruction   label
+     *            1200  40.0%        400  $0612    DEX           loop+2
+     */
+    pub fn write_report<W: Write> (&self, memory: &Memory, symbols: &SymbolTable, limit: usize, out: &mut W) -> io::Result<()> {
+        let percent = |cycles: u64| 100.0 * cycles as f64 / self.total_cycles.max(1) as f64;
+        writeln!(
+            out,
+            "{} instructions, {} cycles, {} interrupts",
+            self.instructions, self.total_cycles, self.interrupts
+        )?;
+
+        writeln!(out, "\nHot spots:")?;
+        writeln!(out, "{:>10} {:>6} {:>10}  address  {:<12}  label", "cycles", "%", "count", "instruction")?;
+        for addr in self.hot_spots().into_iter().take(limit) {
+            let (count, cycles) = self.at(addr);
+            let (text, _) = disassemble(memory, addr);
+            let label = match symbols.nearest(addr) {
+                Some((name, 0)) => name.to_string(),
+                Some((name, offset)) => format!("{}+{}", name, offset),
+                None => String::new(),
+            };
+            writeln!(out, "{:>10} {:>5.1}% {:>10}  ${:04X}    {:<12}  {}", cycles, percent(cycles), count, addr, text, label)?;
+        }
+
+        let mut functions: Vec<(&u16, &FunctionStats)> = self.functions.iter().collect();
+        functions.sort_by_key(|&(&entry, stats)| (std::cmp::Reverse(stats.inclusive), entry));
+        writeln!(out, "\nSubroutines:")?;
+        writeln!(out, "{:>10} {:>6} {:>10} {:>6} {:>8}  subroutine", "inclusive", "%", "exclusive", "%", "calls")?;
+        for (&entry, stats) in functions.into_iter().take(limit) {
+            writeln!(
+                out,
+                "{:>10} {:>5.1}% {:>10} {:>5.1}% {:>8}  {}",
+                stats.inclusive,
+                percent(stats.inclusive),
+                stats.exclusive,
+                percent(stats.exclusive),
+                stats.calls,
+                function_name(symbols, entry)
+            )?;
+        }
+        Ok(())
+    }
+
+    /*
+     *  Exclusive cycles by call stack, one "root;caller;callee cycles" line each, which is
+     *  what flamegraph.pl and speedscope read.
+     */
+    pub fn collapsed_stacks (&self, symbols: &SymbolTable) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(path, cycles)| {
+                let names: Vec<String> = path.iter().map(|&entry| function_name(symbols, entry)).collect();
+                format!("{} {}", names.join(";"), cycles)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+// a subroutine's label, or its address
+fn function_name (symbols: &SymbolTable, entry: u16) -> String {
+    symbols.name_at(entry).map_or_else(|| format!("${:04X}", entry), str::to_string)
+}
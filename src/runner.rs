@@ -0,0 +1,187 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use crate::cpu::CPU;
+use crate::debugger::{MinCPU, StopReason, WatchHit, WatchKind};
+use crate::image_loader::load_file;
+use crate::loader::assemble_file;
+use crate::Memory;
+
+/*
+ *  Headless execution for `run`: loads a program, runs it until a stop condition holds, and
+ *  reports how it ended. Breakpoints, watchpoints and illegal opcodes always stop a run; the
+ *  rest are opt-in through RunOptions. The outcome maps onto the process exit status so
+ *  scripts and CI can tell the cases apart. An exit status is a byte, so the emulator keeps
+ *  the top three values for itself, the way ssh keeps 255, and a program's own status passes
+ *  through unless it would land on one of them:
+ *      0-252  the status the program wrote to the exit port (253-255 are reported as 252),
+ *             or 0 for a stop the user asked for
+ *      253    an illegal opcode
+ *      254    a cycle or instruction budget ran out
+ *      255    an error, such as a file that doesn't assemble
+ *  An illegal opcode is one of the 105 the NMOS 6502 doesn't document; the CPU runs all the
+ *  others.
+ */
+
+pub const EXIT_ERROR: i32 = 255;
+pub const EXIT_LIMIT: i32 = 254;
+pub const EXIT_ILLEGAL_OPCODE: i32 = 253;
+// the highest status a program can exit with
+pub const EXIT_PROGRAM_MAX: i32 = 252;
+
+const BRK: u8 = 0x00;
+
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub max_cycles: Option<u64>,
+    pub max_instructions: Option<u64>,
+    pub stop_at: Option<u16>,
+    // stop on reaching a BRK, before it pushes anything
+    pub stop_on_brk: bool,
+    // stop on an instruction that leaves PC where it was, like JMP * or a branch to itself
+    pub stop_on_self_jump: bool,
+    // a write here ends the run, with the value written as the exit status
+    pub exit_port: Option<u16>,
+    // write a trace_line for every instruction before it executes
+    pub trace: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Breakpoint { id: usize, pc: u16 },
+    Watchpoint(WatchHit),
+    Brk { pc: u16 },
+    JumpToSelf { pc: u16 },
+    ReachedPc(u16),
+    Exit(u8),
+    IllegalOpcode { pc: u16, opcode: u8 },
+    CycleLimit(u64),
+    InstructionLimit(u64),
+}
+
+impl RunOutcome {
+    // stopping where the user asked to is success, running out of budget or into garbage isn't
+    pub fn exit_code (&self) -> i32 {
+        match self {
+            RunOutcome::Exit(value) => (*value as i32).min(EXIT_PROGRAM_MAX),
+            RunOutcome::IllegalOpcode { .. } => EXIT_ILLEGAL_OPCODE,
+            RunOutcome::CycleLimit(_) | RunOutcome::InstructionLimit(_) => EXIT_LIMIT,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for RunOutcome {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunOutcome::Breakpoint { id, pc } => write!(f, "Hit breakpoint #{} at ${:04X}", id, pc),
+            RunOutcome::Watchpoint(hit) => write!(f, "Hit {}", hit),
+            RunOutcome::Brk { pc } => write!(f, "BRK at ${:04X}", pc),
+            RunOutcome::JumpToSelf { pc } => write!(f, "Jump to self at ${:04X}", pc),
+            RunOutcome::ReachedPc(pc) => write!(f, "Reached ${:04X}", pc),
+            RunOutcome::Exit(value) => write!(f, "Exited with ${:02X}", value),
+            RunOutcome::IllegalOpcode { pc, opcode } => write!(f, "Illegal opcode ${:02X} at ${:04X}", opcode, pc),
+            RunOutcome::CycleLimit(cycles) => write!(f, "Cycle limit reached after {} cycles", cycles),
+            RunOutcome::InstructionLimit(count) => write!(f, "Instruction limit reached after {} instructions", count),
+        }
+    }
+}
+
+/*
+ *  .asm sources are assembled in memory and start at their origin; anything else goes
+ *  through the image loaders, with raw binaries placed at load_addr.
+ */
+pub fn load_program_file (cpu: &mut CPU, memory: &mut Memory, path: &str, load_addr: u16) -> Result<(), Box<dyn std::error::Error>> {
+    if Path::new(path).extension().is_some_and(|ext| ext == "asm") {
+        let assembly = assemble_file(path.to_string())?;
+        for block in assembly.blocks() {
+            for (i, &byte) in block.data.iter().enumerate() {
+                memory.write(block.addr.wrapping_add(i as u16), byte);
+            }
+        }
+        cpu.pc = assembly.origin;
+        cpu.debugger.source_map = assembly.source_map;
+        cpu.debugger.symbols = assembly.symbols;
+    } else {
+        load_file(cpu, memory, path, None, load_addr)?;
+    }
+    Ok(())
+}
+
+/*
+ *  The exit port is watched with an ordinary write watchpoint, added for the length of the
+ *  run and told apart from the user's by its id.
+ */
+pub fn run<W: Write> (cpu: &mut CPU, memory: &mut Memory, options: &RunOptions, trace: &mut W) -> std::io::Result<RunOutcome> {
+    let mut instructions = 0;
+    run_counting(cpu, memory, options, trace, &mut instructions)
+}
+
+// run, adding the instructions it executes to instructions; max_instructions counts this run only
+pub(crate) fn run_counting<W: Write> (
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    options: &RunOptions,
+    trace: &mut W,
+    instructions: &mut u64,
+) -> std::io::Result<RunOutcome> {
+    let exit_watch = options
+        .exit_port
+        .map(|port| cpu.debugger.add_watchpoint(WatchKind::Write, port, port, None));
+
+    let outcome = run_until_stopped(cpu, memory, options, exit_watch, trace, instructions);
+
+    if let Some(id) = exit_watch {
+        cpu.debugger.remove_watchpoint(id);
+    }
+    outcome
+}
+
+fn run_until_stopped<W: Write> (
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    options: &RunOptions,
+    exit_watch: Option<usize>,
+    trace: &mut W,
+    instructions: &mut u64,
+) -> std::io::Result<RunOutcome> {
+    let start = *instructions;
+
+    loop {
+        if options.max_cycles.is_some_and(|max| cpu.cycles >= max) {
+            return Ok(RunOutcome::CycleLimit(cpu.cycles));
+        }
+        if options.max_instructions.is_some_and(|max| *instructions - start >= max) {
+            return Ok(RunOutcome::InstructionLimit(*instructions - start));
+        }
+
+        let pc = cpu.pc;
+        let opcode = memory.peek(pc);
+        if !cpu.implements(opcode) {
+            return Ok(RunOutcome::IllegalOpcode { pc, opcode });
+        }
+        if options.stop_on_brk && opcode == BRK {
+            return Ok(RunOutcome::Brk { pc });
+        }
+        if options.trace {
+            writeln!(trace, "{}", cpu.debugger.trace_line(&MinCPU::from_cpu(cpu), memory))?;
+        }
+
+        let stop = cpu.step(memory);
+        *instructions += 1;
+        match stop {
+            Some(StopReason::Watchpoint(hit)) if Some(hit.id) == exit_watch => return Ok(RunOutcome::Exit(hit.new)),
+            Some(StopReason::Watchpoint(hit)) => return Ok(RunOutcome::Watchpoint(hit)),
+            Some(StopReason::Breakpoint(id)) => return Ok(RunOutcome::Breakpoint { id, pc: cpu.pc }),
+            None => {}
+        }
+
+        if options.stop_on_self_jump && cpu.pc == pc {
+            return Ok(RunOutcome::JumpToSelf { pc });
+        }
+        if options.stop_at == Some(cpu.pc) {
+            return Ok(RunOutcome::ReachedPc(cpu.pc));
+        }
+    }
+}
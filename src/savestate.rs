@@ -0,0 +1,217 @@
+use std::fs;
+
+use crate::cpu::CPU;
+use crate::Memory;
+
+/*
+ *  Save states: the CPU, all 64K of memory and the state of attached devices, kept in memory
+ *  as a Snapshot or written to a versioned binary file. A snapshot only restores onto a
+ *  machine with the same devices attached, and leaves the machine untouched when it can't be
+ *  restored. Breakpoints and other debugger settings aren't
+ *  part of a snapshot; the execution history is cleared on restore since it no longer
+ *  matches.
+ *
+ *  File layout, little endian:
+ *      "6502SNAP"  version: u16
+ *      A X Y SP P: u8  PC: u16  cycles: u64  interrupts: u8 (bit 0 IRQ, bit 1 NMI)
+ *      memory length: u32, memory
+ *      device count: u16, then per device name length: u8, name, data length: u32, data
+ */
+
+const MAGIC: &[u8; 8] = b"6502SNAP";
+pub const SAVESTATE_VERSION: u16 = 1;
+
+const IRQ_BIT: u8 = 0b01;
+const NMI_BIT: u8 = 0b10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuState {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub status: u8,
+    pub pc: u16,
+    pub cycles: u64,
+    pub irq_pending: bool,
+    pub nmi_pending: bool,
+}
+
+// a device's own encoding of its registers and internal state, keyed by its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceState {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub cpu: CpuState,
+    pub memory: Vec<u8>,
+    pub devices: Vec<DeviceState>,
+}
+
+fn invalid (message: &str) -> Box<dyn std::error::Error> {
+    message.into()
+}
+
+impl Snapshot {
+    pub fn capture (cpu: &CPU, memory: &Memory) -> Self {
+        Snapshot {
+            cpu: CpuState {
+                a: cpu.a,
+                x: cpu.x,
+                y: cpu.y,
+                sp: cpu.sp,
+                status: cpu.status,
+                pc: cpu.pc,
+                cycles: cpu.cycles,
+                irq_pending: cpu.irq_pending,
+                nmi_pending: cpu.nmi_pending,
+            },
+            memory: memory.contents().to_vec(),
+            devices: memory
+                .devices()
+                .map(|device| DeviceState { name: device.name().to_string(), data: device.save_state() })
+                .collect(),
+        }
+    }
+
+    pub fn restore (&self, cpu: &mut CPU, memory: &mut Memory) -> Result<(), Box<dyn std::error::Error>> {
+        // the same kinds of device in the same order, checked before anything changes
+        let attached: Vec<&str> = memory.devices().map(|device| device.name()).collect();
+        let saved: Vec<&str> = self.devices.iter().map(|device| device.name.as_str()).collect();
+        if attached != saved {
+            return Err(format!("Snapshot has devices [{}] but the machine has [{}]", saved.join(", "), attached.join(", ")).into());
+        }
+
+        // devices can reject their data, so they go first and are put back if one does
+        let current: Vec<Vec<u8>> = memory.devices().map(|device| device.save_state()).collect();
+        for (index, state) in self.devices.iter().enumerate() {
+            let result = memory.devices_mut().nth(index).expect("device counts match").load_state(&state.data);
+            if let Err(e) = result {
+                for (device, data) in memory.devices_mut().zip(&current).take(index) {
+                    device.load_state(data)?;
+                }
+                return Err(format!("Can't restore device {}: {}", state.name, e).into());
+            }
+        }
+
+        let state = &self.cpu;
+        cpu.a = state.a;
+        cpu.x = state.x;
+        cpu.y = state.y;
+        cpu.sp = state.sp;
+        cpu.status = state.status;
+        cpu.pc = state.pc;
+        cpu.cycles = state.cycles;
+        cpu.irq_pending = state.irq_pending;
+        cpu.nmi_pending = state.nmi_pending;
+        cpu.debugger.history.clear();
+
+        memory.set_contents(&self.memory);
+        Ok(())
+    }
+
+    pub fn to_bytes (&self) -> Vec<u8> {
+        let state = &self.cpu;
+        let mut out = Vec::with_capacity(self.memory.len() + 64);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&SAVESTATE_VERSION.to_le_bytes());
+
+        out.extend_from_slice(&[state.a, state.x, state.y, state.sp, state.status]);
+        out.extend_from_slice(&state.pc.to_le_bytes());
+        out.extend_from_slice(&state.cycles.to_le_bytes());
+        let interrupts = if state.irq_pending { IRQ_BIT } else { 0 } | if state.nmi_pending { NMI_BIT } else { 0 };
+        out.push(interrupts);
+
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+
+        out.extend_from_slice(&(self.devices.len() as u16).to_le_bytes());
+        for device in &self.devices {
+            out.push(device.name.len() as u8);
+            out.extend_from_slice(device.name.as_bytes());
+            out.extend_from_slice(&(device.data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&device.data);
+        }
+        out
+    }
+
+    pub fn from_bytes (bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("Not a save state"));
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != SAVESTATE_VERSION {
+            return Err(format!("Unsupported save state version {}", version).into());
+        }
+
+        let [a, x, y, sp, status] = reader.array()?;
+        let pc = u16::from_le_bytes(reader.array()?);
+        let cycles = u64::from_le_bytes(reader.array()?);
+        let [interrupts] = reader.array()?;
+        let cpu = CpuState {
+            a,
+            x,
+            y,
+            sp,
+            status,
+            pc,
+            cycles,
+            irq_pending: interrupts & IRQ_BIT != 0,
+            nmi_pending: interrupts & NMI_BIT != 0,
+        };
+
+        let length = u32::from_le_bytes(reader.array()?) as usize;
+        if length != 0x10000 {
+            return Err(invalid("Save state memory isn't 64K"));
+        }
+        let memory = reader.take(length)?.to_vec();
+
+        let mut devices = Vec::new();
+        for _ in 0..u16::from_le_bytes(reader.array()?) {
+            let [name_length] = reader.array()?;
+            let name = String::from_utf8(reader.take(name_length as usize)?.to_vec())
+                .map_err(|_| invalid("Invalid device name in save state"))?;
+            let length = u32::from_le_bytes(reader.array()?) as usize;
+            devices.push(DeviceState { name, data: reader.take(length)?.to_vec() });
+        }
+
+        if reader.pos != bytes.len() {
+            return Err(invalid("Trailing data after save state"));
+        }
+        Ok(Snapshot { cpu, memory, devices })
+    }
+
+    pub fn save (&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load (path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take (&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if self.pos + len > self.bytes.len() {
+            return Err(invalid("Truncated save state"));
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn array<const N: usize> (&mut self) -> Result<[u8; N], Box<dyn std::error::Error>> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+}
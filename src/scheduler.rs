@@ -0,0 +1,206 @@
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cpu::CPU;
+use crate::runner::{self, RunOptions, RunOutcome};
+use crate::Memory;
+
+/*
+ *  Real-time pacing for the run loop. The CPU runs in frame-sized slices of cycles and the
+ *  scheduler sleeps out the rest of each frame, so a 1 MHz clock at 60 fps runs 16,667
+ *  cycles a frame. An unlimited clock never sleeps; its slices grow or shrink to fill about
+ *  one frame of real time so front ends still redraw at their frame rate.
+ */
+
+pub const DEFAULT_FPS: u32 = 60;
+// how often the achieved speed is recomputed
+const MEASURE_WINDOW: Duration = Duration::from_millis(500);
+const MIN_UNLIMITED_SLICE: u64 = 1_000;
+
+// hz, khz or mhz with an optional fraction ("1.79mhz"), plain Hz ("1000000") or "unlimited"
+pub fn parse_clock (text: &str) -> Result<Option<u64>, String> {
+    let lower = text.trim().to_ascii_lowercase();
+    if lower == "unlimited" || lower == "max" {
+        return Ok(None);
+    }
+
+    let (number, scale) = if let Some(number) = lower.strip_suffix("mhz") {
+        (number, 1_000_000.0)
+    } else if let Some(number) = lower.strip_suffix("khz") {
+        (number, 1_000.0)
+    } else {
+        (lower.strip_suffix("hz").unwrap_or(&lower), 1.0)
+    };
+
+    let hz = number.trim().parse::<f64>().map_err(|_| format!("Invalid clock rate: {}", text))? * scale;
+    if hz < 1.0 {
+        return Err(format!("Clock rate must be at least 1 Hz: {}", text));
+    }
+    Ok(Some(hz.round() as u64))
+}
+
+pub struct Scheduler {
+    // None runs as fast as the host allows
+    clock_hz: Option<u64>,
+    frame: Duration,
+    speed: f64,
+    paused: bool,
+    next_frame: Instant,
+    unlimited_slice: u64,
+    // achieved speed: cycles run since window_start, and the last measurement
+    window_start: Instant,
+    window_cycles: u64,
+    mhz: f64,
+    // the whole run, for average_mhz; last_frame is when the previous frame ended
+    total_cycles: u64,
+    total_time: Duration,
+    last_frame: Instant,
+}
+
+impl Scheduler {
+    pub fn new (clock_hz: Option<u64>, fps: u32) -> Self {
+        let now = Instant::now();
+        Scheduler {
+            clock_hz,
+            frame: Duration::from_secs(1) / fps.max(1),
+            speed: 1.0,
+            paused: false,
+            next_frame: now,
+            unlimited_slice: 100_000,
+            window_start: now,
+            window_cycles: 0,
+            mhz: 0.0,
+            total_cycles: 0,
+            total_time: Duration::ZERO,
+            last_frame: now,
+        }
+    }
+
+    pub fn clock_hz (&self) -> Option<u64> {
+        self.clock_hz
+    }
+
+    // cycles to run this frame: none while paused, the clock's share of a second otherwise
+    pub fn frame_cycles (&self) -> u64 {
+        if self.paused {
+            return 0;
+        }
+        match self.clock_hz {
+            Some(hz) => ((hz as f64 * self.speed * self.frame.as_secs_f64()).round() as u64).max(1),
+            None => self.unlimited_slice,
+        }
+    }
+
+    /*
+     *  Records the cycles the frame ran, then waits until the next frame is due. A frame that
+     *  overran resets the schedule rather than rushing the following ones to catch up.
+     */
+    pub fn end_frame (&mut self, cycles: u64, started: Instant) {
+        self.window_cycles += cycles;
+        self.total_cycles += cycles;
+        let now = Instant::now();
+        if !self.paused {
+            self.total_time += now - self.last_frame;
+        }
+        self.last_frame = now;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= MEASURE_WINDOW {
+            self.mhz = self.window_cycles as f64 / elapsed.as_secs_f64() / 1_000_000.0;
+            self.window_start = Instant::now();
+            self.window_cycles = 0;
+        }
+
+        if self.clock_hz.is_none() && !self.paused {
+            // aim each unlimited slice at one frame of real time
+            let took = started.elapsed();
+            if took < self.frame / 2 {
+                self.unlimited_slice = self.unlimited_slice.saturating_mul(2);
+            } else if took > self.frame * 2 {
+                self.unlimited_slice = (self.unlimited_slice / 2).max(MIN_UNLIMITED_SLICE);
+            }
+            self.next_frame = Instant::now();
+        } else {
+            self.next_frame += self.frame;
+            let now = Instant::now();
+            if self.next_frame > now {
+                thread::sleep(self.next_frame - now);
+            } else {
+                self.next_frame = now;
+            }
+        }
+    }
+
+    // measured over the last half second or so
+    pub fn mhz (&self) -> f64 {
+        self.mhz
+    }
+
+    // over the whole run, leaving out time spent paused
+    pub fn average_mhz (&self) -> f64 {
+        let seconds = self.total_time.as_secs_f64();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.total_cycles as f64 / seconds / 1_000_000.0
+        }
+    }
+
+    pub fn pause (&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume (&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused (&self) -> bool {
+        self.paused
+    }
+
+    // fast-forward multiplier on the clock rate; 1.0 is real time
+    pub fn set_speed (&mut self, speed: f64) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed (&self) -> f64 {
+        self.speed
+    }
+}
+
+/*
+ *  Runs until one of options' stop conditions holds, a frame at a time under the scheduler.
+ *  The cycle and instruction budgets cover the whole run, not each frame. on_frame runs after
+ *  each frame, for drawing and input, and returning false ends the run with None.
+ */
+pub fn run_scheduled<W: Write> (
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    options: &RunOptions,
+    scheduler: &mut Scheduler,
+    trace: &mut W,
+    mut on_frame: impl FnMut(&mut CPU, &mut Memory, &mut Scheduler) -> io::Result<bool>,
+) -> io::Result<Option<RunOutcome>> {
+    let budget = options.max_cycles.unwrap_or(u64::MAX);
+    let mut slice = options.clone();
+    let mut instructions = 0;
+
+    loop {
+        let started = Instant::now();
+        let start_cycles = cpu.cycles;
+        slice.max_cycles = Some(cpu.cycles.saturating_add(scheduler.frame_cycles()).min(budget));
+        slice.max_instructions = options.max_instructions.map(|max| max - instructions);
+        let outcome = runner::run_counting(cpu, memory, &slice, trace, &mut instructions)?;
+        scheduler.end_frame(cpu.cycles - start_cycles, started);
+
+        if !on_frame(cpu, memory, scheduler)? {
+            return Ok(None);
+        }
+        match outcome {
+            RunOutcome::CycleLimit(cycles) if cycles < budget => {}
+            RunOutcome::InstructionLimit(_) => return Ok(Some(RunOutcome::InstructionLimit(instructions))),
+            outcome => return Ok(Some(outcome)),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::terminal::spawn_key_reader;
+
+/*
+ *  Host ends for an emulated serial port: stdin and stdout, a pseudo-terminal for screen or
+ *  minicom to open, or a TCP socket on localhost for nc or telnet. Each gives a writer for
+ *  what the machine transmits and a channel of the bytes it receives, which is what Acia
+ *  and Console take.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialBackend {
+    Stdio,
+    Pty,
+    Tcp(u16),
+}
+
+impl SerialBackend {
+    // "stdio", "pty" or "tcp:<port>"
+    pub fn parse (text: &str) -> Result<Self, String> {
+        match text {
+            "stdio" => Ok(SerialBackend::Stdio),
+            "pty" => Ok(SerialBackend::Pty),
+            _ => text
+                .strip_prefix("tcp:")
+                .and_then(|port| port.parse().ok())
+                .map(SerialBackend::Tcp)
+                .ok_or(format!("Unknown serial port '{}', expected stdio, pty or tcp:<port>", text)),
+        }
+    }
+}
+
+pub struct SerialPort {
+    pub output: Box<dyn Write>,
+    pub input: Receiver<u8>,
+    // where to connect: a device path or an address, empty for stdio
+    pub location: String,
+}
+
+// a TCP port waits here for its first client, so nothing the machine prints early is lost
+pub fn open (backend: SerialBackend) -> io::Result<SerialPort> {
+    match backend {
+        SerialBackend::Stdio => Ok(SerialPort { output: Box::new(io::stdout()), input: spawn_key_reader(), location: String::new() }),
+        SerialBackend::Pty => open_pty(),
+        SerialBackend::Tcp(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("Waiting for a serial connection on {}", listener.local_addr()?);
+            open_tcp(listener)
+        }
+    }
+}
+
+// bytes from source as they arrive, until it closes
+fn forward_bytes (source: impl Read + Send + 'static) -> Receiver<u8> {
+    let (bytes, received) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in BufReader::new(source).bytes() {
+            let Ok(byte) = byte else { break };
+            if bytes.send(byte).is_err() {
+                break;
+            }
+        }
+    });
+    received
+}
+
+// whichever client is connected; output is dropped while there isn't one
+#[derive(Clone)]
+struct Client(Arc<Mutex<Option<TcpStream>>>);
+
+impl Write for Client {
+    fn write (&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let mut client = self.0.lock().unwrap();
+        if let Some(stream) = client.as_mut() {
+            if stream.write_all(bytes).is_err() {
+                *client = None;
+            }
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush (&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/*
+ *  Accepts one client before returning, then serves one at a time: when a client hangs up
+ *  the next to connect takes its place.
+ */
+pub fn open_tcp (listener: TcpListener) -> io::Result<SerialPort> {
+    let location = listener.local_addr()?.to_string();
+    let (first, _) = listener.accept()?;
+    let client = Client(Arc::new(Mutex::new(Some(first.try_clone()?))));
+    let (bytes, input) = mpsc::channel();
+
+    let connected = client.clone();
+    thread::spawn(move || {
+        let mut stream = Some(first);
+        loop {
+            let current = match stream.take() {
+                Some(current) => current,
+                None => {
+                    let Ok((next, _)) = listener.accept() else { break };
+                    let Ok(writer) = next.try_clone() else { continue };
+                    *connected.0.lock().unwrap() = Some(writer);
+                    next
+                }
+            };
+
+            for byte in BufReader::new(&current).bytes() {
+                let Ok(byte) = byte else { break };
+                if bytes.send(byte).is_err() {
+                    return;
+                }
+            }
+            *connected.0.lock().unwrap() = None;
+        }
+    });
+
+    Ok(SerialPort { output: Box::new(client), input, location })
+}
+
+#[cfg(unix)]
+fn open_pty () -> io::Result<SerialPort> {
+    let (master, slave) = pty::open()?;
+    // the machine's output would otherwise echo straight back to it until a terminal program sets the port up
+    let status = std::process::Command::new("stty").args(["raw", "-echo"]).stdin(slave.file.try_clone()?).status()?;
+    if !status.success() {
+        return Err(io::Error::other("stty failed on the new pseudo-terminal"));
+    }
+    eprintln!("Serial port on {}", slave.path);
+
+    let reader = master.try_clone()?;
+    let location = slave.path.clone();
+    // reads fail once no process has the terminal open, so keep the slave end open too
+    let input = forward_bytes(KeepOpen { reader, _slave: slave.file });
+    Ok(SerialPort { output: Box::new(master), input, location })
+}
+
+#[cfg(not(unix))]
+fn open_pty () -> io::Result<SerialPort> {
+    Err(io::Error::other("Pseudo-terminals need a Unix host"))
+}
+
+#[cfg(unix)]
+struct KeepOpen {
+    reader: std::fs::File,
+    _slave: std::fs::File,
+}
+
+#[cfg(unix)]
+impl Read for KeepOpen {
+    fn read (&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::fd::FromRawFd;
+    use std::os::raw::{c_char, c_int};
+
+    extern "C" {
+        fn posix_openpt (flags: c_int) -> c_int;
+        fn grantpt (fd: c_int) -> c_int;
+        fn unlockpt (fd: c_int) -> c_int;
+        fn ptsname (fd: c_int) -> *mut c_char;
+    }
+
+    const O_RDWR: c_int = 2;
+
+    pub struct Slave {
+        pub path: String,
+        pub file: File,
+    }
+
+    pub fn open () -> io::Result<(File, Slave)> {
+        // SAFETY: plain libc calls on a descriptor we own; ptsname's result is copied out before any other call
+        unsafe {
+            let fd = posix_openpt(O_RDWR);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if grantpt(fd) != 0 || unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let name = ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let path = CStr::from_ptr(name).to_string_lossy().into_owned();
+            let file = OpenOptions::new().read(true).write(true).open(&path)?;
+            Ok((master, Slave { path, file }))
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/*
+ *  Address <-> source line debug info from the assembler. Only instructions are mapped, so
+ *  every address in the map is somewhere execution can stop.
+ *
+ *  Text form, one instruction per line:
+ *      $0600 program.asm:3
+ */
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+// file:line, splitting on the last colon so Windows drive letters survive
+pub fn parse_location (text: &str) -> Option<(&str, usize)> {
+    let (file, line) = text.trim().rsplit_once(':')?;
+    let line = line.parse::<usize>().ok()?;
+    (!file.is_empty()).then_some((file, line))
+}
+
+// the same path, or at least the same file name when one side is relative
+fn same_file (stored: &str, requested: &str) -> bool {
+    stored == requested || Path::new(stored).ends_with(requested) || Path::new(requested).ends_with(stored)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locations: BTreeMap<u16, SourceLocation>,
+    addresses: BTreeMap<SourceLocation, Vec<u16>>,
+    // source text per file, for showing the current line
+    sources: HashMap<String, Vec<String>>,
+}
+
+impl SourceMap {
+    pub fn new () -> Self {
+        Self::default()
+    }
+
+    pub fn insert (&mut self, addr: u16, file: &str, line: usize) {
+        let location = SourceLocation { file: file.to_string(), line };
+        if let Some(previous) = self.locations.insert(addr, location.clone()) {
+            if let Some(addresses) = self.addresses.get_mut(&previous) {
+                addresses.retain(|&mapped| mapped != addr);
+            }
+        }
+        self.addresses.entry(location).or_default().push(addr);
+    }
+
+    pub fn add_source (&mut self, file: &str, text: &str) {
+        self.sources.insert(file.to_string(), text.lines().map(str::to_string).collect());
+    }
+
+    pub fn location (&self, addr: u16) -> Option<&SourceLocation> {
+        self.locations.get(&addr)
+    }
+
+    // every address a line produced code at
+    pub fn addresses (&self, file: &str, line: usize) -> Vec<u16> {
+        self.addresses
+            .iter()
+            .filter(|(location, _)| location.line == line && same_file(&location.file, file))
+            .flat_map(|(_, addresses)| addresses.iter().copied())
+            .collect()
+    }
+
+    /*
+     *  Where a breakpoint on file:line lands: the first line at or after it that has code,
+     *  as (that line, its first address).
+     */
+    pub fn resolve (&self, file: &str, line: usize) -> Option<(usize, u16)> {
+        self.addresses
+            .iter()
+            .filter(|(location, addresses)| location.line >= line && !addresses.is_empty() && same_file(&location.file, file))
+            .min_by_key(|(location, _)| location.line)
+            .map(|(location, addresses)| (location.line, addresses[0]))
+    }
+
+    pub fn line_text (&self, location: &SourceLocation) -> Option<&str> {
+        self.sources.get(&location.file)?.get(location.line.checked_sub(1)?).map(String::as_str)
+    }
+
+    // "file:line: source" for the instruction at addr
+    pub fn annotate (&self, addr: u16) -> Option<String> {
+        let location = self.location(addr)?;
+        Some(match self.line_text(location) {
+            Some(text) => format!("{}: {}", location, text.trim()),
+            None => location.to_string(),
+        })
+    }
+
+    pub fn len (&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn iter (&self) -> impl Iterator<Item = (u16, &SourceLocation)> {
+        self.locations.iter().map(|(&addr, location)| (addr, location))
+    }
+
+    pub fn to_text (&self) -> String {
+        self.iter().map(|(addr, location)| format!("${:04X} {}\n", addr, location)).collect()
+    }
+
+    pub fn parse_text (text: &str) -> Result<Self, String> {
+        let mut map = SourceMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid debug info on line {}: {}", number + 1, line);
+
+            let (addr, location) = line.split_once(' ').ok_or_else(invalid)?;
+            let addr = u16::from_str_radix(addr.trim_start_matches('$'), 16).map_err(|_| invalid())?;
+            let (file, source_line) = parse_location(location).ok_or_else(invalid)?;
+            map.insert(addr, file, source_line);
+        }
+        Ok(map)
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use std::io;
+
+/*
+ *  Label -> address map produced by the assembler.
+ *  Reads and writes VICE label files (the format ld65 emits with -Ln), one symbol per line:
+ *      al C:0600 .start
+ */
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn new () -> Self {
+        SymbolTable {
+            symbols: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert (&mut self, name: &str, addr: u16) {
+        self.symbols.insert(name.to_string(), addr);
+    }
+
+    pub fn get (&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn contains (&self, name: &str) -> bool {
+        self.symbols.contains_key(name)
+    }
+
+    // first label (alphabetically) defined at addr
+    pub fn name_at (&self, addr: u16) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|(_, &value)| value == addr)
+            .map(|(name, _)| name.as_str())
+    }
+
+    // the closest label at or before addr, and how far past it addr is
+    pub fn nearest (&self, addr: u16) -> Option<(&str, u16)> {
+        self.by_address()
+            .into_iter()
+            .filter(|&(_, value)| value <= addr)
+            .max_by_key(|&(name, value)| (value, std::cmp::Reverse(name)))
+            .map(|(name, value)| (name, addr - value))
+    }
+
+    pub fn len (&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter (&self) -> impl Iterator<Item = (&str, u16)> {
+        self.symbols.iter().map(|(name, &addr)| (name.as_str(), addr))
+    }
+
+    // symbols ordered by address, then by name
+    pub fn by_address (&self) -> Vec<(&str, u16)> {
+        let mut entries: Vec<(&str, u16)> = self.iter().collect();
+        entries.sort_by_key(|&(name, addr)| (addr, name));
+        entries
+    }
+
+    pub fn to_vice_labels (&self) -> String {
+        let mut out = String::new();
+        for (name, addr) in self.by_address() {
+            out.push_str(&format!("al C:{:04X} .{}\n", addr, name));
+        }
+        out
+    }
+
+    // accepts both "al C:0600 .start" (VICE) and "al 000600 .start" (ld65)
+    pub fn parse_vice_labels (text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut table = SymbolTable::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 3 || parts[0] != "al" {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid label file entry on line {}: {}", index + 1, line),
+                )
+                .into());
+            }
+
+            let addr_str = parts[1].trim_start_matches("C:");
+            let addr = u32::from_str_radix(addr_str, 16)
+                .ok()
+                .filter(|&addr| addr <= 0xFFFF)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid address on line {}: {}", index + 1, parts[1]),
+                    )
+                })?;
+
+            table.insert(parts[2].trim_start_matches('.'), addr as u16);
+        }
+
+        Ok(table)
+    }
+}
@@ -0,0 +1,191 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::cpu::CPU;
+use crate::framebuffer::{Framebuffer, Rgb};
+use crate::machines::easy6502;
+use crate::runner::{RunOptions, RunOutcome};
+use crate::scheduler::{run_scheduled, Scheduler};
+use crate::Memory;
+
+/*
+ *  Terminal front ends. play draws the easy6502 screen: each character cell shows two pixels
+ *  with an upper half block, the foreground the top pixel and the background the bottom one,
+ *  in 24-bit ANSI color, and keys go straight into the key address as they arrive. interact
+ *  is for machines that print their own output, like the Apple I's terminal.
+ */
+
+pub const DEFAULT_CLOCK_HZ: u64 = 1_000_000;
+pub use crate::scheduler::DEFAULT_FPS;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_F: u8 = 0x06;
+const CTRL_P: u8 = 0x10;
+const FAST_FORWARD: [f64; 4] = [1.0, 2.0, 4.0, 8.0];
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+// frames end lines with \r\n so they also draw correctly in raw mode
+pub fn render_half_blocks (framebuffer: &Framebuffer) -> String {
+    let mut out = String::new();
+
+    for y in (0..framebuffer.height).step_by(2) {
+        let mut current: Option<(Rgb, Rgb)> = None;
+        for x in 0..framebuffer.width {
+            let top = framebuffer.pixel(x, y);
+            let bottom = if y + 1 < framebuffer.height { framebuffer.pixel(x, y + 1) } else { [0, 0, 0] };
+
+            if current != Some((top, bottom)) {
+                out.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                    top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                ));
+                current = Some((top, bottom));
+            }
+            out.push(UPPER_HALF_BLOCK);
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+    out
+}
+
+// puts the terminal in raw mode through stty, and puts it back when dropped
+pub struct RawMode {
+    saved: String,
+}
+
+fn stty (args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed; is stdin a terminal?"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl RawMode {
+    pub fn enable () -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop (&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// stdin bytes as they arrive, read on their own thread so the frame loop never blocks
+pub fn spawn_key_reader () -> Receiver<u8> {
+    let (keys, received) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            let Ok(byte) = byte else { break };
+            if keys.send(byte).is_err() {
+                break;
+            }
+        }
+    });
+    received
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayOptions {
+    // None runs unthrottled
+    pub clock_hz: Option<u64>,
+    pub fps: u32,
+    pub run: RunOptions,
+}
+
+impl Default for PlayOptions {
+    fn default () -> Self {
+        PlayOptions { clock_hz: Some(DEFAULT_CLOCK_HZ), fps: DEFAULT_FPS, run: RunOptions::default() }
+    }
+}
+
+/*
+ *  Runs an easy6502 program in real time, drawing the screen every frame, until a stop
+ *  condition in options.run ends it (returned) or Ctrl-C is pressed (None). Ctrl-P pauses
+ *  and resumes, and Ctrl-F steps through the fast-forward speeds.
+ */
+pub fn play (cpu: &mut CPU, memory: &mut Memory, options: &PlayOptions) -> io::Result<Option<RunOutcome>> {
+    let _raw = RawMode::enable()?;
+    let keys = spawn_key_reader();
+    let mut out = io::stdout().lock();
+    // alternate screen, hidden cursor
+    write!(out, "\x1b[?1049h\x1b[?25l")?;
+
+    let mut scheduler = Scheduler::new(options.clock_hz, options.fps);
+    let result = run_scheduled(cpu, memory, &options.run, &mut scheduler, &mut io::sink(), |cpu, memory, scheduler| {
+        for key in keys.try_iter() {
+            match key {
+                CTRL_C => return Ok(false),
+                CTRL_P if scheduler.is_paused() => scheduler.resume(),
+                CTRL_P => scheduler.pause(),
+                CTRL_F => {
+                    let next = FAST_FORWARD.iter().position(|&speed| speed == scheduler.speed()).map_or(0, |i| i + 1);
+                    scheduler.set_speed(FAST_FORWARD[next % FAST_FORWARD.len()]);
+                }
+                key => easy6502::press_key(memory, key),
+            }
+        }
+
+        write!(out, "\x1b[H{}", render_half_blocks(&easy6502::framebuffer(memory)))?;
+        write!(out, "PC=${:04X} {:.2} MHz x{}", cpu.pc, scheduler.mhz(), scheduler.speed())?;
+        if scheduler.is_paused() {
+            write!(out, " PAUSED")?;
+        }
+        write!(out, "  ^P pause ^F speed ^C quit\x1b[K")?;
+        out.flush()?;
+        Ok(true)
+    });
+
+    write!(out, "\x1b[0m\x1b[?25h\x1b[?1049l")?;
+    out.flush()?;
+    result
+}
+
+/*
+ *  Runs in real time with the terminal in raw mode, handing each key to on_key, until a stop
+ *  condition in options.run ends it (returned) or Ctrl-C is pressed (None). Whatever the
+ *  machine prints goes to the terminal through its own devices; draw runs after every frame
+ *  for anything shown in place.
+ */
+pub fn interact (
+    cpu: &mut CPU,
+    memory: &mut Memory,
+    options: &PlayOptions,
+    mut on_key: impl FnMut(&mut CPU, &mut Memory, u8),
+    mut draw: impl FnMut(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<Option<RunOutcome>> {
+    let _raw = RawMode::enable()?;
+    let keys = spawn_key_reader();
+    let mut out = io::stdout();
+
+    let mut scheduler = Scheduler::new(options.clock_hz, options.fps);
+    run_scheduled(cpu, memory, &options.run, &mut scheduler, &mut io::sink(), |cpu, memory, _| {
+        for key in keys.try_iter() {
+            if key == CTRL_C {
+                return Ok(false);
+            }
+            on_key(cpu, memory, key);
+        }
+        draw(&mut out)?;
+        out.flush()?;
+        Ok(true)
+    })
+}
+
+// a character display in a frame, dark text on a green backlight, with lines ending \r\n
+pub fn render_text_panel (lines: &[String]) -> String {
+    let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let border = "\u{2500}".repeat(width + 2);
+    let mut out = format!("\u{250C}{}\u{2510}\r\n", border);
+    for line in lines {
+        out.push_str(&format!("\u{2502}\x1b[30;42m {:<width$} \x1b[0m\u{2502}\r\n", line, width = width));
+    }
+    out.push_str(&format!("\u{2514}{}\u{2518}\r\n", border));
+    out
+}
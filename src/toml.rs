@@ -0,0 +1,366 @@
+/*
+ *  The subset of TOML that machine descriptions need: key = value pairs, [tables] and
+ *  [[arrays of tables]], dotted and quoted keys, strings (basic and literal), integers
+ *  (decimal, 0x, 0o and 0b, with underscores), booleans, arrays, which may span lines, and
+ *  inline tables. Floats, dates and multi-line strings aren't supported. Tables keep their
+ *  keys in file order.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Toml {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Toml>),
+    Table(Vec<(String, Toml)>),
+}
+
+impl Toml {
+    // a whole document, as its root table
+    pub fn parse (text: &str) -> Result<Toml, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        parser.document().map_err(|message| format!("line {}: {}", parser.line(), message))
+    }
+
+    pub fn get (&self, key: &str) -> Option<&Toml> {
+        self.as_table().and_then(|entries| entries.iter().find(|(name, _)| name == key).map(|(_, value)| value))
+    }
+
+    pub fn as_str (&self) -> Option<&str> {
+        match self {
+            Toml::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64 (&self) -> Option<i64> {
+        match self {
+            Toml::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool (&self) -> Option<bool> {
+        match self {
+            Toml::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array (&self) -> Option<&[Toml]> {
+        match self {
+            Toml::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_table (&self) -> Option<&[(String, Toml)]> {
+        match self {
+            Toml::Table(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    // for error messages
+    pub fn type_name (&self) -> &'static str {
+        match self {
+            Toml::String(_) => "a string",
+            Toml::Integer(_) => "an integer",
+            Toml::Bool(_) => "a boolean",
+            Toml::Array(_) => "an array",
+            Toml::Table(_) => "a table",
+        }
+    }
+}
+
+type Entries = Vec<(String, Toml)>;
+
+// the table at path, made as needed; an array of tables stands for its last table
+fn table_at<'a> (mut table: &'a mut Entries, path: &[String]) -> Result<&'a mut Entries, String> {
+    for key in path {
+        let index = match table.iter().position(|(name, _)| name == key) {
+            Some(index) => index,
+            None => {
+                table.push((key.clone(), Toml::Table(Vec::new())));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            Toml::Table(entries) => entries,
+            Toml::Array(items) => match items.last_mut() {
+                Some(Toml::Table(entries)) => entries,
+                _ => return Err(format!("'{}' is an array, not a table", key)),
+            },
+            _ => return Err(format!("'{}' is already a value, not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+fn insert (table: &mut Entries, path: &[String], value: Toml) -> Result<(), String> {
+    let (key, parents) = path.split_last().expect("keys have at least one part");
+    let table = table_at(table, parents)?;
+    if table.iter().any(|(name, _)| name == key) {
+        return Err(format!("Duplicate key '{}'", path.join(".")));
+    }
+    table.push((key.clone(), value));
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn line (&self) -> usize {
+        self.chars[..self.pos.min(self.chars.len())].iter().filter(|&&c| c == '\n').count() + 1
+    }
+
+    fn peek (&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    // spaces and tabs, not new lines
+    fn skip_blanks (&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    // blanks, new lines and comments, as between array items and statements
+    fn skip_lines (&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => self.pos += 1,
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment (&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+
+    fn expect (&mut self, c: char) -> Result<(), String> {
+        self.skip_blanks();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}'", c))
+        }
+    }
+
+    // nothing but a comment may follow a statement on its line
+    fn end_of_line (&mut self) -> Result<(), String> {
+        self.skip_blanks();
+        match self.peek() {
+            Some('#') => self.skip_comment(),
+            Some('\r' | '\n') | None => {}
+            Some(c) => return Err(format!("Unexpected '{}' after the value", c)),
+        }
+        Ok(())
+    }
+
+    fn document (&mut self) -> Result<Toml, String> {
+        let mut root = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        loop {
+            self.skip_lines();
+            match self.peek() {
+                None => return Ok(Toml::Table(root)),
+                Some('[') => {
+                    self.pos += 1;
+                    let array = self.peek() == Some('[');
+                    if array {
+                        self.pos += 1;
+                    }
+                    current = self.key()?;
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                        let (key, parents) = current.split_last().expect("keys have at least one part");
+                        let table = table_at(&mut root, parents)?;
+                        match table.iter_mut().find(|(name, _)| name == key) {
+                            Some((_, Toml::Array(items))) => items.push(Toml::Table(Vec::new())),
+                            Some(_) => return Err(format!("'{}' is already a value, not an array of tables", current.join("."))),
+                            None => table.push((key.clone(), Toml::Array(vec![Toml::Table(Vec::new())]))),
+                        }
+                    } else {
+                        table_at(&mut root, &current)?;
+                    }
+                }
+                Some(_) => {
+                    let key = self.key()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    let path: Vec<String> = current.iter().chain(&key).cloned().collect();
+                    insert(&mut root, &path, value)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    // bare or quoted parts, joined by dots
+    fn key (&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_blanks();
+            let part = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err("Expected a key".to_string());
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            parts.push(part);
+            self.skip_blanks();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn value (&mut self) -> Result<Toml, String> {
+        self.skip_blanks();
+        match self.peek() {
+            Some('"') => Ok(Toml::String(self.basic_string()?)),
+            Some('\'') => Ok(Toml::String(self.literal_string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_lines();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Toml::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_lines();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {}
+                        _ => return Err("Expected ',' or ']' in an array".to_string()),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.skip_blanks();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Toml::Table(entries));
+                }
+                loop {
+                    let key = self.key()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    insert(&mut entries, &key, value)?;
+                    self.skip_blanks();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Toml::Table(entries));
+                        }
+                        _ => return Err("Expected ',' or '}' in an inline table".to_string()),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_alphanumeric() || c == '+' || c == '-' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '.')) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Toml::Bool(true)),
+                    "false" => Ok(Toml::Bool(false)),
+                    _ => integer(&word).map(Toml::Integer).ok_or(format!("Invalid value: {}", word)),
+                }
+            }
+            _ => Err("Expected a value".to_string()),
+        }
+    }
+
+    fn basic_string (&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let c = self.peek().filter(|&c| c != '\n').ok_or("Unterminated string")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escape = self.peek().ok_or("Unterminated string")?;
+                    self.pos += 1;
+                    match escape {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        '"' | '\\' => text.push(escape),
+                        'u' | 'U' => {
+                            let len = if escape == 'u' { 4 } else { 8 };
+                            let digits: String = self.chars.get(self.pos..self.pos + len).ok_or("Bad escape")?.iter().collect();
+                            self.pos += len;
+                            let code = u32::from_str_radix(&digits, 16).map_err(|_| "Bad escape")?;
+                            text.push(char::from_u32(code).ok_or("Bad escape")?);
+                        }
+                        other => return Err(format!("Unknown escape '\\{}'", other)),
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    // single quotes, no escapes
+    fn literal_string (&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != '\'' && c != '\n') {
+            self.pos += 1;
+        }
+        if self.peek() != Some('\'') {
+            return Err("Unterminated string".to_string());
+        }
+        self.pos += 1;
+        Ok(self.chars[start..self.pos - 1].iter().collect())
+    }
+}
+
+// decimal, or 0x, 0o and 0b; underscores may separate digits
+fn integer (word: &str) -> Option<i64> {
+    let digits = word.replace('_', "");
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(rest) => (true, rest.to_string()),
+        None => (false, digits.strip_prefix('+').unwrap_or(&digits).to_string()),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    if digits.is_empty() || digits.starts_with(['+', '-']) || word.starts_with('_') || word.ends_with('_') {
+        return None;
+    }
+    let value = i64::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
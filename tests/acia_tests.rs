@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+
+use rust_6502_emulator::devices::acia::{self, Acia, STATUS_IRQ, STATUS_RDRF, STATUS_TDRE};
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::serial::{self, SerialBackend};
+
+// what the ACIA transmitted, readable from the test
+#[derive(Clone, Default)]
+struct Line(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Line {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn received_bytes_set_rdrf_and_interrupt_when_enabled() {
+    let (sender, input) = mpsc::channel();
+    let mut acia = Acia::new(Line::default(), input);
+    assert_eq!(acia.read(acia::STATUS), STATUS_TDRE);
+
+    // DTR on, receive interrupts enabled
+    acia.write(acia::COMMAND, 0x09);
+    sender.send(b'A').unwrap();
+    sender.send(b'B').unwrap();
+    acia.tick(2);
+    assert!(acia.irq());
+    assert_eq!(acia.read(acia::STATUS), STATUS_IRQ | STATUS_RDRF | STATUS_TDRE);
+    assert!(!acia.irq());
+
+    // the second byte waits until the first has been read
+    acia.tick(2);
+    assert_eq!(acia.read(acia::DATA), b'A');
+    assert_eq!(acia.peek(acia::STATUS), STATUS_TDRE);
+    acia.tick(2);
+    assert_eq!(acia.read(acia::DATA), b'B');
+
+    // with receive interrupts disabled, bytes still arrive but the line stays quiet
+    acia.write(acia::COMMAND, 0x0B);
+    acia.read(acia::STATUS);
+    sender.send(b'C').unwrap();
+    acia.tick(2);
+    assert!(!acia.irq());
+    assert_eq!(acia.peek(acia::STATUS) & STATUS_RDRF, STATUS_RDRF);
+}
+
+#[test]
+fn transmitting_echo_mode_and_programmed_reset() {
+    let (sender, input) = mpsc::channel();
+    let line = Line::default();
+    let mut acia = Acia::new(line.clone(), input);
+
+    acia.write(acia::CONTROL, 0x1F);
+    // DTR on, receive interrupts off, echo
+    acia.write(acia::COMMAND, 0x13);
+    acia.write(acia::DATA, b'>');
+    sender.send(b'x').unwrap();
+    acia.tick(2);
+    assert_eq!(line.0.borrow().as_slice(), b">x");
+
+    // a programmed reset clears command bits 0-4 but keeps control
+    acia.write(acia::STATUS, 0);
+    assert_eq!((acia.peek(acia::COMMAND), acia.peek(acia::CONTROL)), (0x00, 0x1F));
+
+    let state = acia.save_state();
+    let (_, input) = mpsc::channel();
+    let mut restored = Acia::new(Line::default(), input);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.peek(acia::DATA), b'x');
+    assert!(restored.load_state(&[0; 3]).is_err());
+}
+
+#[test]
+fn tcp_serial_port_carries_bytes_both_ways() {
+    assert_eq!(SerialBackend::parse("tcp:6551"), Ok(SerialBackend::Tcp(6551)));
+    assert!(SerialBackend::parse("tcp:").is_err());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"hi").unwrap();
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).unwrap();
+        reply
+    });
+
+    let port = serial::open_tcp(listener).unwrap();
+    assert_eq!(port.location, addr.to_string());
+    let mut acia = Acia::new(port.output, port.input);
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        acia.tick(1);
+        if acia.peek(acia::STATUS) & STATUS_RDRF != 0 {
+            received.push(acia.read(acia::DATA));
+        }
+    }
+    assert_eq!(received, b"hi");
+
+    acia.write(acia::DATA, b'o');
+    acia.write(acia::DATA, b'k');
+    assert_eq!(&client.join().unwrap(), b"ok");
+}
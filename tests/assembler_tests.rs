@@ -1,4 +1,6 @@
-use rust_6502_emulator::loader::assemble;
+use rust_6502_emulator::loader::{assemble, assemble_named, assemble_source};
+use rust_6502_emulator::source_map::SourceMap;
+use rust_6502_emulator::symbols::SymbolTable;
 use std::fs;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -32,3 +34,107 @@ fn assemble_handles_sta_addressing_modes() {
 
     assert_eq!(program, vec![0x85, 0x20, 0x8D, 0x78, 0x56]);
 }
+
+#[test]
+fn assemble_handles_indexed_and_indirect_indexed_modes() {
+    let source = "table:\nSTA $0200,X\nLDA ($10),Y\nLDA ($20, x)\nLDX $10,Y\nLDA $10,Y\nLDA table,X\nSTA $30 , x\n";
+
+    let assembly = assemble_source(source).expect("Failed to assemble");
+
+    assert_eq!(
+        assembly.bytes,
+        vec![
+            0x9D, 0x00, 0x02, // STA $0200,X
+            0xB1, 0x10, // LDA ($10),Y
+            0xA1, 0x20, // LDA ($20,X)
+            0xB6, 0x10, // LDX $10,Y
+            0xB9, 0x10, 0x00, // LDA has no zero page,Y form
+            0xBD, 0x00, 0x06, // LDA table,X
+            0x95, 0x30, // STA $30,X
+        ]
+    );
+
+    let err = assemble_source("LDA ($1234),Y\n").unwrap_err();
+    assert!(err.to_string().contains("zero page"));
+}
+
+#[test]
+fn assemble_resolves_labels_and_exports_symbols() {
+    let source = ".org $0600\nstart:\n    LDX #$05\nloop: DEX\n    BNE loop\n    JMP start\n";
+
+    let assembly = assemble_source(source).expect("Failed to assemble");
+
+    assert_eq!(assembly.origin, 0x0600);
+    assert_eq!(
+        assembly.bytes,
+        vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x4C, 0x00, 0x06]
+    );
+    assert_eq!(assembly.symbols.get("loop"), Some(0x0602));
+    assert_eq!(
+        assembly.symbols.to_vice_labels(),
+        "al C:0600 .start\nal C:0602 .loop\n"
+    );
+
+    let parsed = SymbolTable::parse_vice_labels("al 000602 .loop\nal C:0600 .start\n")
+        .expect("Failed to parse labels");
+    assert_eq!(parsed, assembly.symbols);
+}
+
+#[test]
+fn assemble_listing_shows_address_bytes_and_source() {
+    let assembly = assemble_source("; header\nLDA #$32\nSTA $10 ; store\n").expect("Failed to assemble");
+
+    let listing = assembly.listing_text();
+    let lines: Vec<&str> = listing.lines().collect();
+
+    assert_eq!(lines[0], "    1                  ; header");
+    assert_eq!(lines[1], "    2  0600  A9 32     LDA #$32");
+    assert_eq!(lines[2], "    3  0602  85 10     STA $10 ; store");
+}
+
+#[test]
+fn assemble_binds_labels_before_the_first_org_to_its_address() {
+    let source = "foo:\n; comment\nbar:\n.org $0800\n    JMP foo\n    LDA bar,X\n";
+
+    let assembly = assemble_source(source).expect("Failed to assemble");
+
+    assert_eq!(assembly.origin, 0x0800);
+    assert_eq!(assembly.bytes, vec![0x4C, 0x00, 0x08, 0xBD, 0x00, 0x08]);
+    assert_eq!(assembly.symbols.get("foo"), Some(0x0800));
+    assert_eq!(assembly.listing[0].address, 0x0800);
+}
+
+#[test]
+fn assemble_rejects_undefined_labels() {
+    let err = assemble_source("JMP nowhere\n").unwrap_err();
+    assert!(err.to_string().contains("nowhere"));
+}
+
+#[test]
+fn assemble_rejects_code_that_wraps_past_ffff() {
+    assert_eq!(assemble_source(".org $FFFF\n    NOP\n").unwrap().bytes, [0xEA]);
+    let err = assemble_source(".org $FFFF\n    NOP\n    NOP\n").unwrap_err();
+    assert_eq!(err.to_string(), "Code runs past $FFFF on line 3");
+    let err = assemble_source(".org $FFFE\n    JMP $0600\n").unwrap_err();
+    assert_eq!(err.to_string(), "Code runs past $FFFF on line 2");
+}
+
+#[test]
+fn assemble_maps_instructions_to_source_lines() {
+    let source = "; header\nstart:\n    LDA #$01\n\ndata: .byte 1, 2\n    STA $10\n";
+    let assembly = assemble_named(source, "src/prog.asm").expect("Failed to assemble");
+    let map = &assembly.source_map;
+
+    // data lines aren't mapped, only instructions
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.location(0x0600).unwrap().to_string(), "src/prog.asm:3");
+    assert_eq!(map.location(0x0604).unwrap().line, 6);
+    assert_eq!(map.addresses("prog.asm", 6), vec![0x0604]);
+    assert_eq!(map.resolve("prog.asm", 4), Some((6, 0x0604)));
+    assert_eq!(map.resolve("other.asm", 3), None);
+    assert_eq!(map.annotate(0x0600).unwrap(), "src/prog.asm:3: LDA #$01");
+
+    let text = map.to_text();
+    assert_eq!(text, "$0600 src/prog.asm:3\n$0604 src/prog.asm:6\n");
+    assert_eq!(SourceMap::parse_text(&text).unwrap().to_text(), text);
+}
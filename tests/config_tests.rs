@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::Path;
+
+use rust_6502_emulator::devices::random::Random;
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::machines::config::{DeviceKind, Line, MachineConfig, Reset};
+use rust_6502_emulator::runner::{run, RunOptions, RunOutcome};
+use rust_6502_emulator::toml::Toml;
+use rust_6502_emulator::{Memory, CPU};
+
+#[test]
+fn toml_subset_parses_tables_arrays_and_integers() {
+    let text = r#"
+        # a comment
+        name = "board \"one\""   # trailing comment
+        path = 'C:\roms'
+        sizes = [0x10, 0b1000_0000,
+                 -5, 1_000,]   # may span lines
+        nested.key = true
+
+        [cpu]
+        "quoted key" = 0o17
+        [[device]]
+        type = "via"
+        lcd = { wiring = "breadboard", rows = 4 }
+        [[device]]
+        type = "pia"
+    "#;
+    let doc = Toml::parse(text).unwrap();
+
+    assert_eq!(doc.get("name").and_then(Toml::as_str), Some("board \"one\""));
+    assert_eq!(doc.get("path").and_then(Toml::as_str), Some("C:\\roms"));
+    let sizes: Vec<i64> = doc.get("sizes").and_then(Toml::as_array).unwrap().iter().filter_map(Toml::as_i64).collect();
+    assert_eq!(sizes, [16, 128, -5, 1000]);
+    assert_eq!(doc.get("nested").and_then(|t| t.get("key")).and_then(Toml::as_bool), Some(true));
+    assert_eq!(doc.get("cpu").and_then(|t| t.get("quoted key")).and_then(Toml::as_i64), Some(15));
+
+    let devices = doc.get("device").and_then(Toml::as_array).unwrap();
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[0].get("lcd").and_then(|t| t.get("rows")).and_then(Toml::as_i64), Some(4));
+    assert_eq!(devices[1].get("type").and_then(Toml::as_str), Some("pia"));
+
+    // errors say which line
+    assert_eq!(Toml::parse("a = 1\nb = 2\na = 3").unwrap_err(), "line 3: Duplicate key 'a'");
+    assert_eq!(Toml::parse("a = 1\nb = 0x\n").unwrap_err(), "line 2: Invalid value: 0x");
+    assert!(Toml::parse("a = \"open\nb = 1").unwrap_err().starts_with("line 1:"));
+    assert!(Toml::parse("a = 1 2").is_err());
+}
+
+#[test]
+fn machine_file_builds_regions_and_devices_and_boots_through_the_reset_vector() {
+    let dir = std::env::temp_dir().join(format!("config_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // LDA #$2A / STA $F002, then vectors at the top of a 2 KiB ROM
+    let mut rom = vec![0xA9, 0x2A, 0x8D, 0x02, 0xF0];
+    rom.resize(0x800, 0xEA);
+    rom[0x7FC..0x7FE].copy_from_slice(&[0x00, 0xF8]);
+    fs::write(dir.join("rom.bin"), &rom).unwrap();
+    fs::write(dir.join("data.bin"), [1, 2, 3]).unwrap();
+    fs::write(
+        dir.join("board.toml"),
+        r#"
+        name = "test board"
+        cpu = "6502"
+        clock = "2MHz"
+        reset = "vector"
+        exit_port = 0xF002
+
+        [[region]]
+        type = "ram"
+        start = 0x0200
+        end = 0x02FF
+        fill = 0xEA
+
+        [[region]]
+        type = "ram"
+        start = 0x0300
+        file = "data.bin"
+
+        [[region]]
+        type = "rom"
+        start = 0xF800
+        file = "rom.bin"
+
+        [[device]]
+        type = "via"
+        start = 0x6000
+        end = 0x7FFF
+        lcd = { wiring = "breadboard", columns = 20, rows = 4 }
+
+        [[device]]
+        type = "random"
+        start = 0x00FE
+        seed = 7
+        "#,
+    )
+    .unwrap();
+
+    let config = MachineConfig::load(&dir.join("board.toml")).unwrap();
+    assert_eq!(config.devices[1].kind, DeviceKind::Random { seed: Some(7) });
+    assert_eq!((config.devices[1].start, config.devices[1].end, config.devices[1].line), (0x00FE, 0x00FE, Line::Irq));
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let machine = config.build(&mut memory).unwrap();
+    assert_eq!((machine.name.as_str(), machine.clock_hz, machine.reset), ("test board", Some(2_000_000), Reset::Vector));
+    assert_eq!(machine.lcds.len(), 1);
+    assert_eq!(machine.lcds[0].borrow().lines().len(), 4);
+
+    assert_eq!([memory.peek(0x0200), memory.peek(0x02FF), memory.peek(0x0300), memory.peek(0x0302)], [0xEA, 0xEA, 1, 3]);
+    memory.write(0xF800, 0x00);
+    assert_eq!(memory.peek(0xF800), 0xA9);
+    assert_eq!(memory.read(0x00FE), Random::new(7).read(0));
+
+    machine.reset.apply(&mut cpu, &mut memory);
+    assert_eq!(cpu.pc, 0xF800);
+    let options = RunOptions { exit_port: machine.layout.exit_port, max_cycles: Some(100), ..RunOptions::default() };
+    assert_eq!(run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap(), RunOutcome::Exit(0x2A));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn devices_wired_to_nmi_interrupt_once_per_edge_and_unwired_ones_stay_quiet() {
+    let dir = std::env::temp_dir().join(format!("config_nmi_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut rom = vec![
+        // enable T1 interrupts on both VIAs, start both timers, JMP *
+        0xA9, 0xC0, 0x8D, 0x0E, 0x60, 0x8D, 0x1E, 0x60, 0xA9, 0x40, 0x8D, 0x04, 0x60, 0x8D, 0x14, 0x60,
+        0xA9, 0x00, 0x8D, 0x05, 0x60, 0x8D, 0x15, 0x60, 0x4C, 0x18, 0xFF,
+    ];
+    rom.resize(0x100, 0xEA);
+    // the NMI handler copies $01 to $02, then sets $01: $02 is only set by a second NMI
+    rom[0x80..0x89].copy_from_slice(&[0xA5, 0x01, 0x85, 0x02, 0xA9, 0x01, 0x85, 0x01, 0x40]);
+    rom[0xFA..0xFE].copy_from_slice(&[0x80, 0xFF, 0x00, 0xFF]);
+    fs::write(dir.join("rom.bin"), &rom).unwrap();
+
+    let text = r#"
+        reset = "vector"
+        region = [{ type = "rom", start = 0xFF00, file = "rom.bin" }]
+        [[device]]
+        type = "via"
+        start = 0x6000
+        irq = "nmi"
+        [[device]]
+        type = "via"
+        start = 0x6010
+        irq = "none"
+    "#;
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let machine = MachineConfig::parse(text, &dir).unwrap().build(&mut memory).unwrap();
+    machine.reset.apply(&mut cpu, &mut memory);
+    for _ in 0..500 {
+        cpu.execute(&mut memory);
+    }
+
+    // both timers ran out and still signal it, but NMI only fires on the edge
+    assert_eq!(memory.peek(0x600D) & 0x40, 0x40);
+    assert_eq!((memory.peek(0x0001), memory.peek(0x0002)), (1, 0));
+    assert!(memory.nmi());
+    // and the unwired VIA holds no line at all
+    assert_eq!(memory.peek(0x601D), 0xC0);
+    assert!(!memory.irq());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn machine_files_report_what_is_wrong_with_them() {
+    let base = Path::new("/nonexistent");
+    let error = |text: &str| MachineConfig::parse(text, base).unwrap_err();
+
+    assert_eq!(error("nmae = \"typo\""), "Unknown key 'nmae' in the machine");
+    assert_eq!(error("cpu = \"65c02\""), "CPU '65c02' isn't emulated; only the NMOS 6502 is");
+    assert_eq!(error("reset = \"warm\""), "reset must be \"load\", \"vector\" or an address");
+    assert_eq!(error("load_addr = 0x10000"), "machine.load_addr must be an integer from 0 to 0xFFFF");
+    assert_eq!(error("[[device]]\ntype = \"sid\"\nstart = 0xD400"), "Unknown device type 'sid', expected via, pia, acia, lcd, console or random");
+    assert_eq!(error("[[device]]\ntype = \"via\"\nstart = 0xFFF8"), "The device at $FFF8 runs past $FFFF");
+    assert_eq!(error("[[region]]\ntype = \"rom\"\nstart = 0x8000"), "The ROM at $8000 needs a file");
+    assert_eq!(error("device = 1"), "device must be written as [[device]] tables");
+    assert!(error("name = ").starts_with("line 1:"));
+
+    // a PIA takes just its four registers unless told otherwise
+    let config = MachineConfig::parse("reset = 0xFF00\n[[device]]\ntype = \"pia\"\nstart = 0xD010", base).unwrap();
+    assert_eq!((config.reset, config.devices[0].end), (Reset::At(0xFF00), 0xD013));
+
+    // images are checked when the machine is built
+    let missing = MachineConfig::parse("[[region]]\ntype = \"rom\"\nstart = 0x8000\nfile = \"x.bin\"", base).unwrap();
+    assert!(missing.build(&mut Memory::new()).err().unwrap().starts_with("Cannot read /nonexistent/x.bin"));
+    let too_big = MachineConfig::parse(&format!("[[region]]\ntype = \"rom\"\nstart = 0xFF00\nfile = \"{}\"", file!()), Path::new(".")).unwrap();
+    assert!(too_big.build(&mut Memory::new()).err().unwrap().contains("doesn't fit at $FF00-$FFFF"));
+}
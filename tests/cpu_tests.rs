@@ -0,0 +1,170 @@
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// JSR $0606 / JMP $0603 / sub: LDA #$42 / RTS
+const PROGRAM: [u8; 9] = [0x20, 0x06, 0x06, 0x4C, 0x03, 0x06, 0xA9, 0x42, 0x60];
+const PHA: u16 = 0x0700;
+const PLA: u16 = 0x0701;
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    memory.write(PHA, 0x48);
+    memory.write(PLA, 0x68);
+    (cpu, memory)
+}
+
+// runs the single instruction at addr
+fn execute_at(cpu: &mut CPU, memory: &mut Memory, addr: u16) {
+    cpu.pc = addr;
+    cpu.execute(memory);
+}
+
+#[test]
+fn jsr_and_rts_share_the_stack_in_page_one() {
+    let (mut cpu, mut memory) = setup();
+    cpu.execute(&mut memory);
+    // the return address minus one, high byte first, at $0100 + SP
+    assert_eq!((cpu.pc, cpu.sp), (0x0606, 0xFD));
+    assert_eq!((memory.peek(0x01FF), memory.peek(0x01FE)), (0x06, 0x02));
+
+    cpu.execute(&mut memory);
+    cpu.execute(&mut memory);
+    assert_eq!((cpu.pc, cpu.sp, cpu.a), (0x0603, 0xFF, 0x42));
+}
+
+#[test]
+fn push_and_pull_use_the_same_slot_and_wrap_around_page_one() {
+    let (mut cpu, mut memory) = setup();
+    cpu.a = 0x99;
+    execute_at(&mut cpu, &mut memory, PHA);
+    assert_eq!((memory.peek(0x01FF), cpu.sp), (0x99, 0xFE));
+    cpu.a = 0;
+    execute_at(&mut cpu, &mut memory, PLA);
+    assert_eq!((cpu.a, cpu.sp), (0x99, 0xFF));
+
+    // pushing at SP $00 writes $0100 and wraps to $FF; pulling wraps back
+    cpu.sp = 0x00;
+    cpu.a = 0x77;
+    execute_at(&mut cpu, &mut memory, PHA);
+    assert_eq!((memory.peek(0x0100), cpu.sp), (0x77, 0xFF));
+    cpu.a = 0;
+    execute_at(&mut cpu, &mut memory, PLA);
+    assert_eq!((cpu.a, cpu.sp), (0x77, 0x00));
+}
+
+// loads a program at $0600 and runs count instructions of it
+fn run(program: &[u8], count: usize) -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(program));
+    for _ in 0..count {
+        cpu.execute(&mut memory);
+    }
+    (cpu, memory)
+}
+
+#[test]
+fn indexed_and_indirect_modes_wrap_in_page_zero() {
+    let mut program = vec![
+        0xA2, 0x05, //       LDX #$05
+        0xB5, 0xFE, //       LDA $FE,X      reads $03, not $0103
+        0xA1, 0xFA, //       LDA ($FA,X)    pointer at $FF/$00
+        0xA0, 0x10, //       LDY #$10
+        0x91, 0x10, //       STA ($10),Y
+        0xBE, 0xF8, 0x12, // LDX $12F8,Y    crosses into $1308
+    ];
+    program.resize(16, 0xEA);
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&program));
+    memory.write(0x0003, 0x33);
+    memory.write(0x00FF, 0x00);
+    memory.write(0x0000, 0x20);
+    memory.write(0x2000, 0x44);
+    memory.write(0x0010, 0x00);
+    memory.write(0x0011, 0x30);
+    memory.write(0x1308, 0x55);
+
+    cpu.execute(&mut memory);
+    cpu.execute(&mut memory);
+    assert_eq!(cpu.a, 0x33);
+    cpu.execute(&mut memory);
+    assert_eq!(cpu.a, 0x44);
+    cpu.execute(&mut memory);
+    cpu.execute(&mut memory);
+    assert_eq!(memory.peek(0x3010), 0x44);
+
+    let cycles = cpu.cycles;
+    cpu.execute(&mut memory);
+    assert_eq!((cpu.x, cpu.pc, cpu.cycles - cycles), (0x55, 0x060D, 5));
+}
+
+#[test]
+fn adc_and_sbc_set_carry_and_overflow_in_binary_and_decimal() {
+    let program = [
+        0x18, 0xA9, 0x50, 0x69, 0x50, //       CLC / LDA #$50 / ADC #$50    $A0, V set
+        0x85, 0x00, 0x08, //                   STA $00 / PHP
+        0x38, 0xA9, 0x50, 0xE9, 0xB0, //       SEC / LDA #$50 / SBC #$B0    $A0, borrow, V set
+        0x85, 0x01, 0x08, //                   STA $01 / PHP
+        0xF8, 0x18, 0xA9, 0x58, 0x69, 0x46, // SED / CLC / LDA #$58 / ADC #$46   104 decimal
+        0x85, 0x02, 0x08, //                   STA $02 / PHP
+        0x38, 0xA9, 0x12, 0xE9, 0x21, //       SEC / LDA #$12 / SBC #$21    91 with a borrow
+        0x85, 0x03, 0x08, //                   STA $03 / PHP
+    ];
+    let (_, memory) = run(&program, 22);
+    let results: Vec<u8> = (0..4).map(|i| memory.peek(i)).collect();
+    assert_eq!(results, [0xA0, 0xA0, 0x04, 0x91]);
+    // pushed status bytes: N V - B D I Z C, with B and bit 5 always set on the stack. In
+    // decimal mode an NMOS 6502 takes N and V from the sum before the high digit is adjusted,
+    // which is $A4 for $58 + $46
+    let flags: Vec<u8> = (0..4).map(|i| memory.peek(0x01FF - i)).collect();
+    assert_eq!(flags, [0xF0, 0xF0, 0xF9, 0xB8]);
+}
+
+#[test]
+fn compares_branches_shifts_and_jumps() {
+    let program = [
+        0xA2, 0x03, //       LDX #$03
+        0xCA, //             loop: DEX
+        0xD0, 0xFD, //       BNE loop
+        0xA9, 0x81, //       LDA #$81
+        0x0A, //             ASL A          $02, carry out
+        0x2A, //             ROL A          $05
+        0x6A, //             ROR A          $02, carry from bit 0
+        0xC9, 0x03, //       CMP #$03       A < 3: carry clear, negative
+        0x30, 0x02, //       BMI over
+        0x00, 0x00, //       BRK (skipped)
+        0x6C, 0xFF, 0x06, // over: JMP ($06FF), whose high byte comes from $0600
+    ];
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&program));
+    memory.write(0x06FF, 0x34);
+
+    let start = cpu.cycles;
+    for _ in 0..7 {
+        cpu.execute(&mut memory);
+    }
+    // LDX, then DEX/BNE three times with the last branch not taken
+    assert_eq!((cpu.x, cpu.pc, cpu.cycles - start), (0, 0x0605, 2 + 2 * 3 + 3 * 2 + 2));
+
+    for _ in 0..6 {
+        cpu.execute(&mut memory);
+    }
+    assert_eq!((cpu.a, cpu.status & 0x81), (0x02, 0x80));
+    assert_eq!(cpu.pc, 0x0610);
+    cpu.execute(&mut memory);
+    assert_eq!(cpu.pc, 0xA234);
+}
+
+#[test]
+fn every_documented_opcode_is_implemented_and_no_other() {
+    let cpu = CPU::new();
+    let implemented: Vec<u8> = (0..=0xFF).filter(|&opcode| cpu.implements(opcode)).collect();
+    assert_eq!(implemented.len(), 151);
+    // the NMOS 6502's undocumented opcodes in each column of the opcode matrix
+    for opcode in [0x02, 0x03, 0x04, 0x07, 0x0B, 0x0C, 0x0F, 0x1A, 0x80, 0x9C, 0xEB, 0xFF] {
+        assert!(!cpu.implements(opcode), "${:02X}", opcode);
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::io::Cursor;
+use std::sync::mpsc;
+
+use rust_6502_emulator::dap::{decode_base64, encode_base64, read_message, DapServer};
+use rust_6502_emulator::json::Json;
+
+const SOURCE: &str = "\
+; counts into $10
+start:
+    LDA #$01
+loop:
+    STA $10
+
+    ADC #$01
+    JMP loop
+";
+
+fn write_source(name: &str) -> String {
+    write_program(name, SOURCE)
+}
+
+fn write_program(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(format!("dap_test_{}_{}.asm", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn request(seq: i64, command: &str, arguments: &str) -> Json {
+    Json::parse(&format!(r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#, seq, command, arguments))
+        .unwrap()
+}
+
+// runs the requests through a server and returns every message it sent
+fn session(requests: Vec<Json>) -> Vec<Json> {
+    let (sender, receiver) = mpsc::channel();
+    for request in requests {
+        sender.send(request).unwrap();
+    }
+    drop(sender);
+
+    let mut out = Vec::new();
+    DapServer::new(&mut out).serve(&receiver).unwrap();
+
+    let mut reader = Cursor::new(out);
+    let mut messages = Vec::new();
+    while let Some(message) = read_message(&mut reader).unwrap() {
+        messages.push(message);
+    }
+    messages
+}
+
+fn response<'a>(messages: &'a [Json], command: &str) -> &'a Json {
+    messages
+        .iter()
+        .find(|message| {
+            message.get("type").and_then(Json::as_str) == Some("response")
+                && message.get("command").and_then(Json::as_str) == Some(command)
+        })
+        .unwrap_or_else(|| panic!("no {} response", command))
+}
+
+fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+    messages.iter().filter(|message| message.get("event").and_then(Json::as_str) == Some(event)).collect()
+}
+
+#[test]
+fn launches_and_stops_at_source_line_breakpoints() {
+    let path = write_source("breakpoints");
+    let messages = session(vec![
+        request(1, "initialize", r#"{"adapterID":"6502"}"#),
+        request(2, "launch", &format!(r#"{{"program":"{}"}}"#, path)),
+        // line 6 is blank, so it moves to the ADC on line 7
+        request(3, "setBreakpoints", &format!(r#"{{"source":{{"path":"{}"}},"breakpoints":[{{"line":6}},{{"line":40}}]}}"#, path)),
+        request(4, "configurationDone", "{}"),
+        request(5, "stackTrace", r#"{"threadId":1}"#),
+        request(6, "variables", r#"{"variablesReference":1}"#),
+        request(7, "evaluate", r#"{"expression":"[$10] + 1"}"#),
+        request(8, "disconnect", "{}"),
+    ]);
+
+    let initialize = response(&messages, "initialize");
+    assert_eq!(initialize.get("body").unwrap().get("supportsStepBack"), Some(&Json::Bool(true)));
+    assert_eq!(events(&messages, "initialized").len(), 1);
+
+    let breakpoints = response(&messages, "setBreakpoints").get("body").unwrap().get("breakpoints").unwrap();
+    let breakpoints = breakpoints.as_array().unwrap();
+    assert_eq!(breakpoints[0].get("line").and_then(Json::as_i64), Some(7));
+    assert_eq!(breakpoints[0].get("verified"), Some(&Json::Bool(true)));
+    assert_eq!(breakpoints[1].get("verified"), Some(&Json::Bool(false)));
+
+    let stopped = events(&messages, "stopped");
+    assert_eq!(stopped[0].get("body").unwrap().get("reason").and_then(Json::as_str), Some("breakpoint"));
+
+    let frame = &response(&messages, "stackTrace").get("body").unwrap().get("stackFrames").unwrap().as_array().unwrap()[0];
+    assert_eq!(frame.get("line").and_then(Json::as_i64), Some(7));
+    assert_eq!(frame.get("instructionPointerReference").and_then(Json::as_str), Some("0x0604"));
+
+    let variables = response(&messages, "variables").get("body").unwrap().get("variables").unwrap().to_string();
+    assert!(variables.contains(r#""name":"A","value":"$01""#), "{}", variables);
+    assert!(variables.contains(r#""name":"PC","value":"$0604""#));
+
+    let result = response(&messages, "evaluate").get("body").unwrap().get("result").unwrap();
+    assert_eq!(result.as_str(), Some("$2 (2)"));
+    assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn steps_forward_and_back_and_reads_memory() {
+    let path = write_source("stepping");
+    let messages = session(vec![
+        request(1, "launch", &format!(r#"{{"program":"{}","stopOnEntry":true}}"#, path)),
+        request(2, "configurationDone", "{}"),
+        request(3, "next", r#"{"threadId":1}"#),
+        request(4, "stepIn", r#"{"threadId":1}"#),
+        request(5, "stepBack", r#"{"threadId":1}"#),
+        request(6, "writeMemory", r#"{"memoryReference":"0x0200","data":"3q2+7w=="}"#),
+        request(7, "readMemory", r#"{"memoryReference":"0x01FF","offset":1,"count":4}"#),
+        request(8, "stackTrace", r#"{"threadId":1}"#),
+        request(9, "bogus", "{}"),
+    ]);
+
+    let reasons: Vec<&str> = events(&messages, "stopped")
+        .iter()
+        .map(|event| event.get("body").unwrap().get("reason").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(reasons, ["entry", "step", "step", "step"]);
+
+    let memory = response(&messages, "readMemory").get("body").unwrap();
+    assert_eq!(memory.get("address").and_then(Json::as_str), Some("0x0200"));
+    assert_eq!(decode_base64(memory.get("data").and_then(Json::as_str).unwrap()).unwrap(), [0xDE, 0xAD, 0xBE, 0xEF]);
+
+    // stepped to $0604 then back to the STA on line 5
+    let frame = &response(&messages, "stackTrace").get("body").unwrap().get("stackFrames").unwrap().as_array().unwrap()[0];
+    assert_eq!(frame.get("line").and_then(Json::as_i64), Some(5));
+
+    assert_eq!(response(&messages, "bogus").get("success"), Some(&Json::Bool(false)));
+}
+
+#[test]
+fn illegal_opcodes_stop_with_an_exception_instead_of_running() {
+    let path = write_program("illegal", "    LDA #$01\n    .byte $02\n");
+    let messages = session(vec![
+        request(1, "launch", &format!(r#"{{"program":"{}","stopOnEntry":true}}"#, path)),
+        request(2, "configurationDone", "{}"),
+        request(3, "next", r#"{"threadId":1}"#),
+        request(4, "stepIn", r#"{"threadId":1}"#),
+        request(5, "continue", r#"{"threadId":1}"#),
+        request(6, "variables", r#"{"variablesReference":1}"#),
+    ]);
+
+    let reasons: Vec<&str> = events(&messages, "stopped")
+        .iter()
+        .map(|event| event.get("body").unwrap().get("reason").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(reasons, ["entry", "step", "exception", "exception"]);
+
+    let variables = response(&messages, "variables").get("body").unwrap().get("variables").unwrap().to_string();
+    assert!(variables.contains(r#""name":"PC","value":"$0602""#), "{}", variables);
+}
+
+#[test]
+fn json_and_base64_round_trip() {
+    let text = r#"{"a":[1,-2.5,true,null],"b":"quote \" and \n newline","c":{}}"#;
+    assert_eq!(Json::parse(text).unwrap().to_string(), text);
+    assert!(Json::parse("{\"a\":}").is_err());
+
+    for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob"] {
+        assert_eq!(decode_base64(&encode_base64(bytes)).unwrap(), bytes);
+    }
+    assert_eq!(encode_base64(b"fo"), "Zm8=");
+}
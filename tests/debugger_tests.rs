@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust_6502_emulator::devices::random::Random;
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::debugger::{BreakOn, Condition, MinCPU, StopReason, WatchKind, Watchpoint};
+use rust_6502_emulator::expression::Expr;
+use rust_6502_emulator::loader::{assemble_named, disassemble};
+use rust_6502_emulator::memory::AccessKind;
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// LDA #$10 / STA $00 / LDA #$20 / STA $01 / JMP $0600
+const PROGRAM: [u8; 11] = [0xA9, 0x10, 0x85, 0x00, 0xA9, 0x20, 0x85, 0x01, 0x4C, 0x00, 0x06];
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    (cpu, memory)
+}
+
+fn run_until_stop(cpu: &mut CPU, memory: &mut Memory, max_steps: usize) -> Option<StopReason> {
+    (0..max_steps).find_map(|_| cpu.step(memory))
+}
+
+#[test]
+fn stops_at_address_and_mnemonic_breakpoints() {
+    let (mut cpu, mut memory) = setup();
+
+    let (on, condition) = BreakOn::parse("$0604").unwrap();
+    let id = cpu.debugger.add_breakpoint(on, condition);
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 10), Some(StopReason::Breakpoint(id)));
+    assert_eq!(cpu.pc, 0x0604);
+
+    cpu.debugger.remove_breakpoint(id);
+    let (on, _) = BreakOn::parse("sta").unwrap();
+    assert_eq!(on, BreakOn::Mnemonic(String::from("STA")));
+    let id = cpu.debugger.add_breakpoint(on, None);
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 10), Some(StopReason::Breakpoint(id)));
+    assert_eq!(cpu.pc, 0x0606);
+
+    // every documented mnemonic, whether or not this program uses it
+    for name in ["inx", "ORA", "bit", "Rti"] {
+        assert_eq!(BreakOn::parse(name).unwrap().0, BreakOn::Mnemonic(name.to_ascii_uppercase()));
+    }
+}
+
+#[test]
+fn disassembles_indexed_and_indirect_modes() {
+    let mut memory = Memory::new();
+    let code = [0xB1, 0x10, 0x9D, 0x00, 0x02, 0xB6, 0xFF, 0x61, 0x20, 0xE8];
+    for (i, &byte) in code.iter().enumerate() {
+        memory.write(0x0600 + i as u16, byte);
+    }
+    let lines: Vec<(String, u16)> = [0x0600, 0x0602, 0x0605, 0x0607, 0x0609].iter().map(|&addr| disassemble(&memory, addr)).collect();
+    let expected = [("LDA ($10),Y", 2), ("STA $0200,X", 3), ("LDX $FF,Y", 2), ("ADC ($20,X)", 2), ("INX", 1)];
+    assert_eq!(lines, expected.map(|(text, len)| (text.to_string(), len)));
+}
+
+#[test]
+fn conditional_breakpoint_reads_registers_and_memory() {
+    let (mut cpu, mut memory) = setup();
+    memory.write(0x0200, 5);
+
+    let (on, condition) = BreakOn::parse("opcode $85 if A == $20 && [$0200] > 3").unwrap();
+    let id = cpu.debugger.add_breakpoint(on, condition);
+
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 10), Some(StopReason::Breakpoint(id)));
+    assert_eq!(cpu.pc, 0x0606);
+    assert_eq!(cpu.debugger.breakpoints()[0].hit_count, 1);
+
+    // arithmetic wraps instead of overflowing
+    let state = MinCPU::from_cpu(&cpu);
+    let value = |text: &str| Expr::parse(text).unwrap().eval(&state, &memory);
+    assert_eq!(value("$7FFFFFFFFFFFFFFF + 1"), i64::MIN);
+    assert_eq!(value("0 - $7FFFFFFFFFFFFFFF - 2"), i64::MAX);
+    assert_eq!(value("-(0 - $7FFFFFFFFFFFFFFF - 1)"), i64::MIN);
+}
+
+#[test]
+fn ignore_count_and_disable_skip_hits() {
+    let (mut cpu, mut memory) = setup();
+
+    let id = cpu.debugger.add_breakpoint(BreakOn::Address(0x0602), None);
+    cpu.debugger.breakpoint_mut(id).unwrap().ignore_count = 2;
+
+    // the loop passes $0602 three times before the third pass stops
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 20), Some(StopReason::Breakpoint(id)));
+    assert_eq!(cpu.debugger.breakpoints()[0].hit_count, 3);
+    assert_eq!(cpu.debugger.breakpoints()[0].ignore_count, 0);
+
+    cpu.debugger.breakpoint_mut(id).unwrap().enabled = false;
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 20), None);
+}
+
+#[test]
+fn conditions_reject_unknown_names() {
+    assert!(Condition::parse("Q == 1").is_err());
+    assert!(Condition::parse("(A == 1").is_err());
+    assert!(Condition::parse("C = 1 || !Z").is_ok());
+}
+
+#[test]
+fn write_watchpoint_reports_pc_and_values() {
+    let (mut cpu, mut memory) = setup();
+    memory.write(0x0001, 0x99);
+
+    let (start, end, value) = Watchpoint::parse_spec("$0001").unwrap();
+    let id = cpu.debugger.add_watchpoint(WatchKind::Write, start, end, value);
+
+    match run_until_stop(&mut cpu, &mut memory, 10) {
+        Some(StopReason::Watchpoint(hit)) => {
+            assert_eq!(hit.id, id);
+            assert_eq!(hit.pc, 0x0606);
+            assert_eq!((hit.addr, hit.old, hit.new), (0x0001, 0x99, 0x20));
+            assert_eq!(hit.kind, AccessKind::Write);
+        }
+        other => panic!("expected a watchpoint hit, got {:?}", other),
+    }
+}
+
+#[test]
+fn read_watchpoint_filters_by_value_and_skips_fetches() {
+    // LDA $10 / LDA $11 / JMP $0600
+    let program = [0xA5, 0x10, 0xA5, 0x11, 0x4C, 0x00, 0x06];
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&program));
+    memory.write(0x0010, 0x01);
+    memory.write(0x0011, 0x02);
+
+    // the program's own bytes are fetched, never read as data
+    cpu.debugger.add_watchpoint(WatchKind::Read, 0x0600, 0x0606, None);
+    let (start, end, value) = Watchpoint::parse_spec("$0010-$0011 == $02").unwrap();
+    let id = cpu.debugger.add_watchpoint(WatchKind::Access, start, end, value);
+
+    match run_until_stop(&mut cpu, &mut memory, 10) {
+        Some(StopReason::Watchpoint(hit)) => {
+            assert_eq!(hit.id, id);
+            assert_eq!((hit.pc, hit.addr, hit.new), (0x0602, 0x0011, 0x02));
+        }
+        other => panic!("expected a watchpoint hit, got {:?}", other),
+    }
+}
+
+#[test]
+fn step_back_restores_registers_and_memory() {
+    let (mut cpu, mut memory) = setup();
+    memory.write(0x0000, 0x77);
+    for _ in 0..2 {
+        cpu.step(&mut memory);
+    }
+    assert_eq!((cpu.pc, memory.peek(0x0000)), (0x0604, 0x10));
+
+    let entry = cpu.step_back(&mut memory).unwrap();
+    assert_eq!(entry.to_string(), "$0602  STA  PC: $0602 -> $0604  $0000: $77 -> $10");
+    assert_eq!((cpu.pc, cpu.a, memory.peek(0x0000), cpu.cycles), (0x0602, 0x10, 0x77, 2));
+
+    cpu.step_back(&mut memory).unwrap();
+    assert_eq!((cpu.pc, cpu.a, cpu.cycles), (0x0600, 0x00, 0));
+    assert!(cpu.step_back(&mut memory).is_none());
+}
+
+#[test]
+fn step_back_stops_at_a_write_to_a_device() {
+    let (mut cpu, mut memory) = setup();
+    let random = Rc::new(RefCell::new(Random::new(5)));
+    memory.attach(0x0001, 0x0001, random.clone());
+    let seed = random.borrow().save_state();
+    for _ in 0..5 {
+        cpu.step(&mut memory);
+    }
+    assert_eq!(cpu.pc, 0x0600);
+
+    // the JMP comes back, but the STA to the device is as far as history reaches
+    cpu.step_back(&mut memory).unwrap();
+    assert_eq!(cpu.pc, 0x0608);
+    assert!(cpu.step_back(&mut memory).is_none());
+    assert_eq!((cpu.pc, memory.peek(0x0000)), (0x0608, 0x10));
+    assert_eq!(random.borrow().save_state(), seed);
+}
+
+#[test]
+fn reverse_continue_stops_at_breakpoint_and_history_is_bounded() {
+    let (mut cpu, mut memory) = setup();
+    cpu.debugger.history.set_limit(6);
+    run_until_stop(&mut cpu, &mut memory, 8);
+    assert_eq!(cpu.debugger.history.len(), 6);
+
+    let (on, condition) = BreakOn::parse("$0606 if A == $20").unwrap();
+    let id = cpu.debugger.add_breakpoint(on, condition);
+    assert_eq!(cpu.reverse_continue(&mut memory), Some(StopReason::Breakpoint(id)));
+    assert_eq!(cpu.pc, 0x0606);
+
+    // the oldest two instructions were dropped, so running further back ends early
+    cpu.debugger.remove_breakpoint(id);
+    assert_eq!(cpu.reverse_continue(&mut memory), None);
+    assert_eq!(cpu.pc, 0x0604);
+}
+
+#[test]
+fn breaks_on_source_lines_and_annotates_traces() {
+    let source = "start:\n    LDA #$10\n    STA $00\n\n    LDA #$20\n    JMP start\n";
+    let assembly = assemble_named(source, "prog.asm").unwrap();
+    let (mut cpu, mut memory) = setup();
+    cpu.debugger.source_map = assembly.source_map;
+
+    // line 4 is blank, so the breakpoint lands on line 5
+    let (on, condition) = cpu.debugger.parse_breakpoint("prog.asm:4 if A == $10").unwrap();
+    assert_eq!(on, BreakOn::Address(0x0604));
+    let id = cpu.debugger.add_breakpoint(on, condition);
+    assert_eq!(run_until_stop(&mut cpu, &mut memory, 10), Some(StopReason::Breakpoint(id)));
+    assert!(cpu.debugger.parse_breakpoint("prog.asm:40").is_err());
+
+    let trace = cpu.debugger.trace_line(&MinCPU::from_cpu(&cpu), &memory);
+    assert_eq!(
+        trace,
+        "$0604  A9 20     LDA #$20      A=10 X=00 Y=00 SP=FF P=........  ; prog.asm:5: LDA #$20"
+    );
+}
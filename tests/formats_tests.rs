@@ -0,0 +1,36 @@
+use rust_6502_emulator::formats::{write_output, Block, OutputFormat};
+
+fn program() -> Vec<Block> {
+    vec![Block { addr: 0x0600, data: vec![0xA9, 0x32, 0x85, 0x10] }]
+}
+
+#[test]
+fn prg_prefixes_load_address() {
+    let prg = write_output(OutputFormat::Prg, &program());
+    assert_eq!(prg, vec![0x00, 0x06, 0xA9, 0x32, 0x85, 0x10]);
+}
+
+#[test]
+fn raw_fills_gaps_between_blocks() {
+    let blocks = vec![
+        Block { addr: 0x0600, data: vec![0x01] },
+        Block { addr: 0x0603, data: vec![0x02] },
+    ];
+    assert_eq!(write_output(OutputFormat::Raw, &blocks), vec![0x01, 0x00, 0x00, 0x02]);
+}
+
+#[test]
+fn intel_hex_and_srecord_checksums() {
+    let hex = String::from_utf8(write_output(OutputFormat::IntelHex, &program())).unwrap();
+    assert_eq!(hex, ":04060000A932851086\n:00000001FF\n");
+
+    let srec = String::from_utf8(write_output(OutputFormat::SRecord, &program())).unwrap();
+    assert_eq!(srec, "S00600004844521B\nS1070600A932851082\nS9030600F6\n");
+}
+
+#[test]
+fn memory_image_places_bytes_at_their_address() {
+    let image = write_output(OutputFormat::MemoryImage, &program());
+    assert_eq!(image.len(), 0x10000);
+    assert_eq!(&image[0x0600..0x0604], &[0xA9, 0x32, 0x85, 0x10]);
+}
@@ -0,0 +1,131 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use rust_6502_emulator::gdb::{frame, serve, GdbSession};
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// LDA #$10 / STA $00 / LDA #$20 / STA $01 / JMP $0600
+const PROGRAM: [u8; 11] = [0xA9, 0x10, 0x85, 0x00, 0xA9, 0x20, 0x85, 0x01, 0x4C, 0x00, 0x06];
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    (cpu, memory)
+}
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    // sends a packet and returns the reply body, acking both ways
+    fn request(&mut self, body: &str) -> String {
+        self.stream.write_all(frame(body).as_bytes()).unwrap();
+
+        let mut reply = Vec::new();
+        let mut byte = [0; 1];
+        loop {
+            self.stream.read_exact(&mut byte).unwrap();
+            reply.push(byte[0]);
+            if reply.len() >= 3 && reply[reply.len() - 3] == b'#' {
+                break;
+            }
+        }
+        self.stream.write_all(b"+").unwrap();
+
+        let reply = String::from_utf8(reply).unwrap();
+        let start = reply.find('$').unwrap();
+        assert_eq!(&reply[..start], "+");
+        let (body, checksum) = reply[start + 1..].split_once('#').unwrap();
+        assert_eq!(frame(body), format!("${}#{}", body, checksum));
+        body.to_string()
+    }
+}
+
+#[test]
+fn serves_registers_memory_and_breakpoints_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // devices aren't Send, so the machine stays on the server thread and only the results come back
+    let server = thread::spawn(move || {
+        let (mut cpu, mut memory) = setup();
+        serve(&mut cpu, &mut memory, &listener).unwrap();
+        (cpu.a, memory.peek(0x0200), memory.peek(0x0201))
+    });
+
+    let mut client = Client { stream: TcpStream::connect(("127.0.0.1", port)).unwrap() };
+    assert!(client.request("qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(client.request("?"), "S05");
+    assert_eq!(client.request("g"), "000000ff000006");
+    assert_eq!(client.request("m600,4"), "a9108500");
+
+    assert_eq!(client.request("Z0,606,1"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p5"), "0606");
+    assert_eq!(client.request("z0,606,1"), "OK");
+
+    assert_eq!(client.request("Z2,1,1"), "OK");
+    assert_eq!(client.request("c"), "T05watch:1;");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p5"), "0006");
+
+    assert_eq!(client.request("M200,2:beef"), "OK");
+    assert_eq!(client.request("P0=42"), "OK");
+    assert_eq!(client.request("D"), "OK");
+
+    assert_eq!(server.join().unwrap(), (0x42, 0xBE, 0xEF));
+}
+
+#[test]
+fn continue_stops_when_interrupted_and_serves_target_xml() {
+    let (mut cpu, mut memory) = setup();
+    let mut session = GdbSession::new();
+
+    let reply = session.handle(&mut cpu, &mut memory, "c", &mut || true).unwrap();
+    assert_eq!(reply, "S02");
+
+    let reply = session.handle(&mut cpu, &mut memory, "qXfer:features:read:target.xml:0,a", &mut || false).unwrap();
+    assert_eq!(reply, "m<?xml vers");
+    let reply = session.handle(&mut cpu, &mut memory, "qXfer:features:read:target.xml:0,1000", &mut || false).unwrap();
+    assert!(reply.starts_with('l') && reply.contains(r#"<reg name="pc" bitsize="16""#));
+
+    // hex data that isn't ASCII is an error, even when a character straddles a digit pair
+    assert_eq!(session.handle(&mut cpu, &mut memory, "M200,2:a\u{e9}b", &mut || false).unwrap(), "E01");
+    assert_eq!(session.handle(&mut cpu, &mut memory, "G\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}", &mut || false).unwrap(), "E01");
+
+    assert_eq!(session.handle(&mut cpu, &mut memory, "vMustReplyEmpty", &mut || false).unwrap(), "");
+    assert_eq!(session.handle(&mut cpu, &mut memory, "k", &mut || false), None);
+    assert!(session.finished);
+}
+
+#[test]
+fn illegal_opcodes_stop_with_sigill_and_a_hangup_ends_a_continue() {
+    let (mut cpu, mut memory) = setup();
+    let mut session = GdbSession::new();
+    memory.write(0x0604, 0x02);
+    assert_eq!(session.handle(&mut cpu, &mut memory, "c", &mut || false).unwrap(), "S04");
+    assert_eq!(cpu.pc, 0x0604);
+    assert_eq!(session.handle(&mut cpu, &mut memory, "s", &mut || false).unwrap(), "S04");
+    assert_eq!(cpu.pc, 0x0604);
+
+    // the program loops forever, so only the client going away can end this continue
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut cpu, mut memory) = setup();
+        done.send(serve(&mut cpu, &mut memory, &listener).is_ok()).unwrap();
+    });
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(frame("c").as_bytes()).unwrap();
+    let mut ack = [0; 1];
+    stream.read_exact(&mut ack).unwrap();
+    drop(stream);
+    assert_eq!(finished.recv_timeout(Duration::from_secs(10)), Ok(true));
+}
@@ -0,0 +1,76 @@
+use rust_6502_emulator::formats::{write_output, Block, OutputFormat};
+use rust_6502_emulator::image_loader::{load_image, EntrySource, ImageFormat, Region};
+use rust_6502_emulator::{Memory, CPU};
+use std::path::Path;
+
+#[test]
+fn loads_raw_binary_at_chosen_address() {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+
+    let report = load_image(&mut cpu, &mut memory, ImageFormat::Raw, &[0xA9, 0x01], 0x1000).unwrap();
+
+    assert_eq!(memory.read(0x1001), 0x01);
+    assert_eq!(cpu.pc, 0x1000);
+    assert_eq!(report.regions, vec![Region { start: 0x1000, len: 2 }]);
+}
+
+#[test]
+fn raw_images_covering_the_reset_vector_start_from_it_and_prg_files_at_their_load_address() {
+    let mut rom = vec![0xEA; 0x8000];
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x10, 0x80]);
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let report = load_image(&mut cpu, &mut memory, ImageFormat::Raw, &rom, 0x8000).unwrap();
+    assert_eq!((cpu.pc, report.entry_source), (0x8010, EntrySource::ResetVector));
+
+    // one byte short of the vector's high byte
+    let report = load_image(&mut cpu, &mut memory, ImageFormat::Raw, &rom[..0x7FFD], 0x8000).unwrap();
+    assert_eq!((cpu.pc, report.entry_source), (0x8000, EntrySource::LoadAddress));
+
+    let mut prg = vec![0x00, 0x80];
+    prg.extend(&rom);
+    let report = load_image(&mut cpu, &mut memory, ImageFormat::Prg, &prg, 0).unwrap();
+    assert_eq!((cpu.pc, report.entry_source), (0x8000, EntrySource::LoadAddress));
+}
+
+#[test]
+fn hex_and_srecord_round_trip_with_the_writers() {
+    let blocks = vec![
+        Block { addr: 0x0600, data: (0..40).collect() },
+        Block { addr: 0x2000, data: vec![0xEA] },
+    ];
+
+    for (output, format) in [(OutputFormat::IntelHex, ImageFormat::IntelHex), (OutputFormat::SRecord, ImageFormat::SRecord)] {
+        let mut cpu = CPU::new();
+        let mut memory = Memory::new();
+        let bytes = write_output(output, &blocks);
+
+        assert_eq!(ImageFormat::detect(Path::new("program"), &bytes), format);
+        let report = load_image(&mut cpu, &mut memory, format, &bytes, 0).unwrap();
+
+        assert_eq!(memory.read(0x0627), 39);
+        assert_eq!(memory.read(0x2000), 0xEA);
+        assert_eq!(report.regions, vec![Region { start: 0x0600, len: 40 }, Region { start: 0x2000, len: 1 }]);
+        assert_eq!(cpu.pc, 0x0600);
+    }
+}
+
+#[test]
+fn ines_mirrors_prg_rom_and_starts_at_reset_vector() {
+    let mut rom = b"NES\x1A\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+    let mut prg = vec![0xEA; 0x4000];
+    prg[0x3FFC] = 0x34;
+    prg[0x3FFD] = 0xC1;
+    rom.extend(prg);
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let report = load_image(&mut cpu, &mut memory, ImageFormat::INes, &rom, 0).unwrap();
+
+    assert_eq!(report.entry_source, EntrySource::ResetVector);
+    assert_eq!(cpu.pc, 0xC134);
+    assert_eq!(memory.read(0xBFFD), 0xC1);
+    assert_eq!(report.regions, vec![Region { start: 0x8000, len: 0x8000 }]);
+}
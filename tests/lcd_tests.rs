@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust_6502_emulator::devices::lcd::{self, Lcd, LcdPins, BREADBOARD_4BIT_WIRING, BUSY_FLAG};
+use rust_6502_emulator::devices::via::{self, Via};
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::Memory;
+
+const LCD: u16 = 0xC000;
+
+fn instructions(memory: &mut Memory, values: &[u8]) {
+    for &value in values {
+        memory.write(LCD + lcd::INSTRUCTION, value);
+    }
+}
+
+fn print(memory: &mut Memory, text: &str) {
+    for byte in text.bytes() {
+        memory.write(LCD + lcd::DATA, byte);
+    }
+}
+
+fn memory_mapped(columns: usize, rows: usize) -> (Memory, Rc<RefCell<Lcd>>) {
+    let lcd = Rc::new(RefCell::new(Lcd::new(columns, rows)));
+    let mut memory = Memory::new();
+    memory.attach(LCD, LCD + 1, lcd.clone());
+    // two lines, display and cursor on, clear
+    instructions(&mut memory, &[0x38, 0x0E, 0x06, 0x01]);
+    (memory, lcd)
+}
+
+#[test]
+fn memory_mapped_lcd_wraps_rows_and_reads_back_through_the_data_register() {
+    let (mut memory, lcd) = memory_mapped(20, 4);
+    print(&mut memory, "Row one, then the third row!");
+    instructions(&mut memory, &[0xC0]);
+    print(&mut memory, "Row two");
+
+    assert_eq!(lcd.borrow().text(), "Row one, then the th\nRow two             \nird row!            \n                    ");
+    assert_eq!(lcd.borrow().cursor_position(), Some((1, 7)));
+    assert_eq!(memory.peek(LCD + lcd::INSTRUCTION) & !BUSY_FLAG, 0x47);
+
+    // reading the data register moves the address counter too
+    instructions(&mut memory, &[0x83]);
+    assert_eq!(memory.peek(LCD + lcd::DATA), b' ');
+    assert_eq!([memory.read(LCD + lcd::DATA), memory.read(LCD + lcd::DATA)], [b' ', b'o']);
+    assert_eq!(memory.peek(LCD + lcd::INSTRUCTION) & !BUSY_FLAG, 0x05);
+}
+
+// D4-D7 on PB0-PB3, RS on PB4, RW on PB5, E on PB6
+fn pulse(via: &mut Via, lines: u8) {
+    via.write(via::ORB, lines);
+    via.write(via::ORB, lines | 0x40);
+    via.write(via::ORB, lines);
+}
+
+fn read_nibble(via: &mut Via) -> u8 {
+    via.write(via::ORB, 0x20);
+    via.write(via::ORB, 0x60);
+    let nibble = via.read(via::ORB) & 0x0F;
+    via.write(via::ORB, 0x20);
+    nibble
+}
+
+#[test]
+fn four_bit_lcd_on_a_via_port_sends_and_reads_nibbles() {
+    let lcd = Rc::new(RefCell::new(Lcd::default()));
+    let mut via = Via::new();
+    via.connect(LcdPins::new(lcd.clone(), BREADBOARD_4BIT_WIRING));
+    via.write(via::DDRB, 0x7F);
+
+    // the interface starts out 8-bit, so switching to 4-bit is a single transfer
+    pulse(&mut via, 0x02);
+    for (rs, byte) in [(0x00, 0x28), (0x00, 0x0C), (0x00, 0x06), (0x00, 0x01), (0x10, b'O'), (0x10, b'K')] {
+        pulse(&mut via, rs | (byte >> 4));
+        pulse(&mut via, rs | (byte & 0x0F));
+    }
+    assert_eq!(lcd.borrow().lines(), ["OK              ", "                "]);
+
+    // a status read in two halves, with the data lines as inputs
+    via.write(via::DDRB, 0x70);
+    via.tick(2_000);
+    assert_eq!((read_nibble(&mut via), read_nibble(&mut via)), (0x0, 0x2));
+}
+
+#[test]
+fn display_shifts_by_instruction_and_follows_the_cursor_in_entry_shift_mode() {
+    let (mut memory, lcd) = memory_mapped(8, 2);
+    print(&mut memory, "ABCDEFGHIJ");
+    assert_eq!(lcd.borrow().lines()[0], "ABCDEFGH");
+
+    // shift the display left twice, then right once; each line scrolls round its own 40 characters
+    instructions(&mut memory, &[0x18, 0x18, 0x1C]);
+    assert_eq!(lcd.borrow().lines()[0], "BCDEFGHI");
+    instructions(&mut memory, &[0x1C, 0x1C]);
+    assert_eq!(lcd.borrow().lines()[0], " ABCDEFG");
+    // moving the cursor leaves the text where it is
+    instructions(&mut memory, &[0x10]);
+    assert_eq!(lcd.borrow().address(), 9);
+
+    // with entry shift on the text scrolls under a cursor that stays put; home undoes it
+    instructions(&mut memory, &[0x02, 0x80 | 0x08, 0x07]);
+    print(&mut memory, "XY");
+    assert_eq!(lcd.borrow().lines()[0], "CDEFGHXY");
+    assert_eq!(lcd.borrow().cursor_position(), None);
+    instructions(&mut memory, &[0x02]);
+    assert_eq!(lcd.borrow().lines()[0], "ABCDEFGH");
+    assert_eq!(lcd.borrow().cursor_position(), Some((0, 0)));
+}
+
+#[test]
+fn custom_characters_busy_timing_and_state_round_trip() {
+    let (mut memory, lcd) = memory_mapped(16, 2);
+    lcd.borrow_mut().set_clock_hz(2_000_000);
+
+    // character 1 is a box; CGRAM only keeps five bits a row
+    let pattern = [0xFF, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F, 0x00];
+    instructions(&mut memory, &[0x48]);
+    for row in pattern {
+        memory.write(LCD + lcd::DATA, row);
+    }
+    instructions(&mut memory, &[0x80]);
+    memory.write(LCD + lcd::DATA, 0x01);
+    memory.write(LCD + lcd::DATA, 0x09);
+    assert_eq!(lcd.borrow().custom_char(9), [0x1F, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1F, 0x00]);
+    assert!(lcd.borrow().lines()[0].starts_with("▒▒ "));
+
+    // a data write takes 41 µs, 82 cycles at 2 MHz; clear takes 1.52 ms
+    memory.tick(81);
+    assert_eq!(memory.read(LCD + lcd::INSTRUCTION), BUSY_FLAG | 0x02);
+    memory.tick(1);
+    assert_eq!(memory.read(LCD + lcd::INSTRUCTION), 0x02);
+    let (saved, text) = (lcd.borrow().save_state(), lcd.borrow().text());
+    instructions(&mut memory, &[0x01]);
+    memory.tick(3_039);
+    assert!(lcd.borrow().is_busy());
+    memory.tick(1);
+    assert!(!lcd.borrow().is_busy());
+
+    let mut restored = Lcd::new(16, 2);
+    restored.load_state(&saved).unwrap();
+    assert_eq!((restored.text(), restored.cursor()), (text, Some(2)));
+    assert!(restored.load_state(&saved[1..]).is_err());
+}
+
+#[test]
+fn cursor_moves_while_cgram_is_selected_stay_inside_cgram() {
+    let (mut memory, lcd) = memory_mapped(16, 2);
+
+    // CGRAM address $3F, then a cursor shift right wraps to $00 rather than into DDRAM's range
+    instructions(&mut memory, &[0x7F, 0x14]);
+    assert_eq!(lcd.borrow().address(), 0x00);
+    memory.write(LCD + lcd::DATA, 0x15);
+    assert_eq!(lcd.borrow().custom_char(0)[0], 0x15);
+    instructions(&mut memory, &[0x10, 0x10]);
+    assert_eq!(lcd.borrow().address(), 0x3F);
+
+    // a saved counter past CGRAM is masked when loaded, so reading it back can't go out of range
+    let mut state = lcd.borrow().save_state();
+    state[0xC0] = 0x7F;
+    let mut restored = Lcd::new(16, 2);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.address(), 0x3F);
+    assert_eq!(restored.peek(lcd::DATA), 0x00);
+}
@@ -0,0 +1,84 @@
+use rust_6502_emulator::linker::{link, MemoryConfig};
+use rust_6502_emulator::loader::assemble_object;
+use rust_6502_emulator::object::ObjectFile;
+
+const CONFIG: &str = "
+MEMORY {
+    ZP:  start = $0000, size = $0100;
+    RAM: start = $0200, size = $0100;
+    ROM: start = $8000, size = $0010, fill = yes, fillval = $FF;
+}
+SEGMENTS {
+    ZEROPAGE: load = ZP,  type = zp;
+    CODE:     load = ROM, type = ro;
+    DATA:     load = RAM, type = rw;
+    BSS:      load = RAM, type = bss;
+}
+";
+
+#[test]
+fn link_places_segments_and_resolves_imports() {
+    let main = assemble_object(
+        ".import print\n.export start\nstart: LDA message\n    JSR print\n    JMP start\n.segment \"DATA\"\nmessage: .byte \"Hi\", 0\n",
+    )
+    .expect("Failed to assemble main");
+    let lib = assemble_object(".export print\nprint: RTS\n.segment \"BSS\"\nscratch: .res 4\n")
+        .expect("Failed to assemble lib");
+
+    let config = MemoryConfig::parse(CONFIG).expect("Failed to parse config");
+    let image = link(&[main, lib], &config).expect("Failed to link");
+
+    assert_eq!(image.symbols.get("start"), Some(0x8000));
+    assert_eq!(image.symbols.get("print"), Some(0x8009));
+
+    let rom = image.areas.iter().find(|area| area.name == "ROM").unwrap();
+    assert_eq!(rom.start, 0x8000);
+    assert_eq!(
+        rom.data,
+        vec![
+            0xAD, 0x00, 0x02, // LDA message
+            0x20, 0x09, 0x80, // JSR print
+            0x4C, 0x00, 0x80, // JMP start
+            0x60, // RTS
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ]
+    );
+
+    // BSS takes space in RAM but isn't written out
+    let ram = image.areas.iter().find(|area| area.name == "RAM").unwrap();
+    assert_eq!(ram.data, vec![b'H', b'i', 0]);
+}
+
+#[test]
+fn object_files_round_trip_through_bytes() {
+    let object = assemble_object(".import far\nloop: BNE loop\n    JMP far\n.word loop\n")
+        .expect("Failed to assemble");
+
+    assert_eq!(object.relocations.len(), 2);
+    assert_eq!(ObjectFile::from_bytes(&object.to_bytes()).unwrap(), object);
+}
+
+#[test]
+fn link_reports_unresolved_imports() {
+    let object = assemble_object(".import missing\nJSR missing\n").expect("Failed to assemble");
+    let config = MemoryConfig::parse(CONFIG).unwrap();
+
+    let err = link(&[object], &config).unwrap_err();
+    assert!(err.to_string().contains("missing"));
+}
+
+#[test]
+fn link_rejects_unplaced_segments_and_relocations_outside_their_area() {
+    let config = MemoryConfig::parse(CONFIG).unwrap();
+
+    // a label in a segment the config doesn't place has no address to resolve to
+    let object = assemble_object(".export nowhere\n.segment \"EXTRA\"\nnowhere:\n").expect("Failed to assemble");
+    let err = link(&[object], &config).unwrap_err();
+    assert_eq!(err.to_string(), "Segment EXTRA is missing from the memory config");
+
+    // a damaged object whose relocation points past the end of ROM
+    let mut object = assemble_object("loop: JMP loop\n").expect("Failed to assemble");
+    object.relocations[0].offset = 0x20;
+    let err = link(&[object], &config).unwrap_err();
+    assert_eq!(err.to_string(), "Relocation at $8020 falls outside memory area ROM");
+}
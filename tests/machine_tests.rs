@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+use rust_6502_emulator::devices::console::Console;
+use rust_6502_emulator::devices::random::Random;
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::framebuffer::Framebuffer;
+use rust_6502_emulator::machines::apple1::{self, PIA_START, ROM_START, ROM_SIZE};
+use rust_6502_emulator::machines::breadboard;
+use rust_6502_emulator::machines::console;
+use rust_6502_emulator::machines::easy6502::{self, KEY_ADDR, LOAD_ADDR, PALETTE, RANDOM_ADDR};
+use rust_6502_emulator::runner::{run, RunOptions, RunOutcome};
+use rust_6502_emulator::savestate::Snapshot;
+use rust_6502_emulator::{Memory, CPU};
+
+// LDA #$05 / STA $0200 / LDA #$02 / STA $05FF / LDA $FE / STA $10 / LDA $FF / STA $11
+const PROGRAM: [u8; 20] = [
+    0xA9, 0x05, 0x8D, 0x00, 0x02, 0xA9, 0x02, 0x8D, 0xFF, 0x05, 0xA5, 0xFE, 0x85, 0x10, 0xA5, 0xFF,
+    0x85, 0x11, 0x00, 0x00,
+];
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    easy6502::setup(&mut memory, Some(1234));
+    for (i, &byte) in PROGRAM.iter().enumerate() {
+        memory.write(LOAD_ADDR + i as u16, byte);
+    }
+    cpu.pc = LOAD_ADDR;
+    (cpu, memory)
+}
+
+#[test]
+fn easy6502_programs_draw_to_the_framebuffer_and_read_random_bytes_and_keys() {
+    let (mut cpu, mut memory) = setup();
+    let expected = Random::new(1234).read(0);
+    easy6502::press_key(&mut memory, b'w');
+    for _ in 0..8 {
+        cpu.step(&mut memory);
+    }
+
+    assert_eq!((memory.peek(0x0010), memory.peek(0x0011)), (expected, b'w'));
+    assert_eq!(memory.peek(KEY_ADDR), b'w');
+    assert_ne!(memory.peek(RANDOM_ADDR), expected);
+
+    let screen = easy6502::framebuffer(&memory);
+    assert_eq!((screen.width, screen.height), (32, 32));
+    assert_eq!(screen.pixel(0, 0), PALETTE[5]);
+    assert_eq!(screen.pixel(31, 31), PALETTE[2]);
+    assert_eq!(screen.pixel(1, 0), PALETTE[0]);
+}
+
+#[test]
+fn framebuffers_encode_as_ppm_and_png() {
+    let mut image = Framebuffer::new(2, 1);
+    image.set_pixel(1, 0, [0xFF, 0x80, 0x00]);
+
+    assert_eq!(image.to_ppm(), b"P6\n2 1\n255\n\x00\x00\x00\xFF\x80\x00");
+
+    let scaled = image.scaled(3);
+    assert_eq!((scaled.width, scaled.height, scaled.pixel(5, 2)), (6, 3, [0xFF, 0x80, 0x00]));
+
+    let png = image.to_png();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+    // the stored deflate block holds the filter byte and both pixels as they are
+    let idat = png.windows(4).position(|window| window == b"IDAT").unwrap();
+    assert_eq!(&png[idat + 11..idat + 18], &[0, 0, 0, 0, 0xFF, 0x80, 0x00]);
+    assert!(png.ends_with(b"IEND\xAE\x42\x60\x82"));
+}
+
+#[test]
+fn devices_keep_their_state_in_snapshots() {
+    let (mut cpu, mut memory) = setup();
+    let random = Rc::new(RefCell::new(Random::new(99)));
+    memory.attach(0x00FD, 0x00FD, random.clone());
+
+    let snapshot = Snapshot::capture(&cpu, &memory);
+    assert_eq!(snapshot.devices.iter().map(|device| device.name.as_str()).collect::<Vec<_>>(), ["random", "random"]);
+
+    let first = memory.read(0x00FD);
+    memory.read(0x00FD);
+    snapshot.restore(&mut cpu, &mut memory).unwrap();
+    assert_eq!(memory.read(0x00FD), first);
+    assert_eq!(random.borrow().peek(0), memory.peek(0x00FD));
+
+    // a machine without the devices can't take the snapshot
+    assert!(snapshot.restore(&mut cpu, &mut Memory::new()).is_err());
+
+    // nor can it take bad device data, and then nothing changes, not even the devices before it
+    let mut broken = snapshot.clone();
+    broken.devices[1].data.pop();
+    memory.read(RANDOM_ADDR);
+    memory.write(0x0300, 0x77);
+    cpu.a = 0x55;
+    let (before, random_before) = (memory.peek(RANDOM_ADDR), memory.peek(0x00FD));
+    assert!(broken.restore(&mut cpu, &mut memory).is_err());
+    assert_eq!((memory.peek(RANDOM_ADDR), memory.peek(0x00FD)), (before, random_before));
+    assert_eq!((memory.peek(0x0300), cpu.a), (0x77, 0x55));
+}
+
+// console output that the test can read back
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn console_machine_prints_reads_input_and_exits_with_a_status() {
+    // LDA #'H' / STA $F000 / LDA #'i' / STA $F000 / LDA $F001 / STA $F000 / LDA #$03 / STA $F002
+    let program = [
+        0xA9, 0x48, 0x8D, 0x00, 0xF0, 0xA9, 0x69, 0x8D, 0x00, 0xF0, 0xAD, 0x01, 0xF0, 0x8D, 0x00, 0xF0,
+        0xA9, 0x03, 0x8D, 0x02, 0xF0,
+    ];
+    let (keys, input) = mpsc::channel();
+    let output = Output::default();
+    keys.send(b'!').unwrap();
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let layout = console::setup(&mut memory, Console::new(output.clone(), input));
+    for (i, &byte) in program.iter().enumerate() {
+        memory.write(layout.load_addr + i as u16, byte);
+    }
+    cpu.pc = layout.load_addr;
+
+    let options = RunOptions { exit_port: layout.exit_port, ..RunOptions::default() };
+    let outcome = run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap();
+    assert_eq!((outcome, outcome.exit_code()), (RunOutcome::Exit(3), 3));
+    assert_eq!(output.0.borrow().as_slice(), b"Hi!");
+
+    // with the input used up, reads come back 0 until another byte arrives
+    assert_eq!(memory.read(console::INPUT_ADDR), 0);
+    keys.send(b'?').unwrap();
+    assert_eq!(memory.read(console::INPUT_ADDR), b'?');
+}
+
+#[test]
+fn apple1_boots_through_the_reset_vector_and_echoes_keys_through_the_pia() {
+    // set up the PIA as WozMon does, interrupts enabled included, print "\" and a carriage
+    // return, then echo every key
+    let mut rom = vec![
+        0xD8, 0x58, 0xA0, 0x7F, 0x8C, 0x12, 0xD0, 0xA9, 0xA7, 0x8D, 0x11, 0xD0, 0x8D, 0x13, 0xD0,
+        // LDX #0 / msg: LDA text,X / BEQ key / JSR echo / INX / BNE msg
+        0xA2, 0x00, 0xBD, 0x33, 0xFF, 0xF0, 0x06, 0x20, 0x2A, 0xFF, 0xE8, 0xD0, 0xF5,
+        // key: LDA KBDCR / BPL key / LDA KBD / JSR echo / JMP key
+        0xAD, 0x11, 0xD0, 0x10, 0xFB, 0xAD, 0x10, 0xD0, 0x20, 0x2A, 0xFF, 0x4C, 0x1C, 0xFF,
+        // echo: BIT DSP / BMI echo / STA DSP / RTS
+        0x2C, 0x12, 0xD0, 0x30, 0xFB, 0x8D, 0x12, 0xD0, 0x60,
+        // text
+        0xDC, 0x8D, 0x00,
+    ];
+    rom.resize(ROM_SIZE, 0x00);
+    rom[0xFC..0xFE].copy_from_slice(&[0x00, 0xFF]);
+    assert!(apple1::setup(&mut Memory::new(), rom[..0x80].to_vec(), Vec::new()).is_err());
+
+    let output = Output::default();
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let apple1 = apple1::setup(&mut memory, rom, output.clone()).unwrap();
+    cpu.reset_to_vector(&mut memory);
+    assert_eq!((cpu.pc, cpu.sp), (ROM_START, 0xFD));
+
+    let options = RunOptions { max_instructions: Some(200), ..RunOptions::default() };
+    assert_eq!(run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap(), RunOutcome::InstructionLimit(200));
+    assert_eq!(output.0.borrow().as_slice(), b"\\\r\n");
+
+    // the keyboard strobe raises the PIA's interrupt flag, but the IRQ line isn't connected
+    apple1.press_key(b'a');
+    run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap();
+    assert_eq!(output.0.borrow().as_slice(), b"\\\r\nA");
+    assert!((0xFF1C..0xFF33).contains(&cpu.pc));
+
+    // the ROM can't be written
+    memory.write(ROM_START, 0xEA);
+    assert_eq!(memory.peek(ROM_START), 0xD8);
+
+    // a key sets KBDCR bit 7 until KBD is read, which gives the upper-cased key with bit 7 set
+    apple1.press_key(b'b');
+    assert_eq!(memory.peek(PIA_START + 1) & 0x80, 0x80);
+    assert_eq!(memory.read(PIA_START), 0xC2);
+    assert_eq!(memory.peek(PIA_START + 1) & 0x80, 0x00);
+}
+
+#[test]
+fn breadboard_rom_drives_the_lcd_through_the_via() {
+    // set up the VIA and LCD, print "Hi", then read the LCD's address counter back into $00
+    let mut rom = vec![
+        0xA2, 0xFF, 0x9A, 0xA9, 0xFF, 0x8D, 0x02, 0x60, 0xA9, 0xE0, 0x8D, 0x03, 0x60, 0xA9, 0x38, 0x20,
+        0x42, 0x80, 0xA9, 0x0E, 0x20, 0x42, 0x80, 0xA9, 0x06, 0x20, 0x42, 0x80, 0xA9, 0x01, 0x20, 0x42,
+        0x80, 0xA9, 0x48, 0x20, 0x55, 0x80, 0xA9, 0x69, 0x20, 0x55, 0x80, 0xA9, 0x00, 0x8D, 0x02, 0x60,
+        0xA9, 0x40, 0x8D, 0x01, 0x60, 0xA9, 0xC0, 0x8D, 0x01, 0x60, 0xAD, 0x00, 0x60, 0x85, 0x00, 0x4C,
+        0x3F, 0x80,
+        // lcd_instruction: STA PORTB / E low, high, low with RS clear / RTS
+        0x8D, 0x00, 0x60, 0xA9, 0x00, 0x8D, 0x01, 0x60, 0xA9, 0x80, 0x8D, 0x01, 0x60, 0xA9, 0x00, 0x8D,
+        0x01, 0x60, 0x60,
+        // print_char: the same with RS set
+        0x8D, 0x00, 0x60, 0xA9, 0x20, 0x8D, 0x01, 0x60, 0xA9, 0xA0, 0x8D, 0x01, 0x60, 0xA9, 0x20, 0x8D,
+        0x01, 0x60, 0x60,
+    ];
+    rom.resize(breadboard::ROM_SIZE, 0xEA);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    let lcd = breadboard::setup(&mut memory, rom).unwrap();
+    cpu.reset_to_vector(&mut memory);
+    let options = RunOptions { stop_on_self_jump: true, max_cycles: Some(10_000), ..RunOptions::default() };
+    assert_eq!(run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap(), RunOutcome::JumpToSelf { pc: 0x803F });
+
+    assert_eq!(lcd.borrow().lines(), ["Hi              ", "                "]);
+    assert_eq!(lcd.borrow().cursor(), Some(2));
+    // read straight after the last character, so still busy; address counter 2
+    assert_eq!(memory.peek(0x0000), 0x82);
+    assert!(lcd.borrow().is_busy());
+    memory.tick(41);
+    assert!(!lcd.borrow().is_busy());
+}
@@ -0,0 +1,84 @@
+use std::io::Cursor;
+
+use rust_6502_emulator::monitor::Monitor;
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// JSR sub / LDX #$05 / JMP $0605 / sub: LDA #$42 / STA $10 / RTS
+const PROGRAM: [u8; 13] = [
+    0x20, 0x08, 0x06, 0xA2, 0x05, 0x4C, 0x05, 0x06, 0xA9, 0x42, 0x85, 0x10, 0x60,
+];
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    (cpu, memory)
+}
+
+fn run(monitor: &mut Monitor, cpu: &mut CPU, memory: &mut Memory, input: &str) -> String {
+    let mut out = Vec::new();
+    monitor.run(cpu, memory, Cursor::new(input), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn step_over_and_step_out_of_subroutines() {
+    let (mut cpu, mut memory) = setup();
+    let mut monitor = Monitor::new();
+
+    run(&mut monitor, &mut cpu, &mut memory, "o\n");
+    assert_eq!((cpu.pc, cpu.a, memory.peek(0x0010)), (0x0603, 0x42, 0x42));
+
+    let (mut cpu, mut memory) = setup();
+    run(&mut monitor, &mut cpu, &mut memory, "n 2\nout\n");
+    assert_eq!((cpu.pc, cpu.sp), (0x0603, 0xFF));
+
+    let (mut cpu, mut memory) = setup();
+    let output = run(&mut monitor, &mut cpu, &mut memory, "u 605\nb $0605\nc\n");
+    assert_eq!(cpu.pc, 0x0605);
+    assert!(output.contains("PC=$0605 A=$42 X=$05"), "{}", output);
+    assert!(output.contains("Hit breakpoint #1 at $0605"));
+}
+
+#[test]
+fn memory_dump_edit_fill_and_registers() {
+    let (mut cpu, mut memory) = setup();
+    let mut monitor = Monitor::new();
+
+    let output = run(&mut monitor, &mut cpu, &mut memory, "e 200 48 69 21\nf $0203 $0207 ff\nm 200 +8\nr x $7f\n");
+    assert!(output.contains("$0200  48 69 21 FF FF FF FF FF                          |Hi!.....|"), "{}", output);
+    assert_eq!(cpu.x, 0x7F);
+
+    let output = run(&mut monitor, &mut cpu, &mut memory, "r pc zz\nbogus\n");
+    assert!(output.contains("Invalid address: zz"));
+    assert!(output.contains("Unknown command 'bogus'"));
+}
+
+#[test]
+fn disassembles_and_repeats_the_last_command() {
+    let (mut cpu, mut memory) = setup();
+    let mut monitor = Monitor::new();
+
+    let output = run(&mut monitor, &mut cpu, &mut memory, "d 600 3\n\n");
+    assert!(output.contains("> $0600  20 08 06  JSR $0608"), "{}", output);
+    assert!(output.contains("  $0605  4C 05 06  JMP $0605"));
+    // the empty line carried on from where the first listing ended
+    assert!(output.contains("  $0608  A9 42     LDA #$42"));
+
+    run(&mut monitor, &mut cpu, &mut memory, "n\n\n!!\n");
+    assert_eq!(cpu.pc, 0x060C);
+    assert_eq!(monitor.history(), ["d 600 3", "d", "n"]);
+}
+
+#[test]
+fn stepping_onto_an_illegal_opcode_reports_it_instead_of_running_it() {
+    // LDA #$01 / an undocumented opcode
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&[0xA9, 0x01, 0x02]));
+    let mut monitor = Monitor::new();
+
+    let output = run(&mut monitor, &mut cpu, &mut memory, "n 3\nc\n");
+    assert_eq!(output.matches("Illegal opcode $02 at $0602").count(), 2, "{}", output);
+    assert_eq!((cpu.pc, cpu.a), (0x0602, 0x01));
+}
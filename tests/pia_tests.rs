@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust_6502_emulator::devices::pia::{self, Pia};
+use rust_6502_emulator::devices::via::Pins;
+use rust_6502_emulator::devices::Device;
+
+// puts fixed levels on the inputs and records what CB2 does
+#[derive(Default)]
+struct Lines {
+    inputs: (u8, u8),
+    cb2: Vec<bool>,
+}
+
+impl Pins for Lines {
+    fn drive(&mut self, _port_a: u8, _port_b: u8) {}
+
+    fn sense(&mut self) -> (u8, u8) {
+        self.inputs
+    }
+
+    fn cb2_out(&mut self, level: bool) {
+        self.cb2.push(level);
+    }
+}
+
+#[test]
+fn control_bit_2_switches_between_ddr_and_port_and_reads_clear_the_flags() {
+    let lines = Rc::new(RefCell::new(Lines { inputs: (0x3C, 0x0F), ..Lines::default() }));
+    let mut pia = Pia::new();
+    pia.connect(lines.clone());
+
+    pia.write(pia::PORT_B, 0xF0);
+    pia.write(pia::CRB, 0x04);
+    pia.write(pia::PORT_B, 0xA5);
+    assert_eq!(pia.read(pia::PORT_B), 0xAF);
+    pia.write(pia::CRB, 0x00);
+    assert_eq!(pia.read(pia::PORT_B), 0xF0);
+
+    // CA1 on a rising edge, with its interrupt enabled
+    pia.write(pia::CRA, 0x07);
+    pia.set_ca1(false);
+    assert!(!pia.irq());
+    pia.set_ca1(true);
+    assert!(pia.irq());
+    // the flags are read-only
+    pia.write(pia::CRA, 0x06);
+    assert_eq!(pia.peek(pia::CRA), 0x86);
+    assert!(!pia.irq());
+
+    assert_eq!(pia.read(pia::PORT_A), 0x3C);
+    assert_eq!(pia.peek(pia::CRA), 0x06);
+
+    let state = pia.save_state();
+    let mut restored = Pia::new();
+    restored.load_state(&state).unwrap();
+    assert_eq!((restored.peek(pia::CRA), restored.port_b()), (0x06, 0xAF));
+}
+
+#[test]
+fn writes_to_port_b_drive_cb2_in_handshake_pulse_and_manual_modes() {
+    let lines = Rc::new(RefCell::new(Lines::default()));
+    let mut pia = Pia::new();
+    pia.connect(lines.clone());
+
+    // handshake: low on the write, high again on CB1's active edge
+    pia.write(pia::CRB, 0x24);
+    pia.write(pia::PORT_B, b'A');
+    pia.set_cb1(false);
+    assert_eq!(lines.borrow().cb2, [false, true]);
+    assert_eq!(pia.peek(pia::CRB) & 0x80, 0x80);
+
+    // pulse: low then straight back high
+    pia.write(pia::CRB, 0x2C);
+    pia.write(pia::PORT_B, b'B');
+    assert_eq!(lines.borrow().cb2[2..], [false, true]);
+
+    // manual: bit 3 is the level
+    pia.write(pia::CRB, 0x34);
+    pia.write(pia::CRB, 0x3C);
+    assert_eq!(lines.borrow().cb2[4..], [false, true]);
+}
@@ -0,0 +1,99 @@
+use rust_6502_emulator::debugger::MinCPU;
+use rust_6502_emulator::loader::assemble_named;
+use rust_6502_emulator::profiler::{FunctionStats, Profiler};
+use rust_6502_emulator::runner::{run, RunOptions};
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+const SOURCE: &str = "\
+main:
+    JSR outer
+    JSR leaf
+done:
+    JMP done
+outer:
+    JSR leaf
+    LDA #$01
+    RTS
+leaf:
+    LDA #$02
+    STA $00
+    RTS
+";
+
+fn profile() -> (CPU, Memory) {
+    let assembly = assemble_named(SOURCE, "prog.asm").unwrap();
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&assembly.bytes));
+    cpu.debugger.symbols = assembly.symbols;
+    cpu.debugger.profiler = Some(Profiler::new());
+    let options = RunOptions { stop_on_self_jump: true, ..RunOptions::default() };
+    run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap();
+    (cpu, memory)
+}
+
+#[test]
+fn profile_counts_instructions_and_charges_subroutines() {
+    let (cpu, _) = profile();
+    let profiler = cpu.debugger.profiler.as_ref().unwrap();
+    assert_eq!((profiler.instructions(), profiler.total_cycles()), (12, 51));
+    // LDA #$02 in leaf ran twice, two cycles each
+    assert_eq!(profiler.at(0x060F), (2, 4));
+    // leaf's RTS is hottest; ties go in address order
+    assert_eq!(profiler.hot_spots()[..3], [0x0613, 0x0600, 0x0603]);
+
+    let stats = |addr| profiler.functions()[&addr];
+    assert_eq!(stats(0x0600), FunctionStats { calls: 1, inclusive: 51, exclusive: 15 });
+    assert_eq!(stats(0x0609), FunctionStats { calls: 1, inclusive: 25, exclusive: 14 });
+    assert_eq!(stats(0x060F), FunctionStats { calls: 2, inclusive: 22, exclusive: 22 });
+
+    assert_eq!(
+        profiler.collapsed_stacks(&cpu.debugger.symbols),
+        "main 15\nmain;leaf 11\nmain;outer 14\nmain;outer;leaf 11\n"
+    );
+}
+
+#[test]
+fn report_annotates_hot_spots_with_disassembly_and_labels() {
+    let (cpu, memory) = profile();
+    let mut report = Vec::new();
+    cpu.debugger.profiler.as_ref().unwrap().write_report(&memory, &cpu.debugger.symbols, 3, &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+
+    assert!(report.starts_with("12 instructions, 51 cycles, 0 interrupts\n"));
+    assert!(report.contains("        12  23.5%          2  $0613    RTS           leaf+4\n"));
+    assert!(report.contains("  $0603    JSR $060F     main+3\n"));
+    // three rows in each table, under a header line
+    assert_eq!(report.lines().filter(|line| line.contains('%')).count(), 8);
+    assert!(report.contains("        51 100.0%         15  29.4%        1  main\n"));
+}
+
+// the CPU after a step, as far as the profiler cares
+fn after(pc: u16, sp: u8) -> MinCPU {
+    MinCPU { pc, sp, ..MinCPU::new() }
+}
+
+#[test]
+fn recursion_early_returns_and_interrupts_keep_the_stack_straight() {
+    let mut profiler = Profiler::new();
+    // the root calls $1000, which calls itself
+    profiler.record(0x0600, 0x20, false, 6, &after(0x1000, 0xFD));
+    profiler.record(0x1000, 0x20, false, 6, &after(0x1000, 0xFB));
+    // an IRQ lands in the inner call, and its handler returns
+    profiler.record(0x1000, 0x00, true, 7, &after(0x2000, 0xF8));
+    profiler.record(0x2000, 0x40, false, 6, &after(0x1000, 0xFB));
+    // the inner call drops its return address and the RTS goes straight back to the root
+    profiler.record(0x1000, 0x60, false, 6, &after(0x0603, 0xFF));
+    profiler.record(0x0603, 0xEA, false, 2, &after(0x0604, 0xFF));
+
+    let stats = profiler.functions();
+    // recursion counts once towards inclusive cycles
+    assert_eq!(stats[&0x1000], FunctionStats { calls: 2, inclusive: 25, exclusive: 12 });
+    assert_eq!(stats[&0x2000], FunctionStats { calls: 1, inclusive: 13, exclusive: 13 });
+    assert_eq!(stats[&0x0600], FunctionStats { calls: 1, inclusive: 33, exclusive: 8 });
+    assert_eq!((profiler.instructions(), profiler.total_cycles()), (5, 33));
+    assert_eq!(
+        profiler.collapsed_stacks(&Default::default()),
+        "$0600 8\n$0600;$1000 6\n$0600;$1000;$1000 6\n$0600;$1000;$1000;$2000 13\n"
+    );
+}
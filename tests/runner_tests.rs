@@ -0,0 +1,123 @@
+use std::fs;
+
+use rust_6502_emulator::debugger::BreakOn;
+use rust_6502_emulator::monitor::parse_range;
+use rust_6502_emulator::runner::{load_program_file, run, RunOptions, RunOutcome, EXIT_ERROR, EXIT_ILLEGAL_OPCODE, EXIT_LIMIT, EXIT_PROGRAM_MAX};
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// LDX #$07 / STX $10 / loop: JMP loop
+const PROGRAM: [u8; 7] = [0xA2, 0x07, 0x86, 0x10, 0x4C, 0x04, 0x06];
+
+fn setup() -> (CPU, Memory) {
+    load(&PROGRAM)
+}
+
+fn load(program: &[u8]) -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(program));
+    (cpu, memory)
+}
+
+fn run_with(program: &[u8], options: RunOptions) -> RunOutcome {
+    let (mut cpu, mut memory) = load(program);
+    run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap()
+}
+
+#[test]
+fn run_stops_at_a_breakpoint_and_traces_each_instruction() {
+    let (mut cpu, mut memory) = setup();
+    cpu.debugger.add_breakpoint(BreakOn::Address(0x0604), None);
+
+    let mut trace = Vec::new();
+    let options = RunOptions { trace: true, ..RunOptions::default() };
+    let outcome = run(&mut cpu, &mut memory, &options, &mut trace).unwrap();
+
+    assert_eq!(outcome, RunOutcome::Breakpoint { id: 1, pc: 0x0604 });
+    assert_eq!(outcome.exit_code(), 0);
+    assert_eq!(memory.peek(0x0010), 0x07);
+
+    let trace = String::from_utf8(trace).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("$0602  86 10     STX $10"));
+}
+
+#[test]
+fn run_gives_up_when_the_cycle_budget_runs_out() {
+    let (mut cpu, mut memory) = setup();
+
+    let options = RunOptions { max_cycles: Some(100), ..RunOptions::default() };
+    let outcome = run(&mut cpu, &mut memory, &options, &mut Vec::new()).unwrap();
+
+    // LDX 2 + STX 3 + 32 JMPs at 3 cycles each
+    assert_eq!(outcome, RunOutcome::CycleLimit(101));
+    assert_eq!(outcome.exit_code(), EXIT_LIMIT);
+    assert_eq!(outcome.to_string(), "Cycle limit reached after 101 cycles");
+}
+
+#[test]
+fn each_stop_condition_ends_the_run_with_its_own_outcome() {
+    let outcome = run_with(&PROGRAM, RunOptions { stop_on_self_jump: true, ..RunOptions::default() });
+    assert_eq!(outcome, RunOutcome::JumpToSelf { pc: 0x0604 });
+
+    let outcome = run_with(&PROGRAM, RunOptions { stop_at: Some(0x0604), ..RunOptions::default() });
+    assert_eq!(outcome, RunOutcome::ReachedPc(0x0604));
+
+    let outcome = run_with(&PROGRAM, RunOptions { max_instructions: Some(5), ..RunOptions::default() });
+    assert_eq!((outcome, outcome.exit_code()), (RunOutcome::InstructionLimit(5), EXIT_LIMIT));
+
+    // the STX to the exit port ends the run with X as the status
+    let outcome = run_with(&PROGRAM, RunOptions { exit_port: Some(0x0010), ..RunOptions::default() });
+    assert_eq!((outcome, outcome.exit_code()), (RunOutcome::Exit(0x07), 7));
+
+    // a program's status can't be mistaken for one of the emulator's
+    let codes = [0, 1, 2, 3, 252, 253, 254, 255].map(|value| RunOutcome::Exit(value).exit_code());
+    assert_eq!(codes, [0, 1, 2, 3, 252, 252, 252, 252]);
+    assert!([EXIT_ILLEGAL_OPCODE, EXIT_LIMIT, EXIT_ERROR].iter().all(|&code| code > EXIT_PROGRAM_MAX && code <= 255));
+
+    // LDX #$07 / BRK, then one of the undocumented opcodes
+    let outcome = run_with(&[0xA2, 0x07, 0x00, 0x00, 0x02], RunOptions { stop_on_brk: true, ..RunOptions::default() });
+    assert_eq!((outcome, outcome.exit_code()), (RunOutcome::Brk { pc: 0x0602 }, 0));
+
+    let outcome = run_with(&[0xA2, 0x07, 0x02], RunOptions::default());
+    assert_eq!(outcome, RunOutcome::IllegalOpcode { pc: 0x0602, opcode: 0x02 });
+    assert_eq!(outcome.exit_code(), EXIT_ILLEGAL_OPCODE);
+}
+
+#[test]
+fn brk_pushes_the_return_address_and_jumps_through_the_irq_vector() {
+    let (mut cpu, mut memory) = load(&[0x00, 0xFF, 0xA2, 0x07]);
+    memory.write(0xFFFE, 0x00);
+    memory.write(0xFFFF, 0x80);
+
+    cpu.execute(&mut memory);
+    assert_eq!((cpu.pc, cpu.sp, cpu.cycles), (0x8000, 0xFC, 7));
+    assert_eq!((memory.peek(0x01FF), memory.peek(0x01FE)), (0x06, 0x02));
+    assert_eq!(memory.peek(0x01FD) & 0b0001_0000, 0b0001_0000);
+}
+
+#[test]
+fn programs_load_from_source_or_binary_files() {
+    let dir = std::env::temp_dir();
+    let source = dir.join(format!("runner_test_{}.asm", std::process::id()));
+    let binary = dir.join(format!("runner_test_{}.bin", std::process::id()));
+    fs::write(&source, "    LDX #$07\n    STX $10\nloop:\n    JMP loop\n").unwrap();
+    fs::write(&binary, PROGRAM).unwrap();
+
+    let (mut cpu, mut memory) = (CPU::new(), Memory::new());
+    load_program_file(&mut cpu, &mut memory, &source.to_string_lossy(), 0x1000).unwrap();
+    assert_eq!((cpu.pc, memory.peek(0x0604)), (0x0600, 0x4C));
+    assert!(cpu.debugger.source_map.location(0x0604).is_some_and(|location| location.line == 4));
+
+    let (mut cpu, mut memory) = (CPU::new(), Memory::new());
+    load_program_file(&mut cpu, &mut memory, &binary.to_string_lossy(), 0x1000).unwrap();
+    assert_eq!((cpu.pc, memory.peek(0x1004)), (0x1000, 0x4C));
+
+    fs::remove_file(source).ok();
+    fs::remove_file(binary).ok();
+
+    assert_eq!(parse_range("$0200-$02FF"), Ok((0x0200, 0x02FF)));
+    assert_eq!(parse_range("0x10+4"), Ok((0x0010, 0x0013)));
+    assert!(parse_range("$0300-$0200").is_err());
+}
@@ -0,0 +1,94 @@
+use rust_6502_emulator::cpu::{IRQ_VECTOR, NMI_VECTOR};
+use rust_6502_emulator::savestate::{DeviceState, Snapshot};
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// LDX #$07 / STX $10 / loop: JMP loop
+const PROGRAM: [u8; 7] = [0xA2, 0x07, 0x86, 0x10, 0x4C, 0x04, 0x06];
+
+fn setup() -> (CPU, Memory) {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    (cpu, memory)
+}
+
+fn run(cpu: &mut CPU, memory: &mut Memory, steps: usize) {
+    for _ in 0..steps {
+        cpu.execute(memory);
+    }
+}
+
+#[test]
+fn restoring_a_snapshot_returns_to_the_same_point() {
+    let (mut cpu, mut memory) = setup();
+    run(&mut cpu, &mut memory, 1);
+    cpu.set_irq(true);
+    let bookmark = Snapshot::capture(&cpu, &memory);
+
+    cpu.set_irq(false);
+    run(&mut cpu, &mut memory, 10);
+    // LDX 2, STX 3, then ten JMPs at 3 each
+    assert_eq!(cpu.cycles, 2 + 3 + 9 * 3);
+    assert_eq!(memory.peek(0x0010), 0x07);
+
+    bookmark.restore(&mut cpu, &mut memory).unwrap();
+    assert_eq!((cpu.x, cpu.pc, cpu.cycles, cpu.irq_pending), (0x07, 0x0602, 2, true));
+    assert_eq!(memory.peek(0x0010), 0x00);
+    assert!(cpu.debugger.history.is_empty());
+}
+
+#[test]
+fn save_state_files_round_trip_and_reject_bad_input() {
+    let (mut cpu, mut memory) = setup();
+    run(&mut cpu, &mut memory, 4);
+    cpu.nmi();
+
+    let mut snapshot = Snapshot::capture(&cpu, &memory);
+    let path = std::env::temp_dir().join(format!("savestate_test_{}.sav", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    snapshot.save(&path).unwrap();
+
+    let loaded = Snapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(loaded, snapshot);
+    assert!(loaded.cpu.nmi_pending && !loaded.cpu.irq_pending);
+
+    let mut bytes = snapshot.to_bytes();
+    bytes[8] = 99;
+    assert!(Snapshot::from_bytes(&bytes).unwrap_err().to_string().contains("version 99"));
+    assert!(Snapshot::from_bytes(&snapshot.to_bytes()[..100]).is_err());
+    assert!(Snapshot::from_bytes(b"not a save state").is_err());
+
+    // state for a device that isn't attached can't be restored
+    snapshot.devices.push(DeviceState { name: String::from("via"), data: vec![1, 2, 3] });
+    let reloaded = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    assert_eq!(reloaded.devices, snapshot.devices);
+    assert!(reloaded.restore(&mut cpu, &mut memory).is_err());
+}
+
+#[test]
+fn interrupts_push_state_and_jump_through_vectors() {
+    let (mut cpu, mut memory) = setup();
+    memory.write(IRQ_VECTOR, 0x00);
+    memory.write(IRQ_VECTOR + 1, 0x80);
+    memory.write(NMI_VECTOR, 0x00);
+    memory.write(NMI_VECTOR + 1, 0x90);
+
+    cpu.set_irq(true);
+    cpu.status = cpu.status | 0b0000_0100;
+    cpu.execute(&mut memory);
+    // masked: the LDX ran instead
+    assert_eq!(cpu.pc, 0x0602);
+
+    cpu.status = 0;
+    let cycles = cpu.cycles;
+    cpu.execute(&mut memory);
+    assert_eq!((cpu.pc, cpu.sp, cpu.cycles - cycles), (0x8000, 0xFC, 7));
+    assert_eq!((memory.peek(0x01FF), memory.peek(0x01FE)), (0x06, 0x02));
+    assert_eq!(memory.peek(0x01FD) & 0b0011_0000, 0b0010_0000);
+
+    // still asserted, but the I flag now masks it; an NMI can't be masked
+    cpu.nmi();
+    cpu.execute(&mut memory);
+    assert_eq!((cpu.pc, cpu.nmi_pending), (0x9000, false));
+}
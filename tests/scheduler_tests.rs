@@ -0,0 +1,70 @@
+use rust_6502_emulator::runner::{RunOptions, RunOutcome};
+use rust_6502_emulator::scheduler::{parse_clock, run_scheduled, Scheduler};
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// LDX #$07 / STX $10 / loop: JMP loop
+const PROGRAM: [u8; 7] = [0xA2, 0x07, 0x86, 0x10, 0x4C, 0x04, 0x06];
+
+#[test]
+fn clock_rates_parse_with_units() {
+    assert_eq!(parse_clock("1MHz"), Ok(Some(1_000_000)));
+    assert_eq!(parse_clock("1.79mhz"), Ok(Some(1_790_000)));
+    assert_eq!(parse_clock("500khz"), Ok(Some(500_000)));
+    assert_eq!(parse_clock("2000000"), Ok(Some(2_000_000)));
+    assert_eq!(parse_clock("unlimited"), Ok(None));
+    assert!(parse_clock("fast").is_err());
+}
+
+#[test]
+fn frames_get_the_clocks_share_of_cycles() {
+    let mut scheduler = Scheduler::new(Some(1_000_000), 60);
+    assert_eq!(scheduler.frame_cycles(), 16_667);
+
+    scheduler.set_speed(4.0);
+    assert_eq!(scheduler.frame_cycles(), 66_667);
+
+    scheduler.pause();
+    assert_eq!(scheduler.frame_cycles(), 0);
+    scheduler.resume();
+    assert!(!scheduler.is_paused());
+}
+
+#[test]
+fn scheduled_runs_go_a_frame_at_a_time_until_a_stop_condition() {
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+
+    // 300 cycles a frame at 1 kHz frames
+    let mut scheduler = Scheduler::new(Some(300_000), 1000);
+    let options = RunOptions { max_cycles: Some(1_000), ..RunOptions::default() };
+    let mut frames = Vec::new();
+    let outcome = run_scheduled(&mut cpu, &mut memory, &options, &mut scheduler, &mut Vec::new(), |cpu, _, _| {
+        frames.push(cpu.cycles);
+        Ok(true)
+    })
+    .unwrap();
+
+    // frames stop at the first instruction boundary past each 300 cycle slice
+    assert_eq!(outcome, Some(RunOutcome::CycleLimit(1_001)));
+    assert_eq!(frames, [302, 602, 902, 1_001]);
+
+    // the instruction budget carries across frames: LDX and STX, then a JMP every 3 cycles
+    let mut scheduler = Scheduler::new(Some(300_000), 1000);
+    let options = RunOptions { max_instructions: Some(250), ..RunOptions::default() };
+    let mut frames = 0;
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&PROGRAM));
+    let start = cpu.cycles;
+    let outcome = run_scheduled(&mut cpu, &mut memory, &options, &mut scheduler, &mut Vec::new(), |_, _, _| {
+        frames += 1;
+        Ok(true)
+    })
+    .unwrap();
+    assert_eq!(outcome, Some(RunOutcome::InstructionLimit(250)));
+    assert_eq!((frames, cpu.cycles - start), (3, 2 + 3 + 248 * 3));
+
+    // a frame callback can end the run
+    let mut scheduler = Scheduler::new(None, 1000);
+    let outcome = run_scheduled(&mut cpu, &mut memory, &RunOptions::default(), &mut scheduler, &mut Vec::new(), |_, _, _| Ok(false));
+    assert_eq!(outcome.unwrap(), None);
+}
@@ -0,0 +1,40 @@
+use rust_6502_emulator::framebuffer::Framebuffer;
+use rust_6502_emulator::terminal::{render_half_blocks, render_text_panel};
+
+#[test]
+fn half_blocks_pair_rows_and_only_change_color_when_needed() {
+    // 3x3: a white top-left pixel and a red bottom row, which pairs with black below it
+    let mut image = Framebuffer::new(3, 3);
+    image.set_pixel(0, 0, [255, 255, 255]);
+    for x in 0..3 {
+        image.set_pixel(x, 2, [136, 0, 0]);
+    }
+
+    let rendered = render_half_blocks(&image);
+    let lines: Vec<&str> = rendered.split("\r\n").collect();
+    assert_eq!(
+        lines,
+        [
+            "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}\u{2580}\x1b[0m",
+            "\x1b[38;2;136;0;0m\x1b[48;2;0;0;0m\u{2580}\u{2580}\u{2580}\x1b[0m",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn text_panels_frame_lines_padded_to_the_widest() {
+    let rendered = render_text_panel(&["Hi".to_string(), "\u{FF71}!".to_string(), "".to_string()]);
+    let lines: Vec<&str> = rendered.split("\r\n").collect();
+    assert_eq!(
+        lines,
+        [
+            "\u{250C}\u{2500}\u{2500}\u{2500}\u{2500}\u{2510}",
+            "\u{2502}\x1b[30;42m Hi \x1b[0m\u{2502}",
+            "\u{2502}\x1b[30;42m \u{FF71}! \x1b[0m\u{2502}",
+            "\u{2502}\x1b[30;42m    \x1b[0m\u{2502}",
+            "\u{2514}\u{2500}\u{2500}\u{2500}\u{2500}\u{2518}",
+            "",
+        ]
+    );
+}
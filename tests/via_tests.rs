@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rust_6502_emulator::cpu::IRQ_VECTOR;
+use rust_6502_emulator::devices::via::{self, Pins, Via, IRQ_SR, IRQ_T1, IRQ_T2};
+use rust_6502_emulator::devices::Device;
+use rust_6502_emulator::{load_program, Memory, ProgramSource, CPU};
+
+// remembers what the VIA drives and what CB2 shifted out, and puts fixed levels on the inputs
+#[derive(Default)]
+struct Board {
+    driven: Vec<(u8, u8)>,
+    shifted: Vec<bool>,
+    inputs: (u8, u8),
+}
+
+impl Pins for Board {
+    fn drive(&mut self, port_a: u8, port_b: u8) {
+        self.driven.push((port_a, port_b));
+    }
+
+    fn sense(&mut self) -> (u8, u8) {
+        self.inputs
+    }
+
+    fn cb2_out(&mut self, level: bool) {
+        self.shifted.push(level);
+    }
+}
+
+fn connected() -> (Via, Rc<RefCell<Board>>) {
+    let board = Rc::new(RefCell::new(Board::default()));
+    let mut via = Via::new();
+    via.connect(board.clone());
+    (via, board)
+}
+
+#[test]
+fn ports_drive_outputs_and_read_inputs_through_the_direction_registers() {
+    let (mut via, board) = connected();
+    board.borrow_mut().inputs = (0x5A, 0x0F);
+
+    via.write(via::DDRB, 0xF0);
+    via.write(via::ORB, 0xA5);
+    via.write(via::DDRA, 0xFF);
+    via.write(via::ORA, 0x42);
+
+    // undriven pins float high
+    assert_eq!(board.borrow().driven.last(), Some(&(0x42, 0xAF)));
+    // outputs read back what they drive, inputs what is on the pin
+    assert_eq!(via.read(via::ORB), 0xAF);
+    assert_eq!(via.read(via::ORA), 0x42);
+    via.write(via::DDRA, 0x00);
+    assert_eq!(via.read(via::ORA_NO_HANDSHAKE), 0x5A);
+    assert_eq!(via.read(via::DDRB), 0xF0);
+}
+
+#[test]
+fn one_shot_timer1_interrupts_once_and_free_running_repeats_every_latch_plus_two() {
+    let mut via = Via::new();
+    via.write(via::IER, 0x80 | IRQ_T1);
+    via.write(via::T1C_L, 10);
+    via.write(via::T1C_H, 0);
+
+    via.tick(10);
+    assert_eq!(via.peek(via::T1C_L), 0);
+    assert!(!via.irq());
+    via.tick(1);
+    assert!(via.irq());
+    assert_eq!(via.peek(via::IFR), 0x80 | IRQ_T1);
+
+    // reading the low counter acknowledges it, and a one-shot stays quiet as it wraps
+    via.read(via::T1C_L);
+    via.tick(0x1_0000);
+    assert!(!via.irq());
+
+    // free-running with PB7 output: a square wave with a 12-cycle half period
+    via.write(via::ACR, 0xC0);
+    via.write(via::T1C_H, 0);
+    assert_eq!(via.port_b() & 0x80, 0);
+    via.tick(11);
+    assert!(via.irq());
+    assert_eq!(via.port_b() & 0x80, 0x80);
+    via.read(via::T1C_L);
+    via.tick(12);
+    assert!(via.irq());
+    assert_eq!(via.port_b() & 0x80, 0);
+    assert_eq!(via.peek(via::T1C_L), 0xFF);
+}
+
+#[test]
+fn timer2_and_the_shift_register_set_flags_that_ier_can_mask() {
+    let (mut via, board) = connected();
+    via.write(via::T2C_L, 4);
+    via.write(via::T2C_H, 0);
+    via.tick(5);
+    assert_eq!(via.peek(via::IFR), IRQ_T2);
+    assert!(!via.irq());
+
+    // writing IER without bit 7 clears bits; writing IFR clears the flags written
+    via.write(via::IER, 0x80 | IRQ_T2 | IRQ_SR);
+    assert!(via.irq());
+    via.write(via::IER, IRQ_T2);
+    assert!(!via.irq());
+    assert_eq!(via.peek(via::IER), 0x80 | IRQ_SR);
+    via.write(via::IFR, IRQ_T2);
+    assert_eq!(via.peek(via::IFR), 0);
+
+    // shift out under the system clock: a bit every two cycles, most significant first
+    via.write(via::ACR, 0x18);
+    via.write(via::SR, 0b1011_0001);
+    via.tick(15);
+    assert_eq!(via.peek(via::IFR), 0);
+    via.tick(1);
+    assert_eq!(via.peek(via::IFR), 0x80 | IRQ_SR);
+    let bits: Vec<u8> = board.borrow().shifted.iter().map(|&bit| bit as u8).collect();
+    assert_eq!(bits, [1, 0, 1, 1, 0, 0, 0, 1]);
+    // shifting out recirculates
+    assert_eq!(via.peek(via::SR), 0b1011_0001);
+}
+
+#[test]
+fn via_state_round_trips_and_its_timer_interrupts_the_cpu() {
+    // LDA #$40 / STA $6004 / LDA #$00 / STA $6005 / loop: JMP loop
+    let program = [0xA9, 0x40, 0x8D, 0x04, 0x60, 0xA9, 0x00, 0x8D, 0x05, 0x60, 0x4C, 0x0A, 0x06];
+    let mut cpu = CPU::new();
+    let mut memory = Memory::new();
+    load_program(&mut cpu, &mut memory, ProgramSource::Borrowed(&program));
+    let mut timer = Via::new();
+    timer.write(via::IER, 0x80 | IRQ_T1);
+    memory.attach(0x6000, 0x600F, timer);
+    memory.write(IRQ_VECTOR, 0x00);
+    memory.write(IRQ_VECTOR + 1, 0x07);
+
+    let mut steps = 0;
+    while cpu.pc != 0x0700 && steps < 100 {
+        cpu.execute(&mut memory);
+        steps += 1;
+    }
+    assert_eq!(cpu.pc, 0x0700);
+
+    let state = memory.devices().next().unwrap().save_state();
+    let mut restored = Via::new();
+    restored.load_state(&state).unwrap();
+    assert!(restored.irq());
+    assert_eq!(restored.peek(via::T1L_L), 0x40);
+    assert!(restored.load_state(&state[1..]).is_err());
+}